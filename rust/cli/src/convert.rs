@@ -0,0 +1,25 @@
+use cip25_lib::metadata_json::{metadatum_to_json, MetadataJsonSchema};
+use cip25_lib::{LabelMetadata, TransactionMetadatum};
+
+// The conversions are LabelMetadata::convert_to_v1 / convert_to_v2 of the library.
+
+pub fn to_v2(label_metadata: LabelMetadata) -> Result<LabelMetadata, String> {
+    match label_metadata {
+        LabelMetadata::Unknown { version, .. } => Err(unsupported_version(&version)),
+        other => other.convert_to_v2().map(LabelMetadata::new_label_metadata_v2).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn to_v1(label_metadata: LabelMetadata) -> Result<LabelMetadata, String> {
+    match label_metadata {
+        LabelMetadata::Unknown { version, .. } => Err(unsupported_version(&version)),
+        other => other.convert_to_v1().map(LabelMetadata::new_label_metadata_v1).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn unsupported_version(version: &TransactionMetadatum) -> String {
+    match metadatum_to_json(version, MetadataJsonSchema::NoSchema) {
+        Ok(json) => format!("unsupported CIP-25 version {}", json),
+        Err(_) => format!("unsupported CIP-25 version {:?}", version),
+    }
+}
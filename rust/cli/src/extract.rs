@@ -0,0 +1,13 @@
+use cip25_lib::transaction::{extract_from_auxiliary_data, extract_from_transaction};
+use cip25_lib::Metadata;
+
+// The input can be either a whole transaction or just its auxiliary data
+pub fn extract_metadata(bytes: Vec<u8>) -> Result<Metadata, String> {
+    let metadata = match extract_from_transaction(bytes.clone()) {
+        Ok(transaction) => transaction.metadata,
+        Err(transaction_error) => extract_from_auxiliary_data(bytes).map_err(|auxiliary_data_error| {
+            format!("input is neither a transaction ({}) nor auxiliary data ({})", transaction_error, auxiliary_data_error)
+        })?,
+    };
+    metadata.ok_or_else(|| String::from("no 721 metadata found"))
+}
@@ -0,0 +1,126 @@
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use cip25_lib::json::{FromJson, JsonFormat, ToJson};
+use cip25_lib::metadata_json::MetadataJsonSchema;
+use cip25_lib::prelude::FromBytes;
+use cip25_lib::serialization::ToBytes;
+use cip25_lib::Metadata;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// JSON if it starts with '{', hex if it is valid hex, binary CBOR otherwise
+    Auto,
+    Json,
+    Hex,
+    Binary,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum JsonFlavor {
+    /// The JSON from the CIP-25 document
+    Cip25,
+    /// The rust-shaped JSON of older versions of this library
+    Legacy,
+    /// cardano-cli --json-metadata-no-schema
+    NoSchema,
+    /// cardano-cli --json-metadata-detailed-schema
+    Detailed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Hex,
+    Binary,
+}
+
+pub enum Input {
+    Json(String),
+    Cbor(Vec<u8>),
+}
+
+// reads the whole file, or stdin when no path (or "-") is given
+pub fn read_input(path: &Option<PathBuf>) -> Result<Vec<u8>, String> {
+    match path {
+        Some(path) if path.as_os_str() != "-" => std::fs::read(path).map_err(|e| format!("could not read {}: {}", path.display(), e)),
+        _ => {
+            let mut buf = Vec::new();
+            std::io::stdin().read_to_end(&mut buf).map_err(|e| format!("could not read stdin: {}", e))?;
+            Ok(buf)
+        },
+    }
+}
+
+pub fn parse_input(raw: Vec<u8>, format: InputFormat) -> Result<Input, String> {
+    match format {
+        InputFormat::Json => String::from_utf8(raw).map(Input::Json).map_err(|e| format!("input is not valid UTF-8: {}", e)),
+        InputFormat::Hex => {
+            let text = std::str::from_utf8(&raw).map_err(|e| format!("input is not valid UTF-8: {}", e))?;
+            hex::decode(text.trim()).map(Input::Cbor).map_err(|e| format!("input is not valid hex: {}", e))
+        },
+        InputFormat::Binary => Ok(Input::Cbor(raw)),
+        InputFormat::Auto => {
+            if let Ok(text) = std::str::from_utf8(&raw) {
+                let trimmed = text.trim();
+                if trimmed.starts_with('{') {
+                    return Ok(Input::Json(trimmed.to_owned()));
+                }
+                if !trimmed.is_empty() {
+                    if let Ok(bytes) = hex::decode(trimmed) {
+                        return Ok(Input::Cbor(bytes));
+                    }
+                }
+            }
+            Ok(Input::Cbor(raw))
+        },
+    }
+}
+
+pub fn expect_cbor(input: Input) -> Result<Vec<u8>, String> {
+    match input {
+        Input::Cbor(bytes) => Ok(bytes),
+        Input::Json(_) => Err(String::from("expected CBOR (hex or binary) input, found JSON")),
+    }
+}
+
+pub fn metadata_from_json(json: &str, flavor: JsonFlavor) -> Result<Metadata, String> {
+    match flavor {
+        JsonFlavor::Cip25 => Metadata::from_json(json, JsonFormat::Cip25).map_err(|e| e.to_string()),
+        JsonFlavor::Legacy => Metadata::from_json(json, JsonFormat::Legacy).map_err(|e| e.to_string()),
+        JsonFlavor::NoSchema => Metadata::from_metadata_json(json, MetadataJsonSchema::NoSchema).map_err(|e| e.to_string()),
+        JsonFlavor::Detailed => Metadata::from_metadata_json(json, MetadataJsonSchema::DetailedSchema).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn metadata_to_json(metadata: &Metadata, flavor: JsonFlavor) -> Result<String, String> {
+    match flavor {
+        JsonFlavor::Cip25 => metadata.to_json(JsonFormat::Cip25).map_err(|e| e.to_string()),
+        JsonFlavor::Legacy => metadata.to_json(JsonFormat::Legacy).map_err(|e| e.to_string()),
+        JsonFlavor::NoSchema => metadata.to_metadata_json(MetadataJsonSchema::NoSchema).map_err(|e| e.to_string()),
+        JsonFlavor::Detailed => metadata.to_metadata_json(MetadataJsonSchema::DetailedSchema).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn load_metadata(input: Input, flavor: JsonFlavor) -> Result<Metadata, String> {
+    match input {
+        Input::Json(json) => metadata_from_json(&json, flavor),
+        Input::Cbor(bytes) => Metadata::from_bytes(bytes).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn write_metadata(metadata: &Metadata, output: OutputFormat, flavor: JsonFlavor) -> Result<(), String> {
+    match output {
+        OutputFormat::Json => {
+            println!("{}", metadata_to_json(metadata, flavor)?);
+            Ok(())
+        },
+        OutputFormat::Hex => {
+            println!("{}", hex::encode(metadata.to_bytes().map_err(|e| e.to_string())?));
+            Ok(())
+        },
+        OutputFormat::Binary => std::io::stdout()
+            .write_all(&metadata.to_bytes().map_err(|e| e.to_string())?)
+            .map_err(|e| format!("could not write to stdout: {}", e)),
+    }
+}
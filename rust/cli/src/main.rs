@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use cip25_lib::serialization::ToBytes;
+use cip25_lib::{LabelMetadata, Metadata};
+
+mod convert;
+mod extract;
+mod input;
+
+use input::*;
+
+/// Inspect and convert CIP-25 NFT metadata (transaction metadata label 721)
+#[derive(Parser)]
+#[command(name = "cip25", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Decode CBOR (hex or binary) metadata into JSON
+    Decode {
+        /// Input file, stdin if absent or "-"
+        file: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+        input: InputFormat,
+        /// JSON format to output
+        #[arg(long, value_enum, default_value_t = JsonFlavor::Cip25)]
+        json: JsonFlavor,
+    },
+    /// Encode JSON metadata into CBOR
+    Encode {
+        /// Input file, stdin if absent or "-"
+        file: Option<PathBuf>,
+        /// JSON format of the input
+        #[arg(long, value_enum, default_value_t = JsonFlavor::Cip25)]
+        json: JsonFlavor,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Hex)]
+        output: OutputFormat,
+    },
+    /// Check that a file contains valid CIP-25 metadata and print a report
+    Validate {
+        /// Input file, stdin if absent or "-"
+        file: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+        input: InputFormat,
+        /// JSON format of the input if it is JSON
+        #[arg(long, value_enum, default_value_t = JsonFlavor::Cip25)]
+        json: JsonFlavor,
+        /// Keep going after the first error and list all of them (CBOR input only)
+        #[arg(long)]
+        all_errors: bool,
+    },
+    /// Convert metadata between version 1 and version 2
+    Convert {
+        /// Input file, stdin if absent or "-"
+        file: Option<PathBuf>,
+        /// Version to convert to
+        #[arg(long, value_enum)]
+        to: Version,
+        #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+        input: InputFormat,
+        #[arg(long, value_enum, default_value_t = JsonFlavor::Cip25)]
+        json: JsonFlavor,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+        output: OutputFormat,
+    },
+    /// Extract the 721 metadata from a transaction or auxiliary data CBOR
+    Extract {
+        /// Input file, stdin if absent or "-"
+        file: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+        input: InputFormat,
+        #[arg(long, value_enum, default_value_t = JsonFlavor::Cip25)]
+        json: JsonFlavor,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+        output: OutputFormat,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Version {
+    V1,
+    V2,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    match run(cli.command) {
+        Ok(true) => (),
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(2);
+        },
+    }
+}
+
+// Ok(false) means the command ran fine but the input was found to be invalid
+fn run(command: Command) -> Result<bool, String> {
+    match command {
+        Command::Decode { file, input, json } => {
+            let bytes = expect_cbor(parse_input(read_input(&file)?, input)?)?;
+            let metadata = load_metadata(Input::Cbor(bytes), json)?;
+            write_metadata(&metadata, OutputFormat::Json, json)?;
+        },
+        Command::Encode { file, json, output } => {
+            let metadata = load_metadata(parse_input(read_input(&file)?, InputFormat::Json)?, json)?;
+            write_metadata(&metadata, output, json)?;
+        },
+        Command::Validate { file, input, json, all_errors } => {
+            let input = parse_input(read_input(&file)?, input)?;
+            if let (true, Input::Cbor(bytes)) = (all_errors, &input) {
+                let errors = Metadata::from_bytes_diagnostic(bytes.clone()).errors;
+                if !errors.is_empty() {
+                    println!("invalid CIP-25 metadata: {} errors", errors.len());
+                    for e in errors {
+                        println!("  {}", e);
+                    }
+                    return Ok(false);
+                }
+            }
+            return match load_metadata(input, json) {
+                Ok(Metadata { key_721: LabelMetadata::Unknown { version, .. } }) => {
+                    println!("invalid CIP-25 metadata: {}", convert::unsupported_version(&version));
+                    Ok(false)
+                },
+                Ok(metadata) => {
+                    print_report(&metadata)?;
+                    Ok(true)
+                },
+                Err(e) => {
+                    println!("invalid CIP-25 metadata: {}", e);
+                    Ok(false)
+                },
+            };
+        },
+        Command::Convert { file, to, input, json, output } => {
+            let metadata = load_metadata(parse_input(read_input(&file)?, input)?, json)?;
+            let key_721 = match to {
+                Version::V1 => convert::to_v1(metadata.key_721)?,
+                Version::V2 => convert::to_v2(metadata.key_721)?,
+            };
+            write_metadata(&Metadata::new(key_721), output, json)?;
+        },
+        Command::Extract { file, input, json, output } => {
+            let bytes = expect_cbor(parse_input(read_input(&file)?, input)?)?;
+            let metadata = extract::extract_metadata(bytes)?;
+            write_metadata(&metadata, output, json)?;
+        },
+    }
+    Ok(true)
+}
+
+fn print_report(metadata: &Metadata) -> Result<(), String> {
+    let (version, policies, assets) = match &metadata.key_721 {
+        LabelMetadata::LabelMetadataV1(label_metadata_v1) => (1, label_metadata_v1.data.len(), label_metadata_v1.data.values().map(|assets| assets.len()).sum::<usize>()),
+        LabelMetadata::LabelMetadataV2(label_metadata_v2) => (2, label_metadata_v2.data.len(), label_metadata_v2.data.values().map(|assets| assets.len()).sum::<usize>()),
+        // reported as invalid before getting here
+        LabelMetadata::Unknown { .. } => return Ok(()),
+    };
+    println!("valid CIP-25 metadata");
+    println!("version: {}", version);
+    println!("policies: {}", policies);
+    println!("assets: {}", assets);
+    println!("size: {} bytes", metadata.to_bytes().map_err(|e| e.to_string())?.len());
+    Ok(())
+}
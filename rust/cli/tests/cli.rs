@@ -0,0 +1,204 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+// the transactions and the metadata they hold are shared with the tests of the library
+fn core_fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..").join("core").join("tests").join("fixtures").join(name)
+}
+
+fn read_fixture(name: &str) -> String {
+    std::fs::read_to_string(fixture(name)).unwrap().trim().to_owned()
+}
+
+fn read_core_fixture(name: &str) -> String {
+    std::fs::read_to_string(core_fixture(name)).unwrap().trim().to_owned()
+}
+
+fn cip25(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cip25")).args(args).output().unwrap()
+}
+
+fn cip25_stdin(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cip25"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "cip25 failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn json(text: &str) -> serde_json::Value {
+    serde_json::from_str(text).unwrap()
+}
+
+#[test]
+fn decode_hex() {
+    let output = cip25(&["decode", core_fixture("v1.hex").to_str().unwrap()]);
+    assert_eq!(json(&stdout(&output)), json(&read_fixture("v1.json")));
+}
+
+#[test]
+fn decode_binary_from_stdin() {
+    let binary = std::fs::read(fixture("v1.cbor")).unwrap();
+    let output = cip25_stdin(&["decode", "--input", "binary"], &binary);
+    assert_eq!(json(&stdout(&output)), json(&read_fixture("v1.json")));
+}
+
+#[test]
+fn decode_v2() {
+    let output = cip25(&["decode", fixture("v2.hex").to_str().unwrap()]);
+    assert_eq!(json(&stdout(&output)), json(&read_fixture("v2.json")));
+}
+
+#[test]
+fn decode_rejects_json_input() {
+    let output = cip25(&["decode", fixture("v1.json").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn encode_json() {
+    let output = cip25(&["encode", fixture("v1.json").to_str().unwrap()]);
+    assert_eq!(stdout(&output).trim(), read_core_fixture("v1.hex"));
+}
+
+#[test]
+fn encode_json_binary_from_stdin() {
+    let output = cip25_stdin(&["encode", "--output", "binary", "-"], read_fixture("v2.json").as_bytes());
+    assert!(output.status.success());
+    assert_eq!(hex::encode(output.stdout), read_fixture("v2.hex"));
+}
+
+#[test]
+fn encode_rejects_json_text_too_long() {
+    let json = read_fixture("v1.json").replace("\"NFT 1\"", &format!("\"{}\"", "x".repeat(65)));
+    let output = cip25_stdin(&["encode", "-"], json.as_bytes());
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("65 not in range 0 - 64"));
+}
+
+#[test]
+fn encode_cardano_cli_no_schema() {
+    let output = cip25(&["encode", "--json", "no-schema", fixture("no_schema.json").to_str().unwrap()]);
+    let hex = stdout(&output);
+    let output = cip25_stdin(&["decode", "--json", "no-schema"], hex.as_bytes());
+    let decoded = json(&stdout(&output));
+    let mut expected = json(&read_fixture("no_schema.json"));
+    // only the 721 label is kept
+    expected.as_object_mut().unwrap().remove("674");
+    assert_eq!(decoded, expected);
+}
+
+#[test]
+fn validate_valid() {
+    let output = cip25(&["validate", fixture("v1.cbor").to_str().unwrap()]);
+    let report = stdout(&output);
+    assert!(report.contains("version: 1"));
+    assert!(report.contains("policies: 1"));
+    assert!(report.contains("assets: 2"));
+}
+
+#[test]
+fn validate_json() {
+    let output = cip25(&["validate", fixture("v2.json").to_str().unwrap()]);
+    let report = stdout(&output);
+    assert!(report.contains("version: 2"));
+    assert!(report.contains("assets: 1"));
+}
+
+#[test]
+fn validate_invalid() {
+    let output = cip25(&["validate", fixture("name_too_long.hex").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("invalid CIP-25 metadata"));
+}
+
+#[test]
+fn validate_all_errors() {
+    // "image" renamed to "imagf": the name is too long and image is missing, imagf is kept
+    // as one of the other properties
+    let hex = read_fixture("name_too_long.hex").replace("65696d616765", "65696d616766");
+    let output = cip25_stdin(&["validate", "--all-errors"], hex.as_bytes());
+    assert_eq!(output.status.code(), Some(1));
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.starts_with("invalid CIP-25 metadata: 2 errors"));
+    assert!(!report.contains("Found unexpected key"));
+    assert!(report.contains("Mandatory field \"image\" not found"));
+}
+
+#[test]
+fn validate_unknown_version() {
+    // version 3 instead of 2
+    let hex = read_fixture("v2.hex").replace("6776657273696f6e02", "6776657273696f6e03");
+    let output = cip25_stdin(&["validate"], hex.as_bytes());
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("invalid CIP-25 metadata: unsupported CIP-25 version 3"));
+    // still decoded so it isn't lost
+    let output = cip25_stdin(&["decode"], hex.as_bytes());
+    let decoded = stdout(&output);
+    assert_eq!(json(&decoded)["721"]["version"], 3);
+    // and encoded back, with the map keys in JSON order
+    let output = cip25_stdin(&["encode"], decoded.as_bytes());
+    let output = cip25_stdin(&["decode"], stdout(&output).as_bytes());
+    assert_eq!(json(&stdout(&output)), json(&decoded));
+}
+
+#[test]
+fn convert_v1_to_v2() {
+    let output = cip25(&["convert", "--to", "v2", fixture("v1.json").to_str().unwrap()]);
+    let converted = json(&stdout(&output));
+    let assets = &converted["721"]["7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373"];
+    assert_eq!(converted["721"]["version"], 2);
+    assert_eq!(assets["4e465431"]["name"], "NFT 1");
+    assert_eq!(assets["4e465432"]["name"], "NFT 2");
+}
+
+#[test]
+fn convert_v1_to_v2_rejects_policy_ids_differing_in_case() {
+    let mut v1 = json(&read_fixture("v1.json"));
+    let label = v1["721"].as_object_mut().unwrap();
+    let assets = label["7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373"].clone();
+    label.insert(String::from("7EAE28AF2208BE856F7A119668AE52A49B73725E326DC16579DCC373"), assets);
+    let output = cip25_stdin(&["convert", "--to", "v2", "-"], v1.to_string().as_bytes());
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("are the same policy id in version 2"));
+}
+
+#[test]
+fn convert_v2_to_v1() {
+    let output = cip25(&["convert", "--to", "v1", "--output", "hex", fixture("v2.hex").to_str().unwrap()]);
+    let output = cip25_stdin(&["decode"], stdout(&output).as_bytes());
+    let converted = json(&stdout(&output));
+    // written as in the input
+    assert_eq!(converted["721"]["version"], 1);
+    assert_eq!(converted["721"]["7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373"]["NFT1"]["name"], "NFT 1");
+}
+
+#[test]
+fn extract_from_transactions_and_auxiliary_data() {
+    for name in ["tx_alonzo.hex", "tx_shelley.hex", "aux_shelley_ma.hex"].iter() {
+        let output = cip25(&["extract", "--output", "hex", core_fixture(name).to_str().unwrap()]);
+        assert_eq!(stdout(&output).trim(), read_core_fixture("v1.hex"), "{}", name);
+    }
+}
+
+#[test]
+fn extract_without_auxiliary_data() {
+    let output = cip25(&["extract", core_fixture("tx_no_aux.hex").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+}
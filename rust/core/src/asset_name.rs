@@ -0,0 +1,153 @@
+// Showing asset names to users. Version 2 asset names are any bytes, often but not always
+// UTF-8 text, and CIP-67 labelled ones start with a binary prefix. Version 1 asset names are
+// text but can hold control characters all the same.
+
+use super::*;
+use crate::cip67::{LabelledAssetName, MAX_ASSET_NAME_SIZE};
+use crate::native_script::POLICY_ID_SIZE;
+
+pub trait AssetNameDisplay {
+    fn asset_name_bytes(&self) -> &[u8];
+
+    // The name after any CIP-67 label as text when it is printable UTF-8, as hex otherwise.
+    fn display_name(&self) -> String {
+        let bytes = self.asset_name_bytes();
+        let labelled = LabelledAssetName::parse(bytes);
+        let name = labelled.as_ref().map_or(bytes, |labelled| labelled.name.as_slice());
+        match std::str::from_utf8(name) {
+            Ok(text) if !text.chars().any(char::is_control) => text.to_owned(),
+            _ => hex::encode(name),
+        }
+    }
+
+    // the whole asset name, label included, as on-chain
+    fn to_hex(&self) -> String {
+        hex::encode(self.asset_name_bytes())
+    }
+
+    // printable ASCII as is and \n, \t, \xNN, ... otherwise, for logs
+    fn escaped(&self) -> String {
+        self.asset_name_bytes().escape_ascii().to_string()
+    }
+}
+
+impl AssetNameDisplay for [u8] {
+    fn asset_name_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AssetNameDisplay for String64 {
+    fn asset_name_bytes(&self) -> &[u8] {
+        self.get().as_bytes()
+    }
+}
+
+// an asset as node APIs and cardano-cli give it
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AssetId {
+    pub policy_id: PolicyIdV2,
+    pub asset_name: AssetNameV2,
+}
+
+impl AssetId {
+    pub fn new(policy_id: PolicyIdV2, asset_name: AssetNameV2) -> Self {
+        Self {
+            policy_id,
+            asset_name,
+        }
+    }
+
+    // The hex policy id directly followed by the hex asset name, e.g. the "unit" of the
+    // Blockfrost API, or with a "." in between as cardano-cli writes them.
+    pub fn from_hex(asset_id: &str) -> Result<Self, DeserializeError> {
+        let invalid = || DeserializeError::new("AssetId", DeserializeFailure::InvalidAssetId(asset_id.to_owned()));
+        let (policy_id, asset_name) = match asset_id.split_once('.') {
+            Some(parts) => parts,
+            None if asset_id.is_char_boundary(POLICY_ID_SIZE * 2) => asset_id.split_at(POLICY_ID_SIZE * 2),
+            None => return Err(invalid()),
+        };
+        let policy_id = hex::decode(policy_id).map_err(|_| invalid())?;
+        let asset_name = hex::decode(asset_name).map_err(|_| invalid())?;
+        if policy_id.len() != POLICY_ID_SIZE || asset_name.len() > MAX_ASSET_NAME_SIZE {
+            return Err(invalid());
+        }
+        Ok(Self {
+            policy_id,
+            asset_name,
+        })
+    }
+
+    // without separator
+    pub fn to_hex(&self) -> String {
+        format!("{}{}", hex::encode(&self.policy_id), hex::encode(&self.asset_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cip67::{label_prefix, NFT_LABEL};
+
+    const POLICY_ID: &str = "abababababababababababababababababababababababababababab";
+
+    #[test]
+    fn display_names() {
+        assert_eq!(b"NFT".as_slice().display_name(), "NFT");
+        // the CIP-67 label is stripped, the rest is shown as text
+        let labelled = [label_prefix(NFT_LABEL).as_slice(), b"NFT"].concat();
+        assert_eq!(labelled.as_slice().display_name(), "NFT");
+        assert_eq!(labelled.as_slice().to_hex(), "000de1404e4654");
+        // invalid UTF-8 and control characters fall back to hex
+        assert_eq!([0xff, 0x41].as_slice().display_name(), "ff41");
+        assert_eq!([label_prefix(NFT_LABEL).as_slice(), &[0xff]].concat().as_slice().display_name(), "ff");
+        assert_eq!(b"a\nb".as_slice().display_name(), "610a62");
+        let string64 = String64::new(String::from("a\tb")).unwrap();
+        assert_eq!(string64.display_name(), "610962");
+        assert_eq!(String64::new(String::from("é")).unwrap().display_name(), "é");
+    }
+
+    #[test]
+    fn escaped() {
+        assert_eq!(b"NFT".as_slice().escaped(), "NFT");
+        assert_eq!([b'a', b'\n', 0x00, 0xff, b'"'].as_slice().escaped(), "a\\n\\x00\\xff\\\"");
+        assert_eq!(String64::new(String::from("é")).unwrap().escaped(), "\\xc3\\xa9");
+    }
+
+    #[test]
+    fn asset_ids() {
+        let expected = AssetId::new(hex::decode(POLICY_ID).unwrap(), b"NFT".to_vec());
+        assert_eq!(AssetId::from_hex(&format!("{}4e4654", POLICY_ID)).unwrap(), expected);
+        assert_eq!(AssetId::from_hex(&format!("{}.4e4654", POLICY_ID)).unwrap(), expected);
+        assert_eq!(expected.to_hex(), format!("{}4e4654", POLICY_ID));
+        // no asset name
+        assert_eq!(AssetId::from_hex(POLICY_ID).unwrap().asset_name, Vec::<u8>::new());
+        assert_eq!(AssetId::from_hex(&format!("{}.", POLICY_ID)).unwrap().asset_name, Vec::<u8>::new());
+        // the largest asset name
+        assert_eq!(AssetId::from_hex(&format!("{}{}", POLICY_ID, "00".repeat(32))).unwrap().asset_name, vec![0; 32]);
+    }
+
+    #[test]
+    fn invalid_asset_ids() {
+        let cases = [
+            // too short
+            String::from(&POLICY_ID[2..]),
+            format!("{}.4e4654", &POLICY_ID[2..]),
+            String::new(),
+            // a multi-byte character across the end of the policy id
+            format!("{}é4e", &POLICY_ID[1..]),
+            format!("{}é", &POLICY_ID[..54]),
+            // oversize asset name
+            format!("{}{}", POLICY_ID, "00".repeat(33)),
+            format!("{}.{}", POLICY_ID, "00".repeat(33)),
+            // not hex
+            format!("{}4e465", POLICY_ID),
+            format!("{}.NFT", POLICY_ID),
+            format!("{}..4e4654", POLICY_ID),
+        ];
+        for asset_id in cases {
+            let err = AssetId::from_hex(&asset_id).unwrap_err();
+            assert!(matches!(err.failure(), DeserializeFailure::InvalidAssetId(invalid) if *invalid == asset_id), "{}: {}", asset_id, err);
+        }
+    }
+}
@@ -0,0 +1,369 @@
+// Splitting the 721 metadata of a big collection into documents that each fit a byte budget,
+// e.g. what is left of the transaction size for the metadata of each minting transaction.
+
+use super::*;
+use crate::size::EncodedSize;
+
+// an asset whose metadata doesn't fit the budget even alone in a document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OversizedAsset {
+    // text for version 1, hex for version 2
+    pub policy_id: String,
+    // text for version 1, hex for version 2
+    pub asset_name: String,
+    // of a Metadata with only this asset
+    pub size: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Batches {
+    pub batches: Vec<Metadata>,
+    pub oversized: Vec<OversizedAsset>,
+}
+
+impl LabelMetadata {
+    // Every batch keeps the version of self and is at most budget bytes once encoded with
+    // to_bytes(). The assets of a policy go in the same batch unless they don't fit in one.
+    // An unknown version can't be split and is returned as a single batch.
+    pub fn split_into_batches(&self, budget: usize) -> Result<Batches, SerializeError> {
+        match self {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => split(label_metadata_v1, budget),
+            LabelMetadata::LabelMetadataV2(label_metadata_v2) => split(label_metadata_v2, budget),
+            LabelMetadata::Unknown { .. } => Ok(Batches {
+                batches: vec![Metadata::new(self.clone())],
+                oversized: Vec::new(),
+            }),
+        }
+    }
+}
+
+// what differs between version 1 and 2
+trait Splittable: Clone {
+    type PolicyId: Ord + Clone;
+    type AssetName: Ord + Clone;
+
+    fn data(&self) -> &BTreeMap<Self::PolicyId, BTreeMap<Self::AssetName, MetadataDetails>>;
+
+    fn data_mut(&mut self) -> &mut BTreeMap<Self::PolicyId, BTreeMap<Self::AssetName, MetadataDetails>>;
+
+    fn added_size(&self, policy_id: &Self::PolicyId, asset_name: &Self::AssetName, details: &MetadataDetails) -> Result<isize, SerializeError>;
+
+    fn into_label_metadata(self) -> LabelMetadata;
+
+    fn describe(policy_id: &Self::PolicyId, asset_name: &Self::AssetName) -> (String, String);
+}
+
+impl Splittable for LabelMetadataV1 {
+    type PolicyId = PolicyIdV1;
+    type AssetName = AssetNameV1;
+
+    fn data(&self) -> &DataV1 {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut DataV1 {
+        &mut self.data
+    }
+
+    fn added_size(&self, policy_id: &PolicyIdV1, asset_name: &AssetNameV1, details: &MetadataDetails) -> Result<isize, SerializeError> {
+        LabelMetadataV1::added_size(self, policy_id, asset_name, details)
+    }
+
+    fn into_label_metadata(self) -> LabelMetadata {
+        LabelMetadata::new_label_metadata_v1(self)
+    }
+
+    fn describe(policy_id: &PolicyIdV1, asset_name: &AssetNameV1) -> (String, String) {
+        (policy_id.get().clone(), asset_name.get().clone())
+    }
+}
+
+impl Splittable for LabelMetadataV2 {
+    type PolicyId = PolicyIdV2;
+    type AssetName = AssetNameV2;
+
+    fn data(&self) -> &Data {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut Data {
+        &mut self.data
+    }
+
+    fn added_size(&self, policy_id: &PolicyIdV2, asset_name: &AssetNameV2, details: &MetadataDetails) -> Result<isize, SerializeError> {
+        LabelMetadataV2::added_size(self, policy_id, asset_name, details)
+    }
+
+    fn into_label_metadata(self) -> LabelMetadata {
+        LabelMetadata::new_label_metadata_v2(self)
+    }
+
+    fn describe(policy_id: &PolicyIdV2, asset_name: &AssetNameV2) -> (String, String) {
+        (hex::encode(policy_id), hex::encode(asset_name))
+    }
+}
+
+// the batch being filled and its encoded size as a Metadata
+#[derive(Clone)]
+struct Batch<T> {
+    label_metadata: T,
+    size: usize,
+}
+
+impl<T: Splittable> Batch<T> {
+    fn is_empty(&self) -> bool {
+        self.label_metadata.data().is_empty()
+    }
+
+    fn added_size(&self, policy_id: &T::PolicyId, asset_name: &T::AssetName, details: &MetadataDetails) -> Result<usize, SerializeError> {
+        Ok((self.size as isize + self.label_metadata.added_size(policy_id, asset_name, details)?) as usize)
+    }
+
+    fn insert(&mut self, policy_id: &T::PolicyId, asset_name: &T::AssetName, details: &MetadataDetails) -> Result<(), SerializeError> {
+        self.size = self.added_size(policy_id, asset_name, details)?;
+        self.label_metadata.data_mut().entry(policy_id.clone()).or_default().insert(asset_name.clone(), details.clone());
+        Ok(())
+    }
+
+    fn into_metadata(self) -> Metadata {
+        Metadata::new(self.label_metadata.into_label_metadata())
+    }
+}
+
+fn split<T: Splittable>(label_metadata: &T, budget: usize) -> Result<Batches, SerializeError> {
+    let mut empty = label_metadata.clone();
+    empty.data_mut().clear();
+    let empty = Batch {
+        size: Metadata::new(empty.clone().into_label_metadata()).encoded_size()?,
+        label_metadata: empty,
+    };
+    let mut batches = Vec::new();
+    let mut oversized = Vec::new();
+    let mut batch = empty.clone();
+    for (policy_id, assets) in label_metadata.data() {
+        // the policy alone in a batch, without the assets that can't fit anywhere
+        let mut alone = empty.clone();
+        for (asset_name, details) in assets {
+            let size = empty.added_size(policy_id, asset_name, details)?;
+            if size > budget {
+                let (policy_id, asset_name) = T::describe(policy_id, asset_name);
+                oversized.push(OversizedAsset { policy_id, asset_name, size });
+            } else {
+                alone.insert(policy_id, asset_name, details)?;
+            }
+        }
+        let (first_asset_name, first_details) = match alone.label_metadata.data().get(policy_id).and_then(|assets| assets.iter().next()) {
+            Some(first) => first,
+            None => continue,
+        };
+        // the outer maps of the current batch can get a longer header with one more policy
+        let header_growth = batch.added_size(policy_id, first_asset_name, first_details)? - batch.size
+            - (empty.added_size(policy_id, first_asset_name, first_details)? - empty.size);
+        let with_policy = batch.size + header_growth + (alone.size - empty.size);
+        if with_policy <= budget {
+            for (asset_name, details) in &alone.label_metadata.data()[policy_id] {
+                batch.insert(policy_id, asset_name, details)?;
+            }
+        } else if alone.size <= budget {
+            // the whole policy in a new batch rather than split over two
+            batches.push(std::mem::replace(&mut batch, alone).into_metadata());
+        } else {
+            // too big for one batch, one asset at a time
+            for (asset_name, details) in &alone.label_metadata.data()[policy_id] {
+                if batch.added_size(policy_id, asset_name, details)? > budget {
+                    batches.push(std::mem::replace(&mut batch, empty.clone()).into_metadata());
+                }
+                batch.insert(policy_id, asset_name, details)?;
+            }
+        }
+    }
+    if !batch.is_empty() {
+        batches.push(batch.into_metadata());
+    }
+    Ok(Batches { batches, oversized })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an asset whose size grows with description_len
+    fn details(name: &str, description_len: usize) -> MetadataDetails {
+        let mut details = MetadataDetails::new(String64::new(String::from(name)).unwrap(), String64OrArrString64::new_string64(String64::new(format!("ipfs://{}", name)).unwrap()));
+        if description_len > 0 {
+            let lines = (0..description_len).map(|i| String64::new(format!("line {} of {}", i, name)).unwrap()).collect();
+            details.description = Some(String64OrArrString64::new_arr_string64(lines));
+        }
+        details
+    }
+
+    fn policy_id(policy: usize) -> Vec<u8> {
+        let mut policy_id = vec![0u8; 28];
+        policy_id[27] = policy as u8;
+        policy_id
+    }
+
+    // assets[policy] are the description lengths of the assets of that policy
+    fn collection_v1(assets: &[Vec<usize>]) -> LabelMetadata {
+        let mut data = BTreeMap::new();
+        for (policy, lengths) in assets.iter().enumerate() {
+            let mut policy_assets = BTreeMap::new();
+            for (asset, description_len) in lengths.iter().enumerate() {
+                let name = format!("NFT{:03}", asset);
+                policy_assets.insert(String64::new(name.clone()).unwrap(), details(&name, *description_len));
+            }
+            data.insert(String64::new(hex::encode(policy_id(policy))).unwrap(), policy_assets);
+        }
+        LabelMetadata::new_label_metadata_v1(LabelMetadataV1::new(data))
+    }
+
+    fn collection_v2(assets: &[Vec<usize>]) -> LabelMetadata {
+        let mut data = BTreeMap::new();
+        for (policy, lengths) in assets.iter().enumerate() {
+            let mut policy_assets = BTreeMap::new();
+            for (asset, description_len) in lengths.iter().enumerate() {
+                let name = format!("NFT{:03}", asset);
+                policy_assets.insert(name.clone().into_bytes(), details(&name, *description_len));
+            }
+            data.insert(policy_id(policy), policy_assets);
+        }
+        LabelMetadata::new_label_metadata_v2(LabelMetadataV2::new(data).unwrap())
+    }
+
+    // (policy id, asset name) as in OversizedAsset
+    fn assets(label_metadata: &LabelMetadata) -> Vec<(String, String)> {
+        match label_metadata {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => label_metadata_v1.data.iter()
+                .flat_map(|(policy_id, assets)| assets.keys().map(move |asset_name| LabelMetadataV1::describe(policy_id, asset_name)))
+                .collect(),
+            LabelMetadata::LabelMetadataV2(label_metadata_v2) => label_metadata_v2.data.iter()
+                .flat_map(|(policy_id, assets)| assets.keys().map(move |asset_name| LabelMetadataV2::describe(policy_id, asset_name)))
+                .collect(),
+            LabelMetadata::Unknown { .. } => panic!("unknown version"),
+        }
+    }
+
+    fn assets_policy_ids(label_metadata: &LabelMetadata) -> Vec<String> {
+        let mut policy_ids: Vec<String> = assets(label_metadata).into_iter().map(|(policy_id, _)| policy_id).collect();
+        policy_ids.dedup();
+        policy_ids
+    }
+
+    // every batch fits and has the version of the collection, every asset is either in
+    // exactly one batch or reported as oversized
+    fn check(collection: &LabelMetadata, budget: usize) -> Batches {
+        let batches = collection.split_into_batches(budget).unwrap();
+        let mut split = Vec::new();
+        for batch in &batches.batches {
+            let size = batch.to_bytes().unwrap().len();
+            assert!(size <= budget, "batch of {} bytes for a budget of {}", size, budget);
+            assert_eq!(std::mem::discriminant(&batch.key_721), std::mem::discriminant(collection));
+            assert!(!assets(&batch.key_721).is_empty());
+            split.extend(assets(&batch.key_721));
+        }
+        for oversized in &batches.oversized {
+            assert!(oversized.size > budget);
+            split.push((oversized.policy_id.clone(), oversized.asset_name.clone()));
+        }
+        split.sort();
+        assert_eq!(split, assets(collection), "budget {}", budget);
+        batches
+    }
+
+    #[test]
+    fn batches_fit_the_budget_without_losing_assets() {
+        let assets = vec![vec![0, 3, 1], vec![5; 10], vec![0; 30], vec![2], vec![1, 8, 0, 4, 0, 2]];
+        for collection in [collection_v1(&assets), collection_v2(&assets)] {
+            for budget in (300..=4000).step_by(97) {
+                let batches = check(&collection, budget);
+                assert!(batches.oversized.is_empty(), "budget {}", budget);
+            }
+            let whole = Metadata::new(collection.clone()).to_bytes().unwrap().len();
+            assert_eq!(check(&collection, whole).batches.len(), 1);
+            assert!(check(&collection, whole - 1).batches.len() > 1);
+        }
+    }
+
+    #[test]
+    fn map_headers_growing_is_counted() {
+        // 24 policies make the 721 map header 2 bytes long, 24 assets the policy map one
+        for assets in [vec![vec![0]; 30], vec![vec![0; 30]]] {
+            for collection in [collection_v1(&assets), collection_v2(&assets)] {
+                let whole = Metadata::new(collection.clone()).to_bytes().unwrap().len();
+                for budget in whole - 40..=whole {
+                    let batches = check(&collection, budget);
+                    assert_eq!(batches.batches.len(), if budget == whole { 1 } else { 2 }, "budget {}", budget);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn policies_are_kept_together() {
+        let assets = vec![vec![1; 4]; 6];
+        for collection in [collection_v1, collection_v2] {
+            // room for one policy but not two
+            let one_policy = Metadata::new(collection(&assets[..1])).to_bytes().unwrap().len();
+            let budget = Metadata::new(collection(&assets[..2])).to_bytes().unwrap().len() - 1;
+            assert!(budget >= one_policy + 10);
+            let batches = check(&collection(&assets), budget);
+            assert_eq!(batches.batches.len(), 6);
+            for batch in &batches.batches {
+                assert_eq!(assets_policy_ids(&batch.key_721).len(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn policy_too_big_for_one_batch_is_split() {
+        let assets = vec![vec![2], vec![3; 20], vec![2]];
+        let collection = collection_v1(&assets);
+        let budget = 800;
+        assert!(Metadata::new(collection_v1(&assets[1..2])).to_bytes().unwrap().len() > budget);
+        let batches = check(&collection, budget);
+        let spanning = batches.batches.iter()
+            .filter(|batch| assets_policy_ids(&batch.key_721).contains(&hex::encode(policy_id(1))))
+            .count();
+        assert!(spanning > 1);
+        // the small policies aren't split
+        for policy in [0, 2] {
+            let with_policy = batches.batches.iter()
+                .filter(|batch| assets_policy_ids(&batch.key_721).contains(&hex::encode(policy_id(policy))))
+                .count();
+            assert_eq!(with_policy, 1);
+        }
+    }
+
+    #[test]
+    fn oversized_assets_are_reported() {
+        let assets = vec![vec![0, 40, 1], vec![40]];
+        for collection in [collection_v1(&assets), collection_v2(&assets)] {
+            let batches = check(&collection, 600);
+            assert_eq!(batches.batches.len(), 1);
+            let reported: Vec<(String, String)> = batches.oversized.iter().map(|oversized| (oversized.policy_id.clone(), oversized.asset_name.clone())).collect();
+            let expected = match collection {
+                LabelMetadata::LabelMetadataV1(_) => vec![(hex::encode(policy_id(0)), String::from("NFT001")), (hex::encode(policy_id(1)), String::from("NFT000"))],
+                _ => vec![(hex::encode(policy_id(0)), hex::encode("NFT001")), (hex::encode(policy_id(1)), hex::encode("NFT000"))],
+            };
+            assert_eq!(reported, expected);
+            // the size is that of a document with only the asset
+            let alone = match collection {
+                LabelMetadata::LabelMetadataV1(_) => collection_v1(&[vec![40]]),
+                _ => collection_v2(&[vec![40]]),
+            };
+            assert_eq!(batches.oversized[1].size, Metadata::new(alone).to_bytes().unwrap().len());
+        }
+        // nothing fits
+        let batches = check(&collection_v1(&assets), 10);
+        assert!(batches.batches.is_empty());
+        assert_eq!(batches.oversized.len(), 4);
+    }
+
+    #[test]
+    fn unknown_version_is_a_single_batch() {
+        let unknown = LabelMetadata::new_unknown(TransactionMetadatum::new_int(3), TransactionMetadatum::new_map(Vec::new()));
+        let batches = unknown.split_into_batches(1).unwrap();
+        assert_eq!(batches.batches.len(), 1);
+        assert!(batches.oversized.is_empty());
+    }
+}
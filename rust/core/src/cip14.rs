@@ -0,0 +1,113 @@
+// CIP-14 asset fingerprints: the blake2b-160 digest of the policy id followed by the asset
+// name (raw bytes in both cases), bech32 encoded with the "asset" prefix. They are what
+// explorers and wallets show to tell assets apart.
+
+use super::*;
+use crate::query::AssetMetadataQuery;
+use bech32::ToBase32;
+
+pub const FINGERPRINT_PREFIX: &str = "asset";
+
+pub const FINGERPRINT_SIZE: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AssetFingerprint([u8; FINGERPRINT_SIZE]);
+
+impl AssetFingerprint {
+    pub fn new(policy_id: &[u8], asset_name: &[u8]) -> Self {
+        let hash = blake2b_simd::Params::new()
+            .hash_length(FINGERPRINT_SIZE)
+            .to_state()
+            .update(policy_id)
+            .update(asset_name)
+            .finalize();
+        let mut digest = [0u8; FINGERPRINT_SIZE];
+        digest.copy_from_slice(hash.as_bytes());
+        Self(digest)
+    }
+
+    // None when the policy id isn't hex text, as assets() does for version 1
+    pub fn from_v1(policy_id: &PolicyIdV1, asset_name: &AssetNameV1) -> Option<Self> {
+        let policy_id = hex::decode(policy_id.get()).ok()?;
+        Some(Self::new(&policy_id, asset_name.get().as_bytes()))
+    }
+
+    pub fn digest(&self) -> &[u8; FINGERPRINT_SIZE] {
+        &self.0
+    }
+
+    pub fn to_bech32(&self) -> String {
+        // only fails on an invalid prefix
+        bech32::encode(FINGERPRINT_PREFIX, self.0.to_base32(), bech32::Variant::Bech32).unwrap()
+    }
+}
+
+impl std::fmt::Display for AssetFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_bech32())
+    }
+}
+
+impl LabelMetadata {
+    // every asset described with its fingerprint, in the order of the metadata
+    pub fn fingerprints(&self) -> Result<Vec<(PolicyIdV2, AssetNameV2, AssetFingerprint)>, DeserializeError> {
+        Ok(self
+            .assets()?
+            .into_iter()
+            .map(|(policy_id, asset_name)| {
+                let fingerprint = AssetFingerprint::new(&policy_id, &asset_name);
+                (policy_id, asset_name, fingerprint)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the test vectors of CIP-14
+    #[test]
+    fn cip14_test_vectors() {
+        let vectors = [
+            ("7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373", "", "asset1rjklcrnsdzqp65wjgrg55sy9723kw09mlgvlc3"),
+            ("7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc37e", "", "asset1nl0puwxmhas8fawxp8nx4e2q3wekg969n2auw3"),
+            ("1e349c9bdea19fd6c147626a5260bc44b71635f398b67c59881df209", "", "asset1uyuxku60yqe57nusqzjx38aan3f2wq6s93f6ea"),
+            ("7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373", "504154415445", "asset13n25uv0yaf5kus35fm2k86cqy60z58d9xmde92"),
+            ("1e349c9bdea19fd6c147626a5260bc44b71635f398b67c59881df209", "504154415445", "asset1hv4p5tv2a837mzqrst04d0dcptdjmluqvdx9k3"),
+            ("1e349c9bdea19fd6c147626a5260bc44b71635f398b67c59881df209", "7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373", "asset1aqrdypg669jgazruv5ah07nuyqe0wxjhe2el6f"),
+            ("7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373", "1e349c9bdea19fd6c147626a5260bc44b71635f398b67c59881df209", "asset17jd78wukhtrnmjh3fngzasxm8rck0l2r4hhyyt"),
+            ("7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373", "0000000000000000000000000000000000000000000000000000000000000000", "asset1pkpwyknlvul7az0xx8czhl60pyel45rpje4z8w"),
+        ];
+        for (policy_id, asset_name, expected) in vectors {
+            let fingerprint = AssetFingerprint::new(&hex::decode(policy_id).unwrap(), &hex::decode(asset_name).unwrap());
+            assert_eq!(fingerprint.to_bech32(), expected, "policy id {} asset name {}", policy_id, asset_name);
+        }
+    }
+
+    #[test]
+    fn v1_and_v2_names_give_the_same_fingerprint() {
+        let policy_id = "1e349c9bdea19fd6c147626a5260bc44b71635f398b67c59881df209";
+        let v1 = AssetFingerprint::from_v1(&String64::new(policy_id.to_owned()).unwrap(), &String64::new(String::from("PATATE")).unwrap());
+        assert_eq!(v1.map(|fingerprint| fingerprint.to_string()).as_deref(), Some("asset1hv4p5tv2a837mzqrst04d0dcptdjmluqvdx9k3"));
+        assert_eq!(AssetFingerprint::from_v1(&String64::new(String::from("not hex")).unwrap(), &String64::new(String::from("PATATE")).unwrap()), None);
+    }
+
+    #[test]
+    fn label_metadata_fingerprints() {
+        let policy_id = hex::decode("7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373").unwrap();
+        let details = MetadataDetails::new(String64::new(String::from("Patate")).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("ipfs://patate")).unwrap()));
+        let mut assets = BTreeMap::new();
+        assets.insert(b"PATATE".to_vec(), details.clone());
+        assets.insert(Vec::new(), details);
+        let mut data = BTreeMap::new();
+        data.insert(policy_id, assets);
+        let fingerprints = LabelMetadata::new_label_metadata_v2(LabelMetadataV2::new(data).unwrap())
+            .fingerprints()
+            .unwrap()
+            .into_iter()
+            .map(|(_, _, fingerprint)| fingerprint.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(fingerprints, ["asset1rjklcrnsdzqp65wjgrg55sy9723kw09mlgvlc3", "asset13n25uv0yaf5kus35fm2k86cqy60z58d9xmde92"]);
+    }
+}
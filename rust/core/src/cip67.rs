@@ -0,0 +1,306 @@
+// CIP-67 asset name labels: a 4 byte prefix 0x0LLLLC0 where LLLL is the label number and
+// CC the CRC-8 (polynomial 0x07) of its 2 big-endian bytes, e.g. 0x000de140 for 222.
+//
+// CIP-68 uses them to tell the reference token holding the datum (100) from the user
+// tokens it describes (222 NFT, 333 FT, 444 RFT), which have the same name after the prefix.
+
+use super::*;
+use crate::query::AssetMetadataQuery;
+
+pub const REFERENCE_TOKEN_LABEL: u16 = 100;
+
+pub const NFT_LABEL: u16 = 222;
+
+pub const FT_LABEL: u16 = 333;
+
+pub const RFT_LABEL: u16 = 444;
+
+pub const LABEL_PREFIX_SIZE: usize = 4;
+
+// ledger limit on asset names
+pub const MAX_ASSET_NAME_SIZE: usize = 32;
+
+fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in bytes {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+pub fn label_prefix(label: u16) -> [u8; LABEL_PREFIX_SIZE] {
+    let prefix = ((label as u32) << 12) | ((crc8(&label.to_be_bytes()) as u32) << 4);
+    prefix.to_be_bytes()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LabelledAssetName {
+    pub label: u16,
+    // the asset name after the prefix
+    pub name: Vec<u8>,
+}
+
+impl LabelledAssetName {
+    pub fn new(label: u16, name: Vec<u8>) -> Result<Self, DeserializeError> {
+        if name.len() > MAX_ASSET_NAME_SIZE - LABEL_PREFIX_SIZE {
+            return Err(DeserializeError::new("LabelledAssetName", DeserializeFailure::RangeCheck{ found: name.len(), min: Some(0), max: Some((MAX_ASSET_NAME_SIZE - LABEL_PREFIX_SIZE) as isize) }));
+        }
+        Ok(Self {
+            label,
+            name,
+        })
+    }
+
+    // None when the asset name doesn't start with a valid prefix
+    pub fn parse(asset_name: &[u8]) -> Option<Self> {
+        if asset_name.len() < LABEL_PREFIX_SIZE || asset_name.len() > MAX_ASSET_NAME_SIZE {
+            return None;
+        }
+        let prefix = u32::from_be_bytes([asset_name[0], asset_name[1], asset_name[2], asset_name[3]]);
+        if prefix & 0xf000_000f != 0 {
+            return None;
+        }
+        let label = (prefix >> 12) as u16;
+        if ((prefix >> 4) & 0xff) as u8 != crc8(&label.to_be_bytes()) {
+            return None;
+        }
+        Some(Self {
+            label,
+            name: asset_name[LABEL_PREFIX_SIZE..].to_vec(),
+        })
+    }
+
+    pub fn to_asset_name(&self) -> AssetNameV2 {
+        let mut asset_name = label_prefix(self.label).to_vec();
+        asset_name.extend_from_slice(&self.name);
+        asset_name
+    }
+
+    pub fn token_class(&self) -> TokenClass {
+        match self.label {
+            REFERENCE_TOKEN_LABEL => TokenClass::Reference,
+            NFT_LABEL => TokenClass::Nft,
+            FT_LABEL => TokenClass::Ft,
+            RFT_LABEL => TokenClass::Rft,
+            label => TokenClass::Other(label),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenClass {
+    // holds the CIP-68 datum of the user tokens with the same name
+    Reference,
+    Nft,
+    Ft,
+    Rft,
+    // a label that isn't one of CIP-68
+    Other(u16),
+    // no CIP-67 prefix, e.g. a CIP-25 asset
+    Unlabelled,
+}
+
+impl TokenClass {
+    pub fn of(asset_name: &[u8]) -> Self {
+        match LabelledAssetName::parse(asset_name) {
+            Some(labelled) => labelled.token_class(),
+            None => TokenClass::Unlabelled,
+        }
+    }
+
+    pub fn is_user_token(&self) -> bool {
+        matches!(self, TokenClass::Nft | TokenClass::Ft | TokenClass::Rft)
+    }
+}
+
+// The reference token holding the datum of a user token, None for any other asset name.
+pub fn reference_asset_name(asset_name: &[u8]) -> Option<AssetNameV2> {
+    let labelled = LabelledAssetName::parse(asset_name)?;
+    if !labelled.token_class().is_user_token() {
+        return None;
+    }
+    Some(LabelledAssetName { label: REFERENCE_TOKEN_LABEL, name: labelled.name }.to_asset_name())
+}
+
+// a user token and its reference token, or one of them found alone
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenPair {
+    pub policy_id: PolicyIdV2,
+    // the asset name without the label prefix
+    pub name: Vec<u8>,
+    pub reference: Option<AssetNameV2>,
+    pub user: Option<AssetNameV2>,
+}
+
+// Pairs the user tokens with the reference token of the same policy and name. Reference
+// tokens no user token points to are given with user: None, and user tokens without a
+// reference token with reference: None. Other assets are left out.
+pub fn pair_tokens(assets: &[(PolicyIdV2, AssetNameV2)]) -> Vec<TokenPair> {
+    let references: BTreeMap<(&PolicyIdV2, Vec<u8>), &AssetNameV2> = assets
+        .iter()
+        .filter_map(|(policy_id, asset_name)| match LabelledAssetName::parse(asset_name) {
+            Some(labelled) if labelled.label == REFERENCE_TOKEN_LABEL => Some(((policy_id, labelled.name), asset_name)),
+            _ => None,
+        })
+        .collect();
+    let mut paired = std::collections::BTreeSet::new();
+    let mut pairs = Vec::new();
+    for (policy_id, asset_name) in assets {
+        let labelled = match LabelledAssetName::parse(asset_name) {
+            Some(labelled) if labelled.token_class().is_user_token() => labelled,
+            _ => continue,
+        };
+        let reference = references.get(&(policy_id, labelled.name.clone())).map(|reference| (*reference).clone());
+        if reference.is_some() {
+            paired.insert((policy_id, labelled.name.clone()));
+        }
+        pairs.push(TokenPair {
+            policy_id: policy_id.clone(),
+            name: labelled.name,
+            reference,
+            user: Some(asset_name.clone()),
+        });
+    }
+    for ((policy_id, name), reference) in references {
+        if !paired.contains(&(policy_id, name.clone())) {
+            pairs.push(TokenPair {
+                policy_id: policy_id.clone(),
+                name,
+                reference: Some(reference.clone()),
+                user: None,
+            });
+        }
+    }
+    pairs
+}
+
+impl LabelMetadata {
+    // every asset described with its class, in the order of the metadata
+    pub fn token_classes(&self) -> Result<Vec<(PolicyIdV2, AssetNameV2, TokenClass)>, DeserializeError> {
+        Ok(self
+            .assets()?
+            .into_iter()
+            .map(|(policy_id, asset_name)| {
+                let token_class = TokenClass::of(&asset_name);
+                (policy_id, asset_name, token_class)
+            })
+            .collect())
+    }
+
+    pub fn token_pairs(&self) -> Result<Vec<TokenPair>, DeserializeError> {
+        Ok(pair_tokens(&self.assets()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the examples of CIP-67
+    const VECTORS: [(u16, &str); 4] = [
+        (REFERENCE_TOKEN_LABEL, "000643b0"),
+        (NFT_LABEL, "000de140"),
+        (FT_LABEL, "0014df10"),
+        (RFT_LABEL, "001bc280"),
+    ];
+
+    fn asset_name(hex: &str, name: &str) -> AssetNameV2 {
+        [hex::decode(hex).unwrap(), name.as_bytes().to_vec()].concat()
+    }
+
+    #[test]
+    fn label_prefixes() {
+        for (label, prefix) in VECTORS.iter() {
+            assert_eq!(hex::encode(label_prefix(*label)), *prefix);
+            let labelled = LabelledAssetName::new(*label, b"NFT1".to_vec()).unwrap();
+            assert_eq!(labelled.to_asset_name(), asset_name(prefix, "NFT1"));
+            assert_eq!(LabelledAssetName::parse(&asset_name(prefix, "NFT1")), Some(labelled));
+            // the prefix alone is a valid asset name
+            assert_eq!(LabelledAssetName::parse(&hex::decode(prefix).unwrap()).map(|labelled| labelled.name), Some(Vec::new()));
+        }
+        // the whole label range
+        for label in [0, 1, 0xfff, 0xffff] {
+            assert_eq!(LabelledAssetName::parse(&label_prefix(label)).map(|labelled| labelled.label), Some(label));
+        }
+    }
+
+    #[test]
+    fn invalid_prefixes() {
+        for invalid in [
+            // checksum
+            "000de150", "000de040",
+            // outer nibbles
+            "100de140", "000de141", "f00de14f",
+            // too short
+            "000de1", "",
+        ] {
+            assert_eq!(LabelledAssetName::parse(&hex::decode(invalid).unwrap()), None, "{}", invalid);
+            assert_eq!(TokenClass::of(&hex::decode(invalid).unwrap()), TokenClass::Unlabelled, "{}", invalid);
+        }
+        // longer than the ledger allows
+        assert_eq!(LabelledAssetName::parse(&asset_name("000de140", &"x".repeat(29))), None);
+    }
+
+    #[test]
+    fn name_length() {
+        assert!(LabelledAssetName::new(NFT_LABEL, vec![0; 28]).is_ok());
+        let err = LabelledAssetName::new(NFT_LABEL, vec![0; 29]).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::RangeCheck{ found: 29, min: Some(0), max: Some(28) }), "{}", err);
+    }
+
+    #[test]
+    fn token_classes() {
+        assert_eq!(TokenClass::of(&asset_name("000643b0", "x")), TokenClass::Reference);
+        assert_eq!(TokenClass::of(&asset_name("000de140", "x")), TokenClass::Nft);
+        assert_eq!(TokenClass::of(&asset_name("0014df10", "x")), TokenClass::Ft);
+        assert_eq!(TokenClass::of(&asset_name("001bc280", "x")), TokenClass::Rft);
+        assert_eq!(TokenClass::of(&label_prefix(500)), TokenClass::Other(500));
+        assert_eq!(TokenClass::of(b"NFT1"), TokenClass::Unlabelled);
+        assert!(TokenClass::Nft.is_user_token());
+        assert!(!TokenClass::Reference.is_user_token());
+        assert!(!TokenClass::Other(500).is_user_token());
+    }
+
+    #[test]
+    fn reference_asset_names() {
+        for user in ["000de140", "0014df10", "001bc280"] {
+            assert_eq!(reference_asset_name(&asset_name(user, "x")), Some(asset_name("000643b0", "x")));
+        }
+        assert_eq!(reference_asset_name(&asset_name("000643b0", "x")), None);
+        assert_eq!(reference_asset_name(&[&label_prefix(500)[..], b"x"].concat()), None);
+        assert_eq!(reference_asset_name(b"NFT1"), None);
+    }
+
+    #[test]
+    fn pairs() {
+        let policy_id = vec![1; 28];
+        let other_policy_id = vec![2; 28];
+        let assets = vec![
+            (policy_id.clone(), asset_name("000de140", "paired")),
+            (policy_id.clone(), asset_name("000de140", "orphan user")),
+            (policy_id.clone(), asset_name("000643b0", "orphan reference")),
+            (policy_id.clone(), asset_name("000643b0", "paired")),
+            // the reference token of another policy doesn't count
+            (other_policy_id.clone(), asset_name("000643b0", "orphan user")),
+            // left out
+            (policy_id.clone(), b"unlabelled".to_vec()),
+            (policy_id.clone(), [&label_prefix(500)[..], b"other"].concat()),
+        ];
+        let pair = |policy_id: &PolicyIdV2, name: &str, reference: Option<&str>, user: Option<&str>| TokenPair {
+            policy_id: policy_id.clone(),
+            name: name.as_bytes().to_vec(),
+            reference: reference.map(|prefix| asset_name(prefix, name)),
+            user: user.map(|prefix| asset_name(prefix, name)),
+        };
+        // the user tokens in order, then the reference tokens left alone
+        assert_eq!(pair_tokens(&assets), vec![
+            pair(&policy_id, "paired", Some("000643b0"), Some("000de140")),
+            pair(&policy_id, "orphan user", None, Some("000de140")),
+            pair(&policy_id, "orphan reference", Some("000643b0"), None),
+            pair(&other_policy_id, "orphan user", Some("000643b0"), None),
+        ]);
+    }
+}
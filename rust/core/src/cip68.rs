@@ -0,0 +1,688 @@
+// CIP-68 metadata, held in the inline datum of a reference token instead of label 721.
+//
+// datum    = #6.121([metadata, version, extra])
+// metadata = { * bounded_bytes => plutus_data }
+// version  = int
+// extra    = plutus_data
+//
+// The 222 (NFT) metadata map uses the same field names as CIP-25 but as UTF-8 byte strings,
+// and its text values aren't limited to 64 bytes.
+
+use super::*;
+
+// constructors 0-6 and 7-127 have their own tags, any other is written as #6.102([alt, fields])
+const CONSTR_TAGS: std::ops::RangeInclusive<u64> = 121..=127;
+
+const CONSTR_EXTENDED_TAGS: std::ops::RangeInclusive<u64> = 1280..=1400;
+
+const CONSTR_GENERAL_TAG: u64 = 102;
+
+const POSITIVE_BIGNUM_TAG: u64 = 2;
+
+const NEGATIVE_BIGNUM_TAG: u64 = 3;
+
+// the ledger only accepts byte strings up to 64 bytes, longer ones are split in chunks
+const BYTES_CHUNK_SIZE: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlutusData {
+    Constr {
+        alternative: u64,
+        fields: Vec<PlutusData>,
+    },
+    Map(Vec<(PlutusData, PlutusData)>),
+    List(Vec<PlutusData>),
+    Integer(i128),
+    Bytes(Vec<u8>),
+}
+
+impl PlutusData {
+    pub fn new_constr(alternative: u64, fields: Vec<PlutusData>) -> Self {
+        Self::Constr {
+            alternative,
+            fields,
+        }
+    }
+
+    pub fn new_map(map: Vec<(PlutusData, PlutusData)>) -> Self {
+        Self::Map(map)
+    }
+
+    pub fn new_list(list: Vec<PlutusData>) -> Self {
+        Self::List(list)
+    }
+
+    pub fn new_integer(integer: i128) -> Self {
+        Self::Integer(integer)
+    }
+
+    pub fn new_bytes(bytes: Vec<u8>) -> Self {
+        Self::Bytes(bytes)
+    }
+
+    fn cbor_type(&self) -> CBORType {
+        match self {
+            Self::Constr { .. } => CBORType::Tag,
+            Self::Map(_) => CBORType::Map,
+            Self::List(_) => CBORType::Array,
+            Self::Integer(integer) if *integer < 0 => CBORType::NegativeInteger,
+            Self::Integer(_) => CBORType::UnsignedInteger,
+            Self::Bytes(_) => CBORType::Bytes,
+        }
+    }
+}
+
+// reference token datum
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cip68Datum {
+    // entries in their original order, the keys are byte strings for the standard fields
+    pub metadata: Vec<(PlutusData, PlutusData)>,
+    pub version: u64,
+    pub extra: PlutusData,
+}
+
+impl Cip68Datum {
+    pub fn new(metadata: Vec<(PlutusData, PlutusData)>, version: u64) -> Self {
+        Self {
+            metadata,
+            version,
+            extra: PlutusData::Constr { alternative: 0, fields: Vec::new() },
+        }
+    }
+
+    // The NFT metadata map of the details. Texts longer than 64 bytes are given as arrays of
+    // chunks in MetadataDetails and are joined back into a single byte string here.
+    // Other properties follow the CIP-25 fields, with their texts as UTF-8 byte strings.
+    pub fn from_details(details: &MetadataDetails, version: u64, extra: PlutusData) -> Self {
+        let mut metadata = vec![
+            (field_key("name"), PlutusData::Bytes(details.name.get().as_bytes().to_vec())),
+            (field_key("image"), joined_bytes(&details.image)),
+        ];
+        if let Some(media_type) = &details.media_type {
+            metadata.push((field_key("mediaType"), PlutusData::Bytes(media_type.get().as_bytes().to_vec())));
+        }
+        if let Some(description) = &details.description {
+            metadata.push((field_key("description"), joined_bytes(description)));
+        }
+        if let Some(files) = &details.files {
+            let files = files
+                .iter()
+                .map(|file| PlutusData::Map(vec![
+                    (field_key("name"), PlutusData::Bytes(file.name.get().as_bytes().to_vec())),
+                    (field_key("mediaType"), PlutusData::Bytes(file.media_type.get().as_bytes().to_vec())),
+                    (field_key("src"), joined_bytes(&file.src)),
+                ].into_iter().chain(other_properties_data(&file.other_properties)).collect()))
+                .collect();
+            metadata.push((field_key("files"), PlutusData::List(files)));
+        }
+        metadata.extend(other_properties_data(&details.other_properties));
+        Self {
+            metadata,
+            version,
+            extra,
+        }
+    }
+
+    pub fn from_plutus_data(data: &PlutusData) -> Result<Self, DeserializeError> {
+        (|| -> Result<_, DeserializeError> {
+            let fields = match data {
+                PlutusData::Constr { alternative: 0, fields } => fields,
+                PlutusData::Constr { alternative, .. } => return Err(DeserializeFailure::FixedValueMismatch{ found: Key::Uint(*alternative), expected: Key::Uint(0) }.into()),
+                other => return Err(DeserializeFailure::CBOR(cbor_event::Error::Expected(CBORType::Tag, other.cbor_type())).into()),
+            };
+            if fields.len() != 3 {
+                return Err(DeserializeFailure::DefiniteLenMismatch(fields.len() as u64, Some(3)).into());
+            }
+            let metadata = match &fields[0] {
+                PlutusData::Map(metadata) => metadata.clone(),
+                other => return Err(DeserializeError::new("metadata", DeserializeFailure::CBOR(cbor_event::Error::Expected(CBORType::Map, other.cbor_type())))),
+            };
+            let version = match &fields[1] {
+                PlutusData::Integer(version) => u64::try_from(*version).map_err(|_| DeserializeError::new("version", DeserializeFailure::UnsupportedVersion(Key::Str(version.to_string()))))?,
+                other => return Err(DeserializeError::new("version", DeserializeFailure::CBOR(cbor_event::Error::Expected(CBORType::UnsignedInteger, other.cbor_type())))),
+            };
+            Ok(Self {
+                metadata,
+                version,
+                extra: fields[2].clone(),
+            })
+        })().map_err(|e| e.annotate("Cip68Datum"))
+    }
+
+    pub fn to_plutus_data(&self) -> PlutusData {
+        PlutusData::Constr {
+            alternative: 0,
+            fields: vec![
+                PlutusData::Map(self.metadata.clone()),
+                PlutusData::Integer(self.version as i128),
+                self.extra.clone(),
+            ],
+        }
+    }
+
+    // The metadata map read as CIP-25 details. Texts longer than 64 bytes are split in chunks
+    // (on character boundaries), except for name and mediaType which have no array form in
+    // CIP-25 and are an error. Other fields are kept as other properties, with UTF-8 byte
+    // strings read as texts. Plutus constructors can't be written as metadata and are an error.
+    pub fn details(&self) -> Result<MetadataDetails, DeserializeError> {
+        (|| -> Result<_, DeserializeError> {
+            let mut name = None;
+            let mut image = None;
+            let mut media_type = None;
+            let mut description = None;
+            let mut files = None;
+            let mut other_properties = BTreeMap::new();
+            for (key, value) in &self.metadata {
+                match key {
+                    PlutusData::Bytes(key) if key == b"name" => name = Some(string64(value).map_err(|e| field_error(e, "name"))?),
+                    PlutusData::Bytes(key) if key == b"image" => image = Some(chunked(value).map_err(|e| field_error(e, "image"))?),
+                    PlutusData::Bytes(key) if key == b"mediaType" => media_type = Some(string64(value).map_err(|e| field_error(e, "mediaType"))?),
+                    PlutusData::Bytes(key) if key == b"description" => description = Some(chunked(value).map_err(|e| field_error(e, "description"))?),
+                    PlutusData::Bytes(key) if key == b"files" => files = Some(files_details(value).map_err(|e| field_error(e, "files"))?),
+                    other_key => other_property(&mut other_properties, other_key, value)?,
+                }
+            }
+            let name = name.ok_or_else(|| DeserializeFailure::MandatoryFieldMissing(Key::Bytes(b"name".to_vec())))?;
+            let image = image.ok_or_else(|| DeserializeFailure::MandatoryFieldMissing(Key::Bytes(b"image".to_vec())))?;
+            Ok(MetadataDetails {
+                name,
+                image,
+                media_type,
+                description,
+                files,
+                other_properties,
+            })
+        })().map_err(|e| e.annotate("Cip68Datum::details"))
+    }
+}
+
+fn field_key(field: &str) -> PlutusData {
+    PlutusData::Bytes(field.as_bytes().to_vec())
+}
+
+fn field_error(e: DeserializeError, field: &str) -> DeserializeError {
+    e.annotate(field).annotate_path(PathSegment::Field(String::from(field)))
+}
+
+fn joined_bytes(text: &String64OrArrString64) -> PlutusData {
+    match text {
+        String64OrArrString64::String64(string64) => PlutusData::Bytes(string64.get().as_bytes().to_vec()),
+        String64OrArrString64::ArrString64(chunks) => PlutusData::Bytes(chunks.iter().flat_map(|chunk| chunk.get().bytes()).collect()),
+    }
+}
+
+// a byte string, or a list of them for values that were split by the minting tool
+fn text(value: &PlutusData) -> Result<String, DeserializeError> {
+    let bytes = match value {
+        PlutusData::Bytes(bytes) => bytes.clone(),
+        PlutusData::List(chunks) => {
+            let mut bytes = Vec::new();
+            for (index, chunk) in chunks.iter().enumerate() {
+                match chunk {
+                    PlutusData::Bytes(chunk) => bytes.extend_from_slice(chunk),
+                    other => return Err(DeserializeError::new("text", DeserializeFailure::CBOR(cbor_event::Error::Expected(CBORType::Bytes, other.cbor_type()))).annotate_path(PathSegment::Index(index))),
+                }
+            }
+            bytes
+        },
+        other => return Err(DeserializeFailure::CBOR(cbor_event::Error::Expected(CBORType::Bytes, other.cbor_type())).into()),
+    };
+    Ok(String::from_utf8(bytes).map_err(cbor_event::Error::from)?)
+}
+
+fn string64(value: &PlutusData) -> Result<String64, DeserializeError> {
+    let text = text(value)?;
+    if text.len() > 64 {
+        return Err(DeserializeFailure::CBOR(cbor_event::Error::CustomError(format!("text of {} bytes can't be split in chunks, CIP-25 allows at most 64 bytes for this field", text.len()))).into());
+    }
+    String64::new(text)
+}
+
+fn chunked(value: &PlutusData) -> Result<String64OrArrString64, DeserializeError> {
+    let text = text(value)?;
+    if text.len() <= 64 {
+        return Ok(String64OrArrString64::String64(String64::new(text)?));
+    }
+    Ok(String64OrArrString64::ArrString64(text_chunks(&text).into_iter().map(String64::new).collect::<Result<_, _>>()?))
+}
+
+// on character boundaries, at most 64 bytes each
+fn text_chunks(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    for c in text.chars() {
+        if chunk.len() + c.len_utf8() > 64 {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        chunk.push(c);
+    }
+    chunks.push(chunk);
+    chunks
+}
+
+fn other_property(other_properties: &mut BTreeMap<String, TransactionMetadatum>, key: &PlutusData, value: &PlutusData) -> Result<(), DeserializeError> {
+    let key = match key {
+        PlutusData::Bytes(key) => String::from_utf8(key.clone()).map_err(cbor_event::Error::from)?,
+        other => return Err(DeserializeFailure::UnexpectedKeyType(other.cbor_type()).into()),
+    };
+    if other_properties.contains_key(&key) {
+        return Err(DeserializeFailure::DuplicateKey(Key::Str(key)).into());
+    }
+    (|| -> Result<_, DeserializeError> {
+        check_metadatum_len(key.len())?;
+        other_properties.insert(key.clone(), metadatum(value)?);
+        Ok(())
+    })().map_err(|e| e.annotate_path(PathSegment::Field(key.clone())))
+}
+
+// UTF-8 byte strings become texts, texts and bytes longer than 64 bytes lists of chunks
+fn metadatum(value: &PlutusData) -> Result<TransactionMetadatum, DeserializeError> {
+    match value {
+        PlutusData::Constr { .. } => Err(DeserializeFailure::CBOR(cbor_event::Error::CustomError(String::from("Plutus constructors can't be written as metadata"))).into()),
+        PlutusData::Map(map) => Ok(TransactionMetadatum::Map(map
+            .iter()
+            .map(|(key, value)| Ok((metadatum(key)?, metadatum(value)?)))
+            .collect::<Result<_, DeserializeError>>()?)),
+        PlutusData::List(list) => Ok(TransactionMetadatum::List(list
+            .iter()
+            .enumerate()
+            .map(|(index, element)| metadatum(element).map_err(|e| e.annotate_path(PathSegment::Index(index))))
+            .collect::<Result<_, _>>()?)),
+        PlutusData::Integer(integer) => {
+            if *integer > u64::MAX as i128 || *integer < -(u64::MAX as i128) - 1 {
+                return Err(DeserializeFailure::CBOR(cbor_event::Error::CustomError(format!("{} does not fit in a metadata integer", integer))).into());
+            }
+            Ok(TransactionMetadatum::Int(*integer))
+        },
+        PlutusData::Bytes(bytes) => Ok(match std::str::from_utf8(bytes) {
+            Ok(text) if text.len() <= 64 => TransactionMetadatum::Text(text.to_owned()),
+            Ok(text) => TransactionMetadatum::List(text_chunks(text).into_iter().map(TransactionMetadatum::Text).collect()),
+            Err(_) if bytes.len() <= 64 => TransactionMetadatum::Bytes(bytes.clone()),
+            Err(_) => TransactionMetadatum::List(bytes.chunks(64).map(|chunk| TransactionMetadatum::Bytes(chunk.to_vec())).collect()),
+        }),
+    }
+}
+
+fn other_properties_data(other_properties: &BTreeMap<String, TransactionMetadatum>) -> Vec<(PlutusData, PlutusData)> {
+    other_properties
+        .iter()
+        .map(|(key, value)| (field_key(key), plutus_data(value)))
+        .collect()
+}
+
+fn plutus_data(value: &TransactionMetadatum) -> PlutusData {
+    match value {
+        TransactionMetadatum::Map(map) => PlutusData::Map(map.iter().map(|(key, value)| (plutus_data(key), plutus_data(value))).collect()),
+        TransactionMetadatum::List(list) => PlutusData::List(list.iter().map(plutus_data).collect()),
+        TransactionMetadatum::Int(int) => PlutusData::Integer(*int),
+        TransactionMetadatum::Bytes(bytes) => PlutusData::Bytes(bytes.clone()),
+        TransactionMetadatum::Text(text) => PlutusData::Bytes(text.as_bytes().to_vec()),
+    }
+}
+
+fn files_details(value: &PlutusData) -> Result<Vec<FilesDetails>, DeserializeError> {
+    let files = match value {
+        PlutusData::List(files) => files,
+        other => return Err(DeserializeFailure::CBOR(cbor_event::Error::Expected(CBORType::Array, other.cbor_type())).into()),
+    };
+    files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| file_details(file).map_err(|e| e.annotate_path(PathSegment::Index(index))))
+        .collect()
+}
+
+fn file_details(value: &PlutusData) -> Result<FilesDetails, DeserializeError> {
+    let entries = match value {
+        PlutusData::Map(entries) => entries,
+        other => return Err(DeserializeFailure::CBOR(cbor_event::Error::Expected(CBORType::Map, other.cbor_type())).into()),
+    };
+    let mut name = None;
+    let mut media_type = None;
+    let mut src = None;
+    let mut other_properties = BTreeMap::new();
+    for (key, value) in entries {
+        match key {
+            PlutusData::Bytes(key) if key == b"name" => name = Some(string64(value).map_err(|e| field_error(e, "name"))?),
+            PlutusData::Bytes(key) if key == b"mediaType" => media_type = Some(string64(value).map_err(|e| field_error(e, "mediaType"))?),
+            PlutusData::Bytes(key) if key == b"src" => src = Some(chunked(value).map_err(|e| field_error(e, "src"))?),
+            other_key => other_property(&mut other_properties, other_key, value)?,
+        }
+    }
+    Ok(FilesDetails {
+        name: name.ok_or_else(|| DeserializeFailure::MandatoryFieldMissing(Key::Bytes(b"name".to_vec())))?,
+        media_type: media_type.ok_or_else(|| DeserializeFailure::MandatoryFieldMissing(Key::Bytes(b"mediaType".to_vec())))?,
+        src: src.ok_or_else(|| DeserializeFailure::MandatoryFieldMissing(Key::Bytes(b"src".to_vec())))?,
+        other_properties,
+    })
+}
+
+// Reference token datums of some policies, e.g. read from the UTxOs holding the reference
+// tokens. Keyed by the asset name of the reference token.
+#[derive(Clone, Debug, Default)]
+pub struct Cip68Metadata {
+    pub data: BTreeMap<PolicyIdV2, BTreeMap<AssetNameV2, Cip68Datum>>,
+}
+
+impl Cip68Metadata {
+    pub fn new(data: BTreeMap<PolicyIdV2, BTreeMap<AssetNameV2, Cip68Datum>>) -> Self {
+        Self {
+            data,
+        }
+    }
+}
+
+impl cbor_event::se::Serialize for PlutusData {
+    fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
+        match self {
+            PlutusData::Constr { alternative, fields } => {
+                match alternative {
+                    0..=6 => {
+                        serializer.write_tag(CONSTR_TAGS.start() + alternative)?;
+                    },
+                    7..=127 => {
+                        serializer.write_tag(CONSTR_EXTENDED_TAGS.start() + alternative - 7)?;
+                    },
+                    _ => {
+                        serializer.write_tag(CONSTR_GENERAL_TAG)?;
+                        serializer.write_array(cbor_event::Len::Len(2))?;
+                        serializer.write_unsigned_integer(*alternative)?;
+                    },
+                }
+                serialize_list(serializer, fields)
+            },
+            PlutusData::Map(map) => {
+                serializer.write_map(cbor_event::Len::Len(map.len() as u64))?;
+                for (key, value) in map.iter() {
+                    key.serialize(serializer)?;
+                    value.serialize(serializer)?;
+                }
+                Ok(serializer)
+            },
+            PlutusData::List(list) => serialize_list(serializer, list),
+            PlutusData::Integer(integer) => {
+                if *integer >= 0 {
+                    match u64::try_from(*integer) {
+                        Ok(uint) => serializer.write_unsigned_integer(uint),
+                        Err(_) => {
+                            serializer.write_tag(POSITIVE_BIGNUM_TAG)?;
+                            serialize_bytes(serializer, &bignum_bytes(*integer as u128))
+                        },
+                    }
+                } else {
+                    let nint_arg = -(*integer + 1);
+                    match u64::try_from(nint_arg) {
+                        Ok(nint_arg) => serializer.write_negative_integer_sz(*integer, cbor_event::Sz::canonical(nint_arg)),
+                        Err(_) => {
+                            serializer.write_tag(NEGATIVE_BIGNUM_TAG)?;
+                            serialize_bytes(serializer, &bignum_bytes(nint_arg as u128))
+                        },
+                    }
+                }
+            },
+            PlutusData::Bytes(bytes) => serialize_bytes(serializer, bytes),
+        }
+    }
+}
+
+// same as the ledger: indefinite length unless empty
+fn serialize_list<'se, W: Write>(serializer: &'se mut Serializer<W>, list: &[PlutusData]) -> cbor_event::Result<&'se mut Serializer<W>> {
+    if list.is_empty() {
+        return serializer.write_array(cbor_event::Len::Len(0));
+    }
+    serializer.write_array(cbor_event::Len::Indefinite)?;
+    for element in list.iter() {
+        element.serialize(serializer)?;
+    }
+    serializer.write_special(CBORSpecial::Break)
+}
+
+fn serialize_bytes<'se, W: Write>(serializer: &'se mut Serializer<W>, bytes: &[u8]) -> cbor_event::Result<&'se mut Serializer<W>> {
+    if bytes.len() <= BYTES_CHUNK_SIZE {
+        return serializer.write_bytes(bytes);
+    }
+    let chunks = bytes
+        .chunks(BYTES_CHUNK_SIZE)
+        .map(|chunk| (chunk.len() as u64, cbor_event::Sz::canonical(chunk.len() as u64)))
+        .collect();
+    serializer.write_bytes_sz(bytes, cbor_event::StringLenSz::Indefinite(chunks))
+}
+
+// big-endian without leading zeros
+fn bignum_bytes(value: u128) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let leading_zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+    bytes[leading_zeros..].to_vec()
+}
+
+fn bignum_value(bytes: &[u8]) -> Result<i128, DeserializeError> {
+    let leading_zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+    let bytes = &bytes[leading_zeros..];
+    if bytes.len() > 16 {
+        return Err(DeserializeFailure::RangeCheck{ found: bytes.len(), min: Some(0), max: Some(16) }.into());
+    }
+    let value = bytes.iter().fold(0u128, |value, byte| (value << 8) | *byte as u128);
+    i128::try_from(value).map_err(|_| DeserializeFailure::RangeCheck{ found: bytes.len(), min: Some(0), max: Some(15) }.into())
+}
+
+fn deserialize_list<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Vec<PlutusData>, DeserializeError> {
+    let mut list = Vec::new();
+    let len = raw.array()?;
+    while match len { cbor_event::Len::Len(n) => list.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+        if raw.cbor_type()? == CBORType::Special {
+            match raw.special()? {
+                CBORSpecial::Break => break,
+                _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+            }
+        }
+        let index = list.len();
+        list.push(PlutusData::deserialize(raw).map_err(|e| e.annotate_path(PathSegment::Index(index)))?);
+    }
+    Ok(list)
+}
+
+impl Deserialize for PlutusData {
+    fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
+        (|| -> Result<_, DeserializeError> {
+            match raw.cbor_type()? {
+                CBORType::Tag => match raw.tag()? {
+                    tag if CONSTR_TAGS.contains(&tag) => Ok(Self::Constr { alternative: tag - CONSTR_TAGS.start(), fields: deserialize_list(raw)? }),
+                    tag if CONSTR_EXTENDED_TAGS.contains(&tag) => Ok(Self::Constr { alternative: tag - CONSTR_EXTENDED_TAGS.start() + 7, fields: deserialize_list(raw)? }),
+                    CONSTR_GENERAL_TAG => {
+                        let len = raw.array()?;
+                        let mut read_len = CBORReadLen::new(len);
+                        read_len.read_elems(2)?;
+                        let alternative = raw.unsigned_integer()?;
+                        let fields = deserialize_list(raw)?;
+                        read_len.finish()?;
+                        if len == cbor_event::Len::Indefinite && raw.special()? != CBORSpecial::Break {
+                            return Err(DeserializeFailure::EndingBreakMissing.into());
+                        }
+                        Ok(Self::Constr { alternative, fields })
+                    },
+                    POSITIVE_BIGNUM_TAG => Ok(Self::Integer(bignum_value(&raw.bytes()?)?)),
+                    NEGATIVE_BIGNUM_TAG => Ok(Self::Integer(-bignum_value(&raw.bytes()?)? - 1)),
+                    tag => Err(DeserializeFailure::TagMismatch{ found: tag, expected: *CONSTR_TAGS.start() }.into()),
+                },
+                CBORType::Map => {
+                    let mut map = Vec::new();
+                    let len = raw.map()?;
+                    while match len { cbor_event::Len::Len(n) => map.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+                        if raw.cbor_type()? == CBORType::Special {
+                            match raw.special()? {
+                                CBORSpecial::Break => break,
+                                _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                            }
+                        }
+                        let key = PlutusData::deserialize(raw)?;
+                        let value = PlutusData::deserialize(raw)?;
+                        map.push((key, value));
+                    }
+                    Ok(Self::Map(map))
+                },
+                CBORType::Array => Ok(Self::List(deserialize_list(raw)?)),
+                CBORType::UnsignedInteger => Ok(Self::Integer(raw.unsigned_integer()? as i128)),
+                CBORType::NegativeInteger => Ok(Self::Integer(raw.negative_integer_sz()?.0)),
+                CBORType::Bytes => Ok(Self::Bytes(raw.bytes()?)),
+                _ => Err(DeserializeFailure::NoVariantMatched.into()),
+            }
+        })().map_err(|e| e.annotate("PlutusData"))
+    }
+}
+
+impl cbor_event::se::Serialize for Cip68Datum {
+    fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
+        self.to_plutus_data().serialize(serializer)
+    }
+}
+
+impl Deserialize for Cip68Datum {
+    fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
+        Cip68Datum::from_plutus_data(&PlutusData::deserialize(raw)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &PlutusData) -> Vec<u8> {
+        let bytes = data.to_bytes().unwrap();
+        assert_eq!(&PlutusData::from_bytes(bytes.clone()).unwrap(), data);
+        bytes
+    }
+
+    #[test]
+    fn constructor_tags() {
+        let fields = vec![PlutusData::new_integer(1)];
+        for (alternative, prefix) in [(0, "d8799f"), (6, "d87f9f"), (7, "d905009f"), (127, "d905789f"), (128, "d8668218809f"), (u64::MAX, "d866821bffffffffffffffff9f")] {
+            let bytes = round_trip(&PlutusData::new_constr(alternative, fields.clone()));
+            assert_eq!(hex::encode(bytes), format!("{}01ff", prefix), "alternative {}", alternative);
+        }
+        // no fields is a definite empty list
+        assert_eq!(hex::encode(round_trip(&PlutusData::new_constr(0, Vec::new()))), "d87980");
+        // a definite list of fields, as other encoders write them
+        assert_eq!(PlutusData::from_bytes(hex::decode("d8798101").unwrap()).unwrap(), PlutusData::new_constr(0, fields));
+        let err = PlutusData::from_bytes(hex::decode("d8788101").unwrap()).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::TagMismatch{ found: 120, .. }), "{}", err);
+    }
+
+    #[test]
+    fn bignums() {
+        let cases = [
+            (u64::MAX as i128, "1bffffffffffffffff"),
+            (u64::MAX as i128 + 1, "c249010000000000000000"),
+            (i128::MAX, "c2507fffffffffffffffffffffffffffffff"),
+            (-(u64::MAX as i128) - 1, "3bffffffffffffffff"),
+            (-(u64::MAX as i128) - 2, "c349010000000000000000"),
+            (i128::MIN, "c3507fffffffffffffffffffffffffffffff"),
+        ];
+        for (integer, expected) in cases {
+            assert_eq!(hex::encode(round_trip(&PlutusData::new_integer(integer))), expected, "{}", integer);
+        }
+        // leading zeros are accepted, values not fitting an i128 aren't
+        assert_eq!(PlutusData::from_bytes(hex::decode("c24300002a").unwrap()).unwrap(), PlutusData::new_integer(42));
+        assert!(PlutusData::from_bytes(hex::decode("c25080000000000000000000000000000000").unwrap()).is_err());
+    }
+
+    #[test]
+    fn chunked_bytes() {
+        assert_eq!(round_trip(&PlutusData::new_bytes(vec![1; 64]))[..2], [0x58, 64]);
+        // indefinite bytes of 64 byte chunks, each with its own header
+        for (len, encoded_len) in [(65, 1 + 2 + 64 + 1 + 1 + 1), (128, 1 + 2 * (2 + 64) + 1), (200, 1 + 3 * (2 + 64) + 1 + 8 + 1)] {
+            let bytes = round_trip(&PlutusData::new_bytes(vec![1; len]));
+            assert_eq!(bytes[..3], [0x5f, 0x58, 64]);
+            assert_eq!(bytes.len(), encoded_len, "{} bytes", len);
+        }
+        // bignums are chunked the same way
+        assert_eq!(PlutusData::from_bytes(hex::decode("c25f41014102ff").unwrap()).unwrap(), PlutusData::new_integer(0x0102));
+    }
+
+    #[test]
+    fn nested_round_trip() {
+        round_trip(&PlutusData::new_map(vec![
+            (PlutusData::new_bytes(b"name".to_vec()), PlutusData::new_list(vec![PlutusData::new_integer(-1), PlutusData::new_bytes(Vec::new())])),
+            (PlutusData::new_integer(0), PlutusData::new_constr(3, vec![PlutusData::new_map(Vec::new())])),
+        ]));
+    }
+
+    #[test]
+    fn special_instead_of_break_is_an_error() {
+        for datum in ["d8799ff5ff", "d8799fbff501ffff", "bff501ff"] {
+            let err = PlutusData::from_bytes(hex::decode(datum).unwrap()).unwrap_err();
+            assert!(matches!(err.failure(), DeserializeFailure::EndingBreakMissing), "{}: {}", datum, err);
+        }
+        assert!(Cip68Datum::from_bytes(hex::decode("d8799ff5ff").unwrap()).is_err());
+    }
+
+    fn bytes(text: &str) -> PlutusData {
+        PlutusData::new_bytes(text.as_bytes().to_vec())
+    }
+
+    fn details() -> MetadataDetails {
+        let long_text = "a".repeat(100);
+        let mut details = MetadataDetails::new(String64::new(String::from("NFT")).unwrap(), String64OrArrString64::new_arr_string64(vec![String64::new(long_text[..64].to_owned()).unwrap(), String64::new(long_text[64..].to_owned()).unwrap()]));
+        details.media_type = Some(String64::new(String::from("image/png")).unwrap());
+        details.description = Some(String64OrArrString64::new_string64(String64::new(String::from("description")).unwrap()));
+        let mut file = FilesDetails::new(String64::new(String::from("file")).unwrap(), String64::new(String::from("audio/mpeg")).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("ipfs://file")).unwrap()));
+        file.other_properties.insert(String::from("song"), TransactionMetadatum::new_int(1));
+        details.files = Some(vec![file]);
+        details.other_properties.insert(String::from("artist"), TransactionMetadatum::Text(String::from("someone")));
+        details.other_properties.insert(String::from("hash"), TransactionMetadatum::Bytes(vec![0xff; 4]));
+        details.other_properties.insert(String::from("traits"), TransactionMetadatum::new_map(vec![(TransactionMetadatum::Text(String::from("rank")), TransactionMetadatum::new_list(vec![TransactionMetadatum::new_int(-1), TransactionMetadatum::new_int(u64::MAX as i128)]))]));
+        details
+    }
+
+    #[test]
+    fn details_round_trip() {
+        let extra = PlutusData::new_integer(7);
+        let datum = Cip68Datum::from_details(&details(), 2, extra.clone());
+        assert_eq!(datum.metadata[1], (bytes("image"), bytes(&"a".repeat(100))));
+        assert_eq!(datum.metadata[5], (bytes("artist"), bytes("someone")));
+        let decoded = datum.details().unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), details().to_bytes().unwrap());
+        assert_eq!(Cip68Datum::from_details(&decoded, 2, extra), datum);
+        // and through CBOR
+        assert_eq!(Cip68Datum::from_bytes(datum.to_bytes().unwrap()).unwrap(), datum);
+    }
+
+    #[test]
+    fn details_keep_other_fields() {
+        let datum = Cip68Datum::new(vec![
+            (bytes("name"), bytes("NFT")),
+            (bytes("image"), bytes("ipfs://image")),
+            (bytes("lyrics"), bytes(&"é".repeat(40))),
+            (bytes("id"), PlutusData::new_bytes(vec![0xff; 70])),
+        ], 1);
+        let details = datum.details().unwrap();
+        let lyrics = match &details.other_properties["lyrics"] {
+            TransactionMetadatum::List(chunks) => chunks.clone(),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(lyrics, vec![TransactionMetadatum::Text("é".repeat(32)), TransactionMetadatum::Text("é".repeat(8))]);
+        assert_eq!(details.other_properties["id"], TransactionMetadatum::new_list(vec![TransactionMetadatum::Bytes(vec![0xff; 64]), TransactionMetadatum::Bytes(vec![0xff; 6])]));
+        assert!(details.to_bytes().is_ok());
+    }
+
+    #[test]
+    fn details_errors() {
+        let datum = |field: &str, value: PlutusData| Cip68Datum::new(vec![(bytes("name"), bytes("NFT")), (bytes("image"), bytes("ipfs://image")), (bytes(field), value)], 1);
+        // CIP-25 names can't be split
+        let err = datum("name", bytes(&"a".repeat(70))).details().unwrap_err();
+        assert_eq!(err.path(), [PathSegment::Field(String::from("name"))]);
+        assert!(err.to_string().contains("text of 70 bytes can't be split in chunks"), "{}", err);
+        let err = datum("extra", PlutusData::new_list(vec![PlutusData::new_constr(0, Vec::new())])).details().unwrap_err();
+        assert_eq!(err.path(), [PathSegment::Field(String::from("extra")), PathSegment::Index(0)]);
+        assert!(err.to_string().contains("Plutus constructors"), "{}", err);
+        let err = datum("extra", PlutusData::new_integer(u64::MAX as i128 + 1)).details().unwrap_err();
+        assert_eq!(err.path(), [PathSegment::Field(String::from("extra"))]);
+        let mut duplicate = datum("artist", bytes("a"));
+        duplicate.metadata.push((bytes("artist"), bytes("b")));
+        let err = duplicate.details().unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::DuplicateKey(Key::Str(key)) if key == "artist"), "{}", err);
+        let mut integer_key = datum("artist", bytes("a"));
+        integer_key.metadata.push((PlutusData::new_integer(0), bytes("b")));
+        let err = integer_key.details().unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::UnexpectedKeyType(CBORType::UnsignedInteger)), "{}", err);
+    }
+}
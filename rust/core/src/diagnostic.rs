@@ -0,0 +1,576 @@
+// Decoding of Metadata that keeps going after recoverable errors in order to report
+// all of them at once instead of only the first one:
+//  - an asset with an invalid or missing mandatory field (name, image) is left out
+//  - an invalid optional field (mediaType, description, a file) is dropped
+//  - unknown keys of the 721 map and duplicates are skipped, the first occurrence is kept
+//  - policy ids, asset names and other property keys longer than 64 bytes are skipped
+//  - a special value (true, null, ...) where an indefinite map or array can end is skipped
+// Malformed CBOR still stops the decoding as the rest of the input can't be trusted.
+
+use super::*;
+use crate::serialization::label_shape;
+use crate::transaction::skip;
+use std::io::{Seek, SeekFrom};
+
+pub struct PartialDecode<T> {
+    // None when nothing could be decoded (malformed CBOR, no 721 label, ...)
+    pub value: Option<T>,
+    // in the order they were found in the input
+    pub errors: Vec<DeserializeError>,
+}
+
+impl<T> PartialDecode<T> {
+    pub fn is_valid(&self) -> bool {
+        self.value.is_some() && self.errors.is_empty()
+    }
+}
+
+impl Metadata {
+    pub fn from_bytes_diagnostic(data: Vec<u8>) -> PartialDecode<Metadata> {
+        let mut raw = Deserializer::from(std::io::Cursor::new(data));
+        let mut collector = Collector::default();
+        let value = match collector.metadata(&mut raw) {
+            Ok(value) => value,
+            Err(e) => {
+                let offset = position(&mut raw);
+                collector.record(e.with_offset(offset));
+                None
+            },
+        };
+        PartialDecode {
+            value,
+            errors: collector.errors,
+        }
+    }
+}
+
+fn position<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> u64 {
+    raw.as_mut_ref().stream_position().unwrap()
+}
+
+#[derive(Default)]
+struct Collector {
+    errors: Vec<DeserializeError>,
+    // where the walk currently is. It is not popped when a fatal error returns early
+    // so that the error can still be located.
+    path: Vec<PathSegment>,
+}
+
+impl Collector {
+    fn record(&mut self, e: DeserializeError) {
+        let e = self.path.iter().rev().fold(e, |e, segment| e.annotate_path(segment.clone()));
+        self.errors.push(e);
+    }
+
+    fn record_at(&mut self, location: &str, offset: u64, failure: DeserializeFailure) {
+        self.record(DeserializeError::new(location, failure).with_offset(offset));
+    }
+
+    // decodes a value, on failure the error is recorded and the value skipped
+    fn recover<R: BufRead + Seek, T>(
+        &mut self,
+        raw: &mut Deserializer<R>,
+        decode: impl FnOnce(&mut Deserializer<R>) -> Result<T, DeserializeError>,
+    ) -> Result<Option<T>, DeserializeError> {
+        let initial_position = position(raw);
+        match decode(raw) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => {
+                let offset = position(raw);
+                self.record(e.with_offset(offset));
+                raw.as_mut_ref().seek(SeekFrom::Start(initial_position)).unwrap();
+                skip(raw)?;
+                Ok(None)
+            },
+        }
+    }
+
+    fn in_path<T>(&mut self, segment: PathSegment, walk: impl FnOnce(&mut Self) -> Result<T, DeserializeError>) -> Result<T, DeserializeError> {
+        self.path.push(segment);
+        let result = walk(self)?;
+        self.path.pop();
+        Ok(result)
+    }
+
+    // calls entry for each key/value pair. Returns false (after recording it) if the value is not a map.
+    fn map_entries<R: BufRead + Seek>(
+        &mut self,
+        raw: &mut Deserializer<R>,
+        mut entry: impl FnMut(&mut Self, &mut Deserializer<R>) -> Result<(), DeserializeError>,
+    ) -> Result<bool, DeserializeError> {
+        let len = match self.recover(raw, |raw| Ok(raw.map()?))? {
+            Some(len) => len,
+            None => return Ok(false),
+        };
+        let mut read = 0;
+        while match len { cbor_event::Len::Len(n) => read < n as usize, cbor_event::Len::Indefinite => true, } {
+            // in a definite length map a special key is left to entry, as any unexpected key
+            if len == cbor_event::Len::Indefinite && raw.cbor_type()? == CBORType::Special {
+                let key_position = position(raw);
+                match raw.special()? {
+                    CBORSpecial::Break => break,
+                    _ => {
+                        self.record_at("map", key_position, DeserializeFailure::EndingBreakMissing);
+                        skip(raw)?;
+                    },
+                }
+            } else {
+                entry(self, raw)?;
+            }
+            read += 1;
+        }
+        Ok(true)
+    }
+
+    // reads a text key, anything else is recorded and the whole entry skipped
+    fn text_key<R: BufRead + Seek>(&mut self, raw: &mut Deserializer<R>, location: &str) -> Result<Option<String>, DeserializeError> {
+        let key_position = position(raw);
+        match raw.cbor_type()? {
+            CBORType::Text => Ok(Some(raw.text()?)),
+            other_type => {
+                self.record_at(location, key_position, DeserializeFailure::UnexpectedKeyType(other_type));
+                skip(raw)?;
+                skip(raw)?;
+                Ok(None)
+            },
+        }
+    }
+
+    fn metadata<R: BufRead + Seek>(&mut self, raw: &mut Deserializer<R>) -> Result<Option<Metadata>, DeserializeError> {
+        let map_position = position(raw);
+        let mut key_721 = None;
+        let is_map = self.map_entries(raw, |this, raw| {
+            let key_position = position(raw);
+            match raw.cbor_type()? {
+                CBORType::UnsignedInteger => match raw.unsigned_integer()? {
+                    721 if key_721.is_some() => {
+                        this.record_at("Metadata", key_position, DeserializeFailure::DuplicateKey(Key::Uint(721)));
+                        skip(raw)?;
+                    },
+                    721 => key_721 = Some(this.in_path(PathSegment::Field(String::from("721")), |this| this.label_metadata(raw))?),
+                    // other labels are not CIP-25
+                    _ => skip(raw)?,
+                },
+                _ => {
+                    skip(raw)?;
+                    skip(raw)?;
+                },
+            }
+            Ok(())
+        })?;
+        if !is_map {
+            return Ok(None);
+        }
+        match key_721 {
+            Some(key_721) => Ok(key_721.map(|key_721| Metadata { key_721 })),
+            None => {
+                self.record_at("Metadata", map_position, DeserializeFailure::MandatoryFieldMissing(Key::Uint(721)));
+                Ok(None)
+            },
+        }
+    }
+
+    fn label_metadata<R: BufRead + Seek>(&mut self, raw: &mut Deserializer<R>) -> Result<Option<LabelMetadata>, DeserializeError> {
+        // same as LabelMetadata::deserialize, the version decides the shape
+        let initial_position = position(raw);
+        let (version, has_data) = label_shape(raw)?;
+        raw.as_mut_ref().seek(SeekFrom::Start(initial_position)).unwrap();
+        let version = match version {
+            Some(Ok(version)) => match version_from_metadatum(version.clone()) {
+                Ok(version) => Some(version),
+                Err(_) if is_newer_version(&version) => {
+                    return Ok(self.recover(raw, TransactionMetadatum::deserialize)?.map(|raw| LabelMetadata::Unknown {
+                        version,
+                        raw,
+                    }));
+                },
+                Err(e) => {
+                    self.path.push(PathSegment::Field(String::from(VERSION_KEY)));
+                    self.record(e.annotate("version").with_offset(initial_position));
+                    self.path.pop();
+                    None
+                },
+            },
+            Some(Err(e)) => {
+                self.path.push(PathSegment::Field(String::from(VERSION_KEY)));
+                self.record(e.annotate("version"));
+                self.path.pop();
+                None
+            },
+            None => None,
+        };
+        let is_v2 = match version {
+            Some((number, _)) => number == 2,
+            // an invalid version, guessed from the shape
+            None => has_data,
+        };
+        if is_v2 {
+            let version = version.map(|(_, format)| format).unwrap_or(VersionFormat::Uint);
+            Ok(self.label_metadata_v2(raw, version)?.map(LabelMetadata::LabelMetadataV2))
+        } else {
+            let version = version.map(|(_, format)| format);
+            Ok(self.label_metadata_v1(raw, version)?.map(LabelMetadata::LabelMetadataV1))
+        }
+    }
+
+    fn label_metadata_v1<R: BufRead + Seek>(&mut self, raw: &mut Deserializer<R>, version: Option<VersionFormat>) -> Result<Option<LabelMetadataV1>, DeserializeError> {
+        let mut data = BTreeMap::new();
+        let is_map = self.map_entries(raw, |this, raw| {
+            let key_position = position(raw);
+            let key = match this.text_key(raw, "LabelMetadataV1")? {
+                Some(key) => key,
+                None => return Ok(()),
+            };
+            if key == VERSION_KEY {
+                // already checked when choosing the version
+                return skip(raw);
+            }
+            this.in_path(PathSegment::PolicyId(key.clone()), |this| {
+                let policy_id = match String64::new(key) {
+                    Ok(policy_id) => policy_id,
+                    Err(e) => {
+                        this.record(e.with_offset(key_position));
+                        return skip(raw);
+                    },
+                };
+                if data.contains_key(&policy_id) {
+                    this.record_at("LabelMetadataV1", key_position, DeserializeFailure::DuplicateKey(Key::Str(policy_id.get().clone())));
+                    return skip(raw);
+                }
+                let mut assets = BTreeMap::new();
+                this.map_entries(raw, |this, raw| {
+                    let key_position = position(raw);
+                    let key = match this.text_key(raw, "LabelMetadataV1")? {
+                        Some(key) => key,
+                        None => return Ok(()),
+                    };
+                    this.in_path(PathSegment::AssetName(key.clone()), |this| {
+                        let asset_name = match String64::new(key) {
+                            Ok(asset_name) => asset_name,
+                            Err(e) => {
+                                this.record(e.with_offset(key_position));
+                                return skip(raw);
+                            },
+                        };
+                        if assets.contains_key(&asset_name) {
+                            this.record_at("LabelMetadataV1", key_position, DeserializeFailure::DuplicateKey(Key::Str(asset_name.get().clone())));
+                            return skip(raw);
+                        }
+                        if let Some(details) = this.metadata_details(raw)? {
+                            assets.insert(asset_name, details);
+                        }
+                        Ok(())
+                    })
+                })?;
+                data.insert(policy_id, assets);
+                Ok(())
+            })
+        })?;
+        Ok(if is_map { Some(LabelMetadataV1 { data, version }) } else { None })
+    }
+
+    fn label_metadata_v2<R: BufRead + Seek>(&mut self, raw: &mut Deserializer<R>, version: VersionFormat) -> Result<Option<LabelMetadataV2>, DeserializeError> {
+        let map_position = position(raw);
+        let mut data = None;
+        let is_map = self.map_entries(raw, |this, raw| {
+            let key_position = position(raw);
+            match this.text_key(raw, "LabelMetadataV2")?.as_deref() {
+                Some("data") if data.is_some() => {
+                    this.record_at("LabelMetadataV2", key_position, DeserializeFailure::DuplicateKey(Key::Str(String::from("data"))));
+                    skip(raw)
+                },
+                Some("data") => {
+                    data = Some(this.in_path(PathSegment::Field(String::from("data")), |this| this.data_v2(raw))?);
+                    Ok(())
+                },
+                // already checked when choosing the version
+                Some(VERSION_KEY) => skip(raw),
+                Some(unknown_key) => {
+                    this.record_at("LabelMetadataV2", key_position, DeserializeFailure::UnknownKey(Key::Str(unknown_key.to_owned())));
+                    skip(raw)
+                },
+                None => Ok(()),
+            }
+        })?;
+        if !is_map {
+            return Ok(None);
+        }
+        let data = match data {
+            Some(data) => data,
+            None => {
+                self.record_at("LabelMetadataV2", map_position, DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from("data"))));
+                BTreeMap::new()
+            },
+        };
+        Ok(Some(LabelMetadataV2 { data, version }))
+    }
+
+    fn data_v2<R: BufRead + Seek>(&mut self, raw: &mut Deserializer<R>) -> Result<Data, DeserializeError> {
+        let mut data = BTreeMap::new();
+        self.map_entries(raw, |this, raw| {
+            let policy_id = match this.recover(raw, |raw| Ok(raw.bytes()?))? {
+                Some(policy_id) => policy_id,
+                None => return skip(raw),
+            };
+            let key_position = position(raw);
+            this.in_path(PathSegment::PolicyId(hex::encode(&policy_id)), |this| {
+                if let Err(failure) = check_metadatum_len(policy_id.len()) {
+                    this.record_at("LabelMetadataV2", key_position, failure);
+                    return skip(raw);
+                }
+                if data.contains_key(&policy_id) {
+                    this.record_at("LabelMetadataV2", key_position, DeserializeFailure::DuplicateKey(Key::Bytes(policy_id)));
+                    return skip(raw);
+                }
+                let mut assets = BTreeMap::new();
+                this.map_entries(raw, |this, raw| {
+                    let asset_name = match this.recover(raw, |raw| Ok(raw.bytes()?))? {
+                        Some(asset_name) => asset_name,
+                        None => return skip(raw),
+                    };
+                    let key_position = position(raw);
+                    this.in_path(PathSegment::AssetName(hex::encode(&asset_name)), |this| {
+                        if let Err(failure) = check_metadatum_len(asset_name.len()) {
+                            this.record_at("LabelMetadataV2", key_position, failure);
+                            return skip(raw);
+                        }
+                        if assets.contains_key(&asset_name) {
+                            this.record_at("LabelMetadataV2", key_position, DeserializeFailure::DuplicateKey(Key::Bytes(asset_name)));
+                            return skip(raw);
+                        }
+                        if let Some(details) = this.metadata_details(raw)? {
+                            assets.insert(asset_name, details);
+                        }
+                        Ok(())
+                    })
+                })?;
+                data.insert(policy_id, assets);
+                Ok(())
+            })
+        })?;
+        Ok(data)
+    }
+
+    // an optional field: Some(None) when present but invalid
+    fn field<R: BufRead + Seek, T>(
+        &mut self,
+        raw: &mut Deserializer<R>,
+        field: &mut Option<Option<T>>,
+        key: &str,
+        key_position: u64,
+        decode: impl FnOnce(&mut Self, &mut Deserializer<R>) -> Result<Option<T>, DeserializeError>,
+    ) -> Result<(), DeserializeError> {
+        if field.is_some() {
+            self.record_at("MetadataDetails", key_position, DeserializeFailure::DuplicateKey(Key::Str(key.to_owned())));
+            return skip(raw);
+        }
+        *field = Some(self.in_path(PathSegment::Field(key.to_owned()), |this| decode(this, raw))?);
+        Ok(())
+    }
+
+    // None when a mandatory field is missing or invalid
+    fn metadata_details<R: BufRead + Seek>(&mut self, raw: &mut Deserializer<R>) -> Result<Option<MetadataDetails>, DeserializeError> {
+        let map_position = position(raw);
+        let mut name = None;
+        let mut image = None;
+        let mut media_type = None;
+        let mut description = None;
+        let mut files = None;
+        let mut other_properties = BTreeMap::new();
+        let is_map = self.map_entries(raw, |this, raw| {
+            let key_position = position(raw);
+            match this.text_key(raw, "MetadataDetails")?.as_deref() {
+                Some("name") => this.field(raw, &mut name, "name", key_position, |this, raw| {
+                    this.recover(raw, |raw| String64::deserialize(raw).map_err(|e| e.annotate("name")))
+                }),
+                Some("image") => this.field(raw, &mut image, "image", key_position, |this, raw| {
+                    this.recover(raw, |raw| String64OrArrString64::deserialize(raw).map_err(|e| e.annotate("image")))
+                }),
+                Some("mediaType") => this.field(raw, &mut media_type, "mediaType", key_position, |this, raw| {
+                    this.recover(raw, |raw| String64::deserialize(raw).map_err(|e| e.annotate("media_type")))
+                }),
+                Some("description") => this.field(raw, &mut description, "description", key_position, |this, raw| {
+                    this.recover(raw, |raw| String64OrArrString64::deserialize(raw).map_err(|e| e.annotate("description")))
+                }),
+                Some("files") => this.field(raw, &mut files, "files", key_position, |this, raw| this.files(raw)),
+                Some(other_key) if other_properties.contains_key(other_key) => {
+                    this.record_at("MetadataDetails", key_position, DeserializeFailure::DuplicateKey(Key::Str(other_key.to_owned())));
+                    skip(raw)
+                },
+                Some(other_key) => this.in_path(PathSegment::Field(other_key.to_owned()), |this| {
+                    if let Err(failure) = check_metadatum_len(other_key.len()) {
+                        this.record_at("MetadataDetails", key_position, failure);
+                        return skip(raw);
+                    }
+                    if let Some(value) = this.recover(raw, TransactionMetadatum::deserialize)? {
+                        other_properties.insert(other_key.to_owned(), value);
+                    }
+                    Ok(())
+                }),
+                None => Ok(()),
+            }
+        })?;
+        if !is_map {
+            return Ok(None);
+        }
+        for (key, missing) in [("name", name.is_none()), ("image", image.is_none())] {
+            if missing {
+                self.record_at("MetadataDetails", map_position, DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from(key))));
+            }
+        }
+        match (name.flatten(), image.flatten()) {
+            (Some(name), Some(image)) => Ok(Some(MetadataDetails {
+                name,
+                image,
+                media_type: media_type.flatten(),
+                description: description.flatten(),
+                files: files.flatten(),
+                other_properties,
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    // invalid files are dropped, None if it's not an array
+    fn files<R: BufRead + Seek>(&mut self, raw: &mut Deserializer<R>) -> Result<Option<Vec<FilesDetails>>, DeserializeError> {
+        let len = match self.recover(raw, |raw| raw.array().map_err(|e| DeserializeError::from(e).annotate("files")))? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        let mut files = Vec::new();
+        let mut index = 0;
+        while match len { cbor_event::Len::Len(n) => index < n as usize, cbor_event::Len::Indefinite => true, } {
+            if len == cbor_event::Len::Indefinite && raw.cbor_type()? == CBORType::Special {
+                let file_position = position(raw);
+                match raw.special()? {
+                    CBORSpecial::Break => break,
+                    _ => {
+                        self.in_path(PathSegment::Index(index), |this| {
+                            this.record_at("files", file_position, DeserializeFailure::EndingBreakMissing);
+                            Ok(())
+                        })?;
+                        index += 1;
+                        continue;
+                    },
+                }
+            }
+            if let Some(file) = self.in_path(PathSegment::Index(index), |this| this.recover(raw, FilesDetails::deserialize))? {
+                files.push(file);
+            }
+            index += 1;
+        }
+        Ok(Some(files))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    // {721: {POLICY_ID: {"NFT1": details}}}
+    fn metadata(details: &str) -> Vec<u8> {
+        hex::decode(format!("a11902d1a1{}a1{}{}", text_hex(POLICY_ID), text_hex("NFT1"), details)).unwrap()
+    }
+
+    fn name_and_image() -> String {
+        format!("{}{}{}{}", text_hex("name"), text_hex("NFT 1"), text_hex("image"), text_hex("ipfs://patate"))
+    }
+
+    fn asset_path() -> Vec<PathSegment> {
+        vec![PathSegment::Field(String::from("721")), PathSegment::PolicyId(POLICY_ID.to_owned()), PathSegment::AssetName(String::from("NFT1"))]
+    }
+
+    fn assert_asset_kept(decoded: &PartialDecode<Metadata>) {
+        let value = decoded.value.as_ref().expect("a partial value");
+        match &value.key_721 {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => assert_eq!(label_metadata_v1.data.values().next().unwrap().len(), 1),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn file_without_break_is_recorded() {
+        // "files": [_ true]
+        let decoded = Metadata::from_bytes_diagnostic(metadata(&format!("a3{}{}9ff5ff", name_and_image(), text_hex("files"))));
+        assert_eq!(decoded.errors.len(), 1, "{:?}", decoded.errors);
+        assert!(matches!(decoded.errors[0].failure(), DeserializeFailure::EndingBreakMissing));
+        let mut path = asset_path();
+        path.extend([PathSegment::Field(String::from("files")), PathSegment::Index(0)]);
+        assert_eq!(decoded.errors[0].path(), path.as_slice());
+        assert_asset_kept(&decoded);
+    }
+
+    #[test]
+    fn map_entry_without_break_is_recorded() {
+        // {_ "name": .., "image": .., true: 1}
+        let decoded = Metadata::from_bytes_diagnostic(metadata(&format!("bf{}f501ff", name_and_image())));
+        assert_eq!(decoded.errors.len(), 1, "{:?}", decoded.errors);
+        assert!(matches!(decoded.errors[0].failure(), DeserializeFailure::EndingBreakMissing));
+        assert_eq!(decoded.errors[0].path(), asset_path().as_slice());
+        assert_asset_kept(&decoded);
+    }
+
+    #[test]
+    fn other_property_without_break_is_recorded() {
+        // "x": [_ true]
+        let decoded = Metadata::from_bytes_diagnostic(metadata(&format!("a3{}{}9ff5ff", name_and_image(), text_hex("x"))));
+        assert_eq!(decoded.errors.len(), 1, "{:?}", decoded.errors);
+        assert!(matches!(decoded.errors[0].failure(), DeserializeFailure::EndingBreakMissing));
+        assert_asset_kept(&decoded);
+    }
+
+    #[test]
+    fn special_value_in_indefinite_array_is_recorded() {
+        // "image": [_ true], neither of the variants of String64OrArrString64
+        let decoded = Metadata::from_bytes_diagnostic(metadata(&format!("a2{}{}{}9ff5ff", text_hex("name"), text_hex("NFT 1"), text_hex("image"))));
+        assert_eq!(decoded.errors.len(), 1, "{:?}", decoded.errors);
+        assert!(matches!(decoded.errors[0].failure(), DeserializeFailure::NoVariantMatched), "{}", decoded.errors[0]);
+        assert_eq!(decoded.errors[0].path(), [asset_path(), vec![PathSegment::Field(String::from("image"))]].concat().as_slice());
+        // "files": [{"name": .., "mediaType": .., "src": [_ true]}]
+        let file = format!("a3{}{}{}{}{}9ff5ff", text_hex("name"), text_hex("file"), text_hex("mediaType"), text_hex("image/png"), text_hex("src"));
+        let decoded = Metadata::from_bytes_diagnostic(metadata(&format!("a3{}{}81{}", name_and_image(), text_hex("files"), file)));
+        assert_eq!(decoded.errors.len(), 1, "{:?}", decoded.errors);
+        assert!(matches!(decoded.errors[0].failure(), DeserializeFailure::NoVariantMatched), "{}", decoded.errors[0]);
+        assert_eq!(decoded.errors[0].path().last(), Some(&PathSegment::Field(String::from("src"))));
+        assert_asset_kept(&decoded);
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let decoded = Metadata::from_bytes_diagnostic(vec![0x9f, 0xf5]);
+        assert!(decoded.value.is_none());
+        assert!(!decoded.errors.is_empty());
+    }
+
+    fn assert_too_long_recorded(decoded: &PartialDecode<Metadata>, path: &[PathSegment]) {
+        assert_eq!(decoded.errors.len(), 1, "{:?}", decoded.errors);
+        assert_too_long(&decoded.errors[0], 65);
+        assert_eq!(decoded.errors[0].path(), path);
+    }
+
+    #[test]
+    fn long_policy_ids_and_asset_names_are_recorded() {
+        assert!(Metadata::from_bytes_diagnostic(metadata_v2(&[1; 28], b"NFT1")).is_valid());
+        let data = [PathSegment::Field(String::from("721")), PathSegment::Field(String::from("data"))];
+        let decoded = Metadata::from_bytes_diagnostic(metadata_v2(&[1; 65], b"NFT1"));
+        assert_too_long_recorded(&decoded, &[&data[..], &[PathSegment::PolicyId(hex::encode([1; 65]))]].concat());
+        match decoded.value.map(|value| value.key_721) {
+            Some(LabelMetadata::LabelMetadataV2(label_metadata_v2)) => assert!(label_metadata_v2.data.is_empty()),
+            other => panic!("unexpected {:?}", other),
+        }
+        let decoded = Metadata::from_bytes_diagnostic(metadata_v2(&[1; 28], &[2; 65]));
+        assert_too_long_recorded(&decoded, &[&data[..], &[PathSegment::PolicyId(hex::encode([1; 28])), PathSegment::AssetName(hex::encode([2; 65]))]].concat());
+    }
+
+    #[test]
+    fn long_other_properties_are_recorded() {
+        let long = "x".repeat(65);
+        let decoded = Metadata::from_bytes_diagnostic(metadata(&format!("a3{}{}01", name_and_image(), text_hex(&long))));
+        assert_too_long_recorded(&decoded, &[asset_path(), vec![PathSegment::Field(long.clone())]].concat());
+        assert_asset_kept(&decoded);
+        let decoded = Metadata::from_bytes_diagnostic(metadata(&format!("a3{}{}{}", name_and_image(), text_hex("x"), text_hex(&long))));
+        assert_too_long_recorded(&decoded, &[asset_path(), vec![PathSegment::Field(String::from("x"))]].concat());
+        assert_asset_kept(&decoded);
+    }
+}
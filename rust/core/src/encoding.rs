@@ -0,0 +1,313 @@
+// Encodings other than the default one of to_bytes(), which uses definite lengths, the
+// shortest heads, the field order of the structs and the BTreeMap order of policies / assets.
+//
+// The value is encoded the default way then rewritten as a generic CBOR tree so the same
+// rules apply to every type (Metadata, LabelMetadata, MetadataDetails, FilesDetails, ...).
+
+use super::*;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use cbor_event::{LenSz, StringLenSz, Sz};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EncodingOptions {
+    // RFC 8949 section 4.2.1 (core deterministic): definite lengths, shortest heads and
+    // map keys sorted by the bytewise order of their encoding
+    CoreDeterministic,
+    // RFC 7049 section 3.9 (canonical CBOR): the same but shorter keys sort first
+    LegacyCanonical,
+    // definite lengths and shortest heads in the default field order, same as to_bytes()
+    Minimal,
+    // the key order, lengths and head sizes of the given CBOR (usually the bytes the value
+    // was decoded from) for every part of the value that is still the same
+    PreserveOriginal(Vec<u8>),
+}
+
+pub(crate) fn reencode(bytes: Vec<u8>, options: &EncodingOptions) -> Result<Vec<u8>, SerializeError> {
+    let node = read_node(&mut Deserializer::from(std::io::Cursor::new(bytes)))?;
+    let node = match options {
+        EncodingOptions::CoreDeterministic => sort_maps(minimal(node), &|a: &[u8], b: &[u8]| a.cmp(b))?,
+        EncodingOptions::LegacyCanonical => sort_maps(minimal(node), &|a: &[u8], b: &[u8]| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))?,
+        EncodingOptions::Minimal => minimal(node),
+        EncodingOptions::PreserveOriginal(original) => {
+            let original = read_node(&mut Deserializer::from(std::io::Cursor::new(original.clone())))?;
+            preserve(node, &original)?
+        },
+    };
+    let mut serializer = Serializer::new_vec();
+    write_node(&mut serializer, &node)?;
+    Ok(serializer.finalize())
+}
+
+// any CBOR item together with how it was encoded
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Uint(u64, Sz),
+    Nint(i128, Sz),
+    Bytes(Vec<u8>, StringLenSz),
+    Text(String, StringLenSz),
+    Array(Vec<Node>, LenSz),
+    Map(Vec<(Node, Node)>, LenSz),
+    Tag(u64, Sz, Box<Node>),
+    Special(CBORSpecial),
+}
+
+fn read_node<R: BufRead>(raw: &mut Deserializer<R>) -> cbor_event::Result<Node> {
+    Ok(match raw.cbor_type()? {
+        CBORType::UnsignedInteger => {
+            let (value, sz) = raw.unsigned_integer_sz()?;
+            Node::Uint(value, sz)
+        },
+        CBORType::NegativeInteger => {
+            let (value, sz) = raw.negative_integer_sz()?;
+            Node::Nint(value, sz)
+        },
+        CBORType::Bytes => {
+            let (value, sz) = raw.bytes_sz()?;
+            Node::Bytes(value, sz)
+        },
+        CBORType::Text => {
+            let (value, sz) = raw.text_sz()?;
+            Node::Text(value, sz)
+        },
+        CBORType::Array => {
+            let len = raw.array_sz()?;
+            let mut items = Vec::new();
+            while match len { LenSz::Len(n, _) => (items.len() as u64) < n, LenSz::Indefinite => !(raw.cbor_type()? == CBORType::Special && raw.special_break()?), } {
+                items.push(read_node(raw)?);
+            }
+            Node::Array(items, len)
+        },
+        CBORType::Map => {
+            let len = raw.map_sz()?;
+            let mut entries = Vec::new();
+            while match len { LenSz::Len(n, _) => (entries.len() as u64) < n, LenSz::Indefinite => !(raw.cbor_type()? == CBORType::Special && raw.special_break()?), } {
+                let key = read_node(raw)?;
+                entries.push((key, read_node(raw)?));
+            }
+            Node::Map(entries, len)
+        },
+        CBORType::Tag => {
+            let (tag, sz) = raw.tag_sz()?;
+            Node::Tag(tag, sz, Box::new(read_node(raw)?))
+        },
+        CBORType::Special => Node::Special(raw.special()?),
+    })
+}
+
+fn write_node<W: Write>(serializer: &mut Serializer<W>, node: &Node) -> cbor_event::Result<()> {
+    match node {
+        Node::Uint(value, sz) => {
+            serializer.write_unsigned_integer_sz(*value, *sz)?;
+        },
+        Node::Nint(value, sz) => {
+            serializer.write_negative_integer_sz(*value, *sz)?;
+        },
+        Node::Bytes(value, sz) => {
+            serializer.write_bytes_sz(value, sz.clone())?;
+        },
+        Node::Text(value, sz) => {
+            serializer.write_text_sz(value, sz.clone())?;
+        },
+        Node::Array(items, len) => {
+            serializer.write_array_sz(*len)?;
+            for item in items {
+                write_node(serializer, item)?;
+            }
+            if *len == LenSz::Indefinite {
+                serializer.write_special(CBORSpecial::Break)?;
+            }
+        },
+        Node::Map(entries, len) => {
+            serializer.write_map_sz(*len)?;
+            for (key, value) in entries {
+                write_node(serializer, key)?;
+                write_node(serializer, value)?;
+            }
+            if *len == LenSz::Indefinite {
+                serializer.write_special(CBORSpecial::Break)?;
+            }
+        },
+        Node::Tag(tag, sz, inner) => {
+            serializer.write_tag_sz(*tag, *sz)?;
+            write_node(serializer, inner)?;
+        },
+        Node::Special(special) => {
+            serializer.write_special(*special)?;
+        },
+    }
+    Ok(())
+}
+
+fn minimal_len(len: usize) -> LenSz {
+    LenSz::Len(len as u64, Sz::canonical(len as u64))
+}
+
+fn minimal(node: Node) -> Node {
+    match node {
+        Node::Uint(value, _) => Node::Uint(value, Sz::canonical(value)),
+        Node::Nint(value, _) => Node::Nint(value, Sz::canonical((-value - 1) as u64)),
+        Node::Bytes(value, _) => {
+            let sz = Sz::canonical(value.len() as u64);
+            Node::Bytes(value, StringLenSz::Len(sz))
+        },
+        Node::Text(value, _) => {
+            let sz = Sz::canonical(value.len() as u64);
+            Node::Text(value, StringLenSz::Len(sz))
+        },
+        Node::Array(items, _) => {
+            let len = minimal_len(items.len());
+            Node::Array(items.into_iter().map(minimal).collect(), len)
+        },
+        Node::Map(entries, _) => {
+            let len = minimal_len(entries.len());
+            Node::Map(entries.into_iter().map(|(key, value)| (minimal(key), minimal(value))).collect(), len)
+        },
+        Node::Tag(tag, _, inner) => Node::Tag(tag, Sz::canonical(tag), Box::new(minimal(*inner))),
+        special => special,
+    }
+}
+
+// sorts every map by the encoding of its keys
+fn sort_maps(node: Node, order: &dyn Fn(&[u8], &[u8]) -> Ordering) -> cbor_event::Result<Node> {
+    Ok(match node {
+        Node::Array(items, len) => Node::Array(items.into_iter().map(|item| sort_maps(item, order)).collect::<cbor_event::Result<_>>()?, len),
+        Node::Map(entries, len) => {
+            let mut encoded = Vec::with_capacity(entries.len());
+            for (key, value) in entries {
+                encoded.push((encode(&key)?, key, sort_maps(value, order)?));
+            }
+            encoded.sort_by(|(a, _, _), (b, _, _)| order(a, b));
+            Node::Map(encoded.into_iter().map(|(_, key, value)| (key, value)).collect(), len)
+        },
+        Node::Tag(tag, sz, inner) => Node::Tag(tag, sz, Box::new(sort_maps(*inner, order)?)),
+        other => other,
+    })
+}
+
+fn encode(node: &Node) -> cbor_event::Result<Vec<u8>> {
+    let mut serializer = Serializer::new_vec();
+    write_node(&mut serializer, node)?;
+    Ok(serializer.finalize())
+}
+
+fn preserved_len(len: usize, original: LenSz) -> LenSz {
+    match original {
+        LenSz::Indefinite => LenSz::Indefinite,
+        LenSz::Len(original_len, sz) if original_len == len as u64 => LenSz::Len(original_len, sz),
+        LenSz::Len(_, _) => minimal_len(len),
+    }
+}
+
+// node encoded like original where they hold the same values. Map entries that are in both
+// keep the original order, new ones come after.
+fn preserve(node: Node, original: &Node) -> cbor_event::Result<Node> {
+    Ok(match (node, original) {
+        (Node::Uint(value, _), Node::Uint(original_value, sz)) if value == *original_value => Node::Uint(value, *sz),
+        (Node::Nint(value, _), Node::Nint(original_value, sz)) if value == *original_value => Node::Nint(value, *sz),
+        (Node::Bytes(value, _), Node::Bytes(original_value, sz)) if value == *original_value => Node::Bytes(value, sz.clone()),
+        (Node::Text(value, _), Node::Text(original_value, sz)) if value == *original_value => Node::Text(value, sz.clone()),
+        (Node::Array(items, _), Node::Array(original_items, original_len)) => {
+            let len = preserved_len(items.len(), *original_len);
+            let items = items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| match original_items.get(index) {
+                    Some(original_item) => preserve(item, original_item),
+                    None => Ok(item),
+                })
+                .collect::<cbor_event::Result<_>>()?;
+            Node::Array(items, len)
+        },
+        (Node::Map(entries, _), Node::Map(original_entries, original_len)) => {
+            // the entries by the minimal encoding of their key, so that keys holding the same
+            // value match whatever their encoding, in the order of entries for duplicates
+            let mut by_key: BTreeMap<Vec<u8>, VecDeque<usize>> = BTreeMap::new();
+            for (index, (key, _)) in entries.iter().enumerate() {
+                by_key.entry(encode(&minimal(key.clone()))?).or_default().push_back(index);
+            }
+            let mut entries: Vec<Option<(Node, Node)>> = entries.into_iter().map(Some).collect();
+            let mut preserved = Vec::with_capacity(entries.len());
+            for (original_key, original_value) in original_entries {
+                let index = by_key.get_mut(&encode(&minimal(original_key.clone()))?).and_then(VecDeque::pop_front);
+                if let Some((key, value)) = index.and_then(|index| entries[index].take()) {
+                    preserved.push((preserve(key, original_key)?, preserve(value, original_value)?));
+                }
+            }
+            preserved.extend(entries.into_iter().flatten());
+            let len = preserved_len(preserved.len(), *original_len);
+            Node::Map(preserved, len)
+        },
+        (Node::Tag(tag, _, inner), Node::Tag(original_tag, sz, original_inner)) if tag == *original_tag => Node::Tag(tag, *sz, Box::new(preserve(*inner, original_inner)?)),
+        (node, _) => node,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an indefinite map with the keys "aa", "a", 1000, -1, h'01' and 1 (with a 1-byte head),
+    // "a" holding an indefinite [1] whose 1 has a 4-byte head
+    const UNSORTED: &str = "bf 626161 00 6161 9f1a00000001ff 1903e8 00 20 00 4101 00 1801 00 ff";
+
+    fn reencoded(hex: &str, options: &EncodingOptions) -> String {
+        hex::encode(reencode(hex::decode(hex.replace(' ', "")).unwrap(), options).unwrap())
+    }
+
+    #[test]
+    fn core_deterministic_sorts_bytewise() {
+        // 01 < 1903e8 < 20 < 4101 < 6161 < 626161
+        assert_eq!(reencoded(UNSORTED, &EncodingOptions::CoreDeterministic), "a6 01 00 1903e8 00 20 00 4101 00 6161 8101 626161 00".replace(' ', ""));
+    }
+
+    #[test]
+    fn legacy_canonical_sorts_shorter_keys_first() {
+        // 1-byte keys, then 2-byte ones, then 1903e8 < 626161
+        assert_eq!(reencoded(UNSORTED, &EncodingOptions::LegacyCanonical), "a6 01 00 20 00 4101 00 6161 8101 1903e8 00 626161 00".replace(' ', ""));
+    }
+
+    #[test]
+    fn minimal_keeps_the_order() {
+        assert_eq!(reencoded(UNSORTED, &EncodingOptions::Minimal), "a6 626161 00 6161 8101 1903e8 00 20 00 4101 00 01 00".replace(' ', ""));
+    }
+
+    #[test]
+    fn sorted_nested_maps() {
+        // {"b": {2: 0, 1: 0}, "a": [{"y": 0, "x": 0}]}
+        let hex = "a2 6162 a2020001 00 6161 81a2617900617800";
+        assert_eq!(reencoded(hex, &EncodingOptions::CoreDeterministic), "a2 6161 81a2617800617900 6162 a201000200".replace(' ', ""));
+    }
+
+    #[test]
+    fn preserve_original_round_trips() {
+        // an indefinite map with "image" before "name", "name" with a 1-byte length head
+        // and the "x" other property with a 4-byte head
+        let original = hex::decode("bf 65696d616765 6d697066733a2f2f706174617465 78046e616d65 66506174617465 6178 1a00000001 ff".replace(' ', "")).unwrap();
+        let details = MetadataDetails::from_bytes(original.clone()).unwrap();
+        assert_ne!(details.to_bytes().unwrap(), original);
+        assert_eq!(details.to_bytes_with(&EncodingOptions::PreserveOriginal(original.clone())).unwrap(), original);
+        // a changed value is minimal, a new key comes after the original ones
+        let mut changed = details.clone();
+        changed.name = String64::new(String::from("Patates")).unwrap();
+        changed.other_properties.insert(String::from("y"), TransactionMetadatum::new_int(2));
+        let expected = "bf 65696d616765 6d697066733a2f2f706174617465 78046e616d65 6750617461746573 6178 1a00000001 6179 02 ff";
+        assert_eq!(hex::encode(changed.to_bytes_with(&EncodingOptions::PreserveOriginal(original)).unwrap()), expected.replace(' ', ""));
+    }
+
+    #[test]
+    fn preserve_original_keeps_duplicate_keys() {
+        // {1: 0, 1: 1, "x": [0, 1]} with long heads on the keys, the list and its integers
+        let original = hex::decode("a3 1801 00 1801 01 6178 9802 1800 1801".replace(' ', "")).unwrap();
+        let metadatum = TransactionMetadatum::from_bytes(original.clone()).unwrap();
+        assert_eq!(metadatum.to_bytes_with(&EncodingOptions::PreserveOriginal(original.clone())).unwrap(), original);
+        // without the second 1 the other entries keep their heads, the map length is minimal
+        let mut entries = match metadatum {
+            TransactionMetadatum::Map(entries) => entries,
+            _ => panic!("not a map"),
+        };
+        entries.remove(1);
+        let removed = TransactionMetadatum::new_map(entries).to_bytes_with(&EncodingOptions::PreserveOriginal(original)).unwrap();
+        assert_eq!(hex::encode(removed), "a2 1801 00 6178 9802 1800 1801".replace(' ', ""));
+    }
+}
@@ -0,0 +1,920 @@
+// JSON (de)serialization following the shape used in the CIP-25 document:
+//
+// {
+//   "721": {
+//     "<policy_id>": {
+//       "<asset_name>": { "name": ..., "image": ..., "mediaType": ..., ... }
+//     },
+//     "version": "2.0"
+//   }
+// }
+//
+// Version 2 policy ids / asset names are raw bytes on-chain so they are hex-encoded here.
+// Metadata of an unknown version (LabelMetadata::Unknown) is written in the cardano-cli
+// "no schema" format and read back as such when its version is above 2.
+// The shape produced by the old derived serde impls is still reachable with JsonFormat::Legacy.
+
+use super::*;
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, ObjectValidation, Schema, SchemaObject, StringValidation, SubschemaValidation};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum JsonFormat {
+    // the format from the CIP-25 document
+    #[default]
+    Cip25,
+    // the rust-shaped format that the derived serde impls used to produce
+    Legacy,
+}
+
+pub trait ToJson {
+    fn to_json(&self, format: JsonFormat) -> Result<String, serde_json::Error>;
+
+    fn to_json_value(&self, format: JsonFormat) -> Result<serde_json::Value, serde_json::Error>;
+}
+
+pub trait FromJson {
+    fn from_json(json: &str, format: JsonFormat) -> Result<Self, serde_json::Error> where Self: Sized;
+}
+
+// the same forms as in CBOR: 1, "1.0", 2 or "2.0"
+fn parse_version<E: de::Error>(version: VersionValue) -> Result<(u64, VersionFormat), E> {
+    match version {
+        VersionValue::Uint(number @ 1..=2) => Ok((number, VersionFormat::Uint)),
+        VersionValue::Text(text) if text == "1.0" => Ok((1, VersionFormat::Text)),
+        VersionValue::Text(text) if text == "2.0" => Ok((2, VersionFormat::Text)),
+        VersionValue::Uint(other) => Err(E::custom(format!("unsupported CIP-25 version {}", other))),
+        VersionValue::Text(other) => Err(E::custom(format!("unsupported CIP-25 version \"{}\"", other))),
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+enum VersionValue {
+    Uint(u64),
+    Text(String),
+}
+
+impl VersionValue {
+    fn new(number: u64, format: VersionFormat) -> Self {
+        match format {
+            VersionFormat::Uint => Self::Uint(number),
+            VersionFormat::Text => Self::Text(format!("{}.0", number)),
+        }
+    }
+}
+
+// the 721 label map before we know which version it is, as the version key can come last
+struct LabelMap {
+    version: Option<(u64, VersionFormat)>,
+    policies: Vec<(String, BTreeMap<String, MetadataDetails>)>,
+}
+
+impl<'de> serde::Deserialize<'de> for LabelMap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LabelMapVisitor;
+
+        impl<'de> Visitor<'de> for LabelMapVisitor {
+            type Value = LabelMap;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a map of policy ids to maps of asset names to metadata details")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<LabelMap, A::Error> {
+                let mut version = None;
+                let mut policies = Vec::new();
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == VERSION_KEY {
+                        if version.is_some() {
+                            return Err(de::Error::duplicate_field(VERSION_KEY));
+                        }
+                        version = Some(parse_version(map.next_value::<VersionValue>()?)?);
+                    } else {
+                        let assets = map.next_value::<BTreeMap<String, MetadataDetails>>()?;
+                        policies.push((key, assets));
+                    }
+                }
+                Ok(LabelMap {
+                    version,
+                    policies,
+                })
+            }
+        }
+
+        deserializer.deserialize_map(LabelMapVisitor)
+    }
+}
+
+impl LabelMap {
+    fn into_v1<E: de::Error>(self) -> Result<LabelMetadataV1, E> {
+        let mut label_metadata_v1 = BTreeMap::new();
+        for (policy_id, assets) in self.policies {
+            let mut assets_v1 = BTreeMap::new();
+            for (asset_name, details) in assets {
+                let asset_name = String64::new(asset_name).map_err(E::custom)?;
+                assets_v1.insert(asset_name, details);
+            }
+            let policy_id = String64::new(policy_id).map_err(E::custom)?;
+            if label_metadata_v1.insert(policy_id.clone(), assets_v1).is_some() {
+                return Err(E::custom(format!("duplicate policy id \"{}\"", policy_id.get())));
+            }
+        }
+        Ok(LabelMetadataV1 {
+            data: label_metadata_v1,
+            version: self.version.map(|(_, format)| format),
+        })
+    }
+
+    fn into_v2<E: de::Error>(self, version: VersionFormat) -> Result<LabelMetadataV2, E> {
+        let mut data = BTreeMap::new();
+        for (policy_id, assets) in self.policies {
+            let mut assets_v2 = BTreeMap::new();
+            for (asset_name, details) in assets {
+                let asset_name_bytes = hex::decode(&asset_name)
+                    .map_err(|e| E::custom(format!("asset name \"{}\" is not valid hex: {}", asset_name, e)))?;
+                // the hex of a JSON key can differ only in case from another one
+                if assets_v2.insert(asset_name_bytes, details).is_some() {
+                    return Err(E::custom(format!("duplicate asset name \"{}\" in policy id \"{}\"", asset_name, policy_id)));
+                }
+            }
+            let policy_id_bytes = hex::decode(&policy_id)
+                .map_err(|e| E::custom(format!("policy id \"{}\" is not valid hex: {}", policy_id, e)))?;
+            if data.insert(policy_id_bytes, assets_v2).is_some() {
+                return Err(E::custom(format!("duplicate policy id \"{}\"", policy_id)));
+            }
+        }
+        let mut label_metadata_v2 = LabelMetadataV2::new(data).map_err(E::custom)?;
+        label_metadata_v2.version = version;
+        Ok(label_metadata_v2)
+    }
+}
+
+impl serde::Serialize for LabelMetadata {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => serde::Serialize::serialize(label_metadata_v1, serializer),
+            LabelMetadata::LabelMetadataV2(label_metadata_v2) => serde::Serialize::serialize(label_metadata_v2, serializer),
+            LabelMetadata::Unknown { raw, .. } => {
+                let json = metadata_json::metadatum_to_json(raw, metadata_json::MetadataJsonSchema::NoSchema).map_err(serde::ser::Error::custom)?;
+                serde::Serialize::serialize(&json, serializer)
+            },
+        }
+    }
+}
+
+// the version can come last so the label is read as a JSON value first
+impl<'de> serde::Deserialize<'de> for LabelMetadata {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = serde_json::Value::deserialize(deserializer)?;
+        let version = json
+            .get(VERSION_KEY)
+            .and_then(|version| metadata_json::metadatum_from_json(version, metadata_json::MetadataJsonSchema::NoSchema).ok());
+        if let Some(version) = version.filter(is_newer_version) {
+            let raw = metadata_json::metadatum_from_json(&json, metadata_json::MetadataJsonSchema::NoSchema).map_err(de::Error::custom)?;
+            return Ok(Self::new_unknown(version, raw));
+        }
+        let label_map = LabelMap::deserialize(json).map_err(de::Error::custom)?;
+        match label_map.version {
+            Some((2, format)) => label_map.into_v2(format).map(Self::LabelMetadataV2),
+            _ => label_map.into_v1().map(Self::LabelMetadataV1),
+        }
+    }
+}
+
+impl serde::Serialize for LabelMetadataV1 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.data.len() + self.version.iter().count()))?;
+        for (policy_id, assets) in self.data.iter() {
+            map.serialize_entry(policy_id, assets)?;
+        }
+        if let Some(format) = self.version {
+            map.serialize_entry(VERSION_KEY, &VersionValue::new(1, format))?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for LabelMetadataV1 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let label_map = LabelMap::deserialize(deserializer)?;
+        match label_map.version {
+            None | Some((1, _)) => label_map.into_v1(),
+            Some((other, _)) => Err(de::Error::custom(format!("expected version 1, found {}", other))),
+        }
+    }
+}
+
+impl serde::Serialize for LabelMetadataV2 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.data.len() + 1))?;
+        for (policy_id, assets) in self.data.iter() {
+            let assets = assets
+                .iter()
+                .map(|(asset_name, details)| (hex::encode(asset_name), details))
+                .collect::<BTreeMap<_, _>>();
+            map.serialize_entry(&hex::encode(policy_id), &assets)?;
+        }
+        map.serialize_entry(VERSION_KEY, &VersionValue::new(2, self.version))?;
+        map.end()
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for LabelMetadataV2 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let label_map = LabelMap::deserialize(deserializer)?;
+        match label_map.version {
+            Some((2, format)) => label_map.into_v2(format),
+            Some((other, _)) => Err(de::Error::custom(format!("expected version 2, found {}", other))),
+            None => Err(de::Error::missing_field(VERSION_KEY)),
+        }
+    }
+}
+
+// the other properties of MetadataDetails / FilesDetails, in the cardano-cli "no schema" format
+impl serde::Serialize for TransactionMetadatum {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let json = metadata_json::metadatum_to_json(self, metadata_json::MetadataJsonSchema::NoSchema).map_err(serde::ser::Error::custom)?;
+        serde::Serialize::serialize(&json, serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for TransactionMetadatum {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = serde_json::Value::deserialize(deserializer)?;
+        metadata_json::metadatum_from_json(&json, metadata_json::MetadataJsonSchema::NoSchema).map_err(de::Error::custom)
+    }
+}
+
+// the keys of the other properties are text metadatums as well
+pub(crate) fn deserialize_other_properties<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, TransactionMetadatum>, D::Error> {
+    let other_properties: BTreeMap<String, TransactionMetadatum> = serde::Deserialize::deserialize(deserializer)?;
+    for key in other_properties.keys() {
+        check_metadatum_len(key.len()).map_err(|e| de::Error::custom(format!("key \"{}\": {}", key, e)))?;
+    }
+    Ok(other_properties)
+}
+
+// label 777, with the same key for the rate as in CBOR
+impl serde::Serialize for royalty::RoyaltyMetadata {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry(self.rate_key.as_str(), &self.rate)?;
+        map.serialize_entry("addr", &self.addr)?;
+        map.end()
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for royalty::RoyaltyMetadata {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct RoyaltyFields {
+            rate: Option<String64>,
+            pct: Option<String64>,
+            addr: String64OrArrString64,
+        }
+
+        let fields = RoyaltyFields::deserialize(deserializer)?;
+        let (rate, rate_key) = match (fields.rate, fields.pct) {
+            (Some(rate), None) => (rate, royalty::RateKey::Rate),
+            (None, Some(pct)) => (pct, royalty::RateKey::Pct),
+            (Some(_), Some(_)) => return Err(de::Error::custom("both \"rate\" and \"pct\" are given")),
+            (None, None) => return Err(de::Error::missing_field("rate")),
+        };
+        royalty::check_rate(rate.get()).map_err(|e| de::Error::custom(e.failure()))?;
+        Ok(royalty::RoyaltyMetadata {
+            rate,
+            addr: fields.addr,
+            rate_key,
+        })
+    }
+}
+
+// JSON schemas describing the shape above so they can be reused for validation outside of this library.
+// Policy ids are hashes (28 bytes) and asset names are at most 32 bytes per the ledger rules.
+
+const POLICY_ID_PATTERN: &str = "^[0-9a-fA-F]{56}$";
+const ASSET_NAME_V2_PATTERN: &str = "^([0-9a-fA-F]{2}){0,32}$";
+
+fn string_schema(max_length: Option<u32>, pattern: Option<&str>, description: Option<&str>) -> Schema {
+    SchemaObject {
+        metadata: description.map(|description| Box::new(schemars::schema::Metadata {
+            description: Some(description.to_owned()),
+            ..Default::default()
+        })),
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            max_length,
+            pattern: pattern.map(str::to_owned),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }.into()
+}
+
+fn version_schema(accepted: Vec<serde_json::Value>) -> Schema {
+    SchemaObject {
+        enum_values: Some(accepted),
+        ..Default::default()
+    }.into()
+}
+
+fn label_map_schema(gen: &mut SchemaGenerator, asset_name: Schema, version: Schema, version_required: bool) -> Schema {
+    let assets = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        object: Some(Box::new(ObjectValidation {
+            property_names: Some(Box::new(asset_name)),
+            additional_properties: Some(Box::new(gen.subschema_for::<MetadataDetails>())),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    let mut object = ObjectValidation {
+        additional_properties: Some(Box::new(Schema::Bool(false))),
+        ..Default::default()
+    };
+    object.properties.insert(VERSION_KEY.to_owned(), version);
+    if version_required {
+        object.required.insert(VERSION_KEY.to_owned());
+    }
+    object.pattern_properties.insert(POLICY_ID_PATTERN.to_owned(), assets.into());
+    SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        object: Some(Box::new(object)),
+        ..Default::default()
+    }.into()
+}
+
+impl schemars::JsonSchema for String64 {
+    fn schema_name() -> String {
+        String::from("String64")
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        // maxLength counts characters, the on-chain limit is 64 bytes once UTF-8 encoded
+        string_schema(Some(64), None, Some("text of at most 64 bytes once UTF-8 encoded"))
+    }
+}
+
+impl schemars::JsonSchema for LabelMetadata {
+    fn schema_name() -> String {
+        String::from("LabelMetadata")
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                one_of: Some(vec![gen.subschema_for::<LabelMetadataV1>(), gen.subschema_for::<LabelMetadataV2>()]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }.into()
+    }
+}
+
+impl schemars::JsonSchema for LabelMetadataV1 {
+    fn schema_name() -> String {
+        String::from("LabelMetadataV1")
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        label_map_schema(
+            gen,
+            string_schema(Some(64), None, Some("UTF-8 asset name")),
+            version_schema(vec![1.into(), "1.0".into()]),
+            false,
+        )
+    }
+}
+
+impl schemars::JsonSchema for LabelMetadataV2 {
+    fn schema_name() -> String {
+        String::from("LabelMetadataV2")
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        label_map_schema(
+            gen,
+            string_schema(None, Some(ASSET_NAME_V2_PATTERN), Some("hex-encoded asset name")),
+            version_schema(vec![2.into(), "2.0".into()]),
+            true,
+        )
+    }
+}
+
+impl schemars::JsonSchema for TransactionMetadatum {
+    fn schema_name() -> String {
+        String::from("TransactionMetadatum")
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            metadata: Some(Box::new(schemars::schema::Metadata {
+                description: Some(String::from("any metadatum in the cardano-cli no schema format")),
+                ..Default::default()
+            })),
+            instance_type: Some(vec![InstanceType::Object, InstanceType::Array, InstanceType::String, InstanceType::Integer].into()),
+            ..Default::default()
+        }.into()
+    }
+}
+
+const ROYALTY_RATE_PATTERN: &str = "^([0-9]+(\\.[0-9]*)?|\\.[0-9]+)$";
+
+impl schemars::JsonSchema for royalty::RoyaltyMetadata {
+    fn schema_name() -> String {
+        String::from("RoyaltyMetadata")
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let rate = string_schema(Some(64), Some(ROYALTY_RATE_PATTERN), Some("decimal between 0 and 1"));
+        let addr = gen.subschema_for::<String64OrArrString64>();
+        let with_rate_key = |rate_key: &str| -> Schema {
+            let mut object = ObjectValidation {
+                additional_properties: Some(Box::new(Schema::Bool(false))),
+                ..Default::default()
+            };
+            object.properties.insert(rate_key.to_owned(), rate.clone());
+            object.properties.insert(String::from("addr"), addr.clone());
+            object.required.insert(rate_key.to_owned());
+            object.required.insert(String::from("addr"));
+            SchemaObject {
+                instance_type: Some(InstanceType::Object.into()),
+                object: Some(Box::new(object)),
+                ..Default::default()
+            }.into()
+        };
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                one_of: Some(vec![with_rate_key("rate"), with_rate_key("pct")]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }.into()
+    }
+}
+
+// Mirrors of the public types with the derived serde impls they used to have.
+// These are only used by JsonFormat::Legacy. Other properties didn't exist then, they are an
+// optional field here so that they aren't lost. Unknown versions and version fields can't be
+// written in this shape and are an error. As with the derived impls, version 2 data can't be
+// written to JSON either as its keys are byte arrays.
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct LegacyFilesDetails {
+    name: String64,
+    media_type: String64,
+    src: LegacyString64OrArrString64,
+    #[serde(default, deserialize_with = "deserialize_other_properties", skip_serializing_if = "BTreeMap::is_empty")]
+    other_properties: BTreeMap<String, TransactionMetadatum>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+enum LegacyLabelMetadata {
+    LabelMetadataV1(BTreeMap<PolicyIdV1, BTreeMap<AssetNameV1, LegacyMetadataDetails>>),
+    LabelMetadataV2(LegacyLabelMetadataV2),
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct LegacyLabelMetadataV2 {
+    #[serde(deserialize_with = "deserialize_legacy_data")]
+    data: BTreeMap<PolicyIdV2, BTreeMap<AssetNameV2, LegacyMetadataDetails>>,
+}
+
+fn deserialize_legacy_data<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<PolicyIdV2, BTreeMap<AssetNameV2, LegacyMetadataDetails>>, D::Error> {
+    let data: BTreeMap<PolicyIdV2, BTreeMap<AssetNameV2, LegacyMetadataDetails>> = serde::Deserialize::deserialize(deserializer)?;
+    check_data_keys(&data).map_err(de::Error::custom)?;
+    Ok(data)
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct LegacyMetadata {
+    key_721: LegacyLabelMetadata,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct LegacyMetadataDetails {
+    name: String64,
+    image: LegacyString64OrArrString64,
+    media_type: Option<String64>,
+    description: Option<LegacyString64OrArrString64>,
+    files: Option<Vec<LegacyFilesDetails>>,
+    #[serde(default, deserialize_with = "deserialize_other_properties", skip_serializing_if = "BTreeMap::is_empty")]
+    other_properties: BTreeMap<String, TransactionMetadatum>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+enum LegacyString64OrArrString64 {
+    String64(String64),
+    ArrString64(Vec<String64>),
+}
+
+fn legacy_error(what: &str) -> serde_json::Error {
+    serde::ser::Error::custom(format!("{} can't be written in the legacy JSON format", what))
+}
+
+fn convert_nested_map<K: Ord, A, B: From<A>>(map: BTreeMap<K, BTreeMap<K, A>>) -> BTreeMap<K, BTreeMap<K, B>> {
+    map.into_iter()
+        .map(|(outer, inner)| (outer, inner.into_iter().map(|(k, v)| (k, B::from(v))).collect()))
+        .collect()
+}
+
+impl From<FilesDetails> for LegacyFilesDetails {
+    fn from(native: FilesDetails) -> Self {
+        Self {
+            name: native.name,
+            media_type: native.media_type,
+            src: native.src.into(),
+            other_properties: native.other_properties,
+        }
+    }
+}
+
+impl From<LegacyFilesDetails> for FilesDetails {
+    fn from(legacy: LegacyFilesDetails) -> Self {
+        Self {
+            name: legacy.name,
+            media_type: legacy.media_type,
+            src: legacy.src.into(),
+            other_properties: legacy.other_properties,
+        }
+    }
+}
+
+impl TryFrom<LabelMetadata> for LegacyLabelMetadata {
+    type Error = serde_json::Error;
+
+    fn try_from(native: LabelMetadata) -> Result<Self, serde_json::Error> {
+        match native {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => match label_metadata_v1.version {
+                Some(_) => Err(legacy_error("a version 1 with a version field")),
+                None => Ok(Self::LabelMetadataV1(convert_nested_map(label_metadata_v1.data))),
+            },
+            LabelMetadata::LabelMetadataV2(label_metadata_v2) => Ok(Self::LabelMetadataV2(LegacyLabelMetadataV2::try_from(label_metadata_v2)?)),
+            LabelMetadata::Unknown { .. } => Err(legacy_error("an unknown CIP-25 version")),
+        }
+    }
+}
+
+impl From<LegacyLabelMetadata> for LabelMetadata {
+    fn from(legacy: LegacyLabelMetadata) -> Self {
+        match legacy {
+            LegacyLabelMetadata::LabelMetadataV1(label_metadata_v1) => Self::LabelMetadataV1(LabelMetadataV1::new(convert_nested_map(label_metadata_v1))),
+            LegacyLabelMetadata::LabelMetadataV2(label_metadata_v2) => Self::LabelMetadataV2(label_metadata_v2.into()),
+        }
+    }
+}
+
+// the version was always written as a number
+impl TryFrom<LabelMetadataV2> for LegacyLabelMetadataV2 {
+    type Error = serde_json::Error;
+
+    fn try_from(native: LabelMetadataV2) -> Result<Self, serde_json::Error> {
+        match native.version {
+            VersionFormat::Uint => Ok(Self {
+                data: convert_nested_map(native.data),
+            }),
+            VersionFormat::Text => Err(legacy_error("a version 2 written as text")),
+        }
+    }
+}
+
+// the keys were checked by deserialize_legacy_data
+impl From<LegacyLabelMetadataV2> for LabelMetadataV2 {
+    fn from(legacy: LegacyLabelMetadataV2) -> Self {
+        Self {
+            data: convert_nested_map(legacy.data),
+            version: VersionFormat::Uint,
+        }
+    }
+}
+
+impl TryFrom<Metadata> for LegacyMetadata {
+    type Error = serde_json::Error;
+
+    fn try_from(native: Metadata) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            key_721: LegacyLabelMetadata::try_from(native.key_721)?,
+        })
+    }
+}
+
+impl From<LegacyMetadata> for Metadata {
+    fn from(legacy: LegacyMetadata) -> Self {
+        Self::new(legacy.key_721.into())
+    }
+}
+
+impl From<MetadataDetails> for LegacyMetadataDetails {
+    fn from(native: MetadataDetails) -> Self {
+        Self {
+            name: native.name,
+            image: native.image.into(),
+            media_type: native.media_type,
+            description: native.description.map(Into::into),
+            files: native.files.map(|files| files.into_iter().map(Into::into).collect()),
+            other_properties: native.other_properties,
+        }
+    }
+}
+
+impl From<LegacyMetadataDetails> for MetadataDetails {
+    fn from(legacy: LegacyMetadataDetails) -> Self {
+        Self {
+            name: legacy.name,
+            image: legacy.image.into(),
+            media_type: legacy.media_type,
+            description: legacy.description.map(Into::into),
+            files: legacy.files.map(|files| files.into_iter().map(Into::into).collect()),
+            other_properties: legacy.other_properties,
+        }
+    }
+}
+
+impl From<String64OrArrString64> for LegacyString64OrArrString64 {
+    fn from(native: String64OrArrString64) -> Self {
+        match native {
+            String64OrArrString64::String64(string64) => Self::String64(string64),
+            String64OrArrString64::ArrString64(arr_string64) => Self::ArrString64(arr_string64),
+        }
+    }
+}
+
+impl From<LegacyString64OrArrString64> for String64OrArrString64 {
+    fn from(legacy: LegacyString64OrArrString64) -> Self {
+        match legacy {
+            LegacyString64OrArrString64::String64(string64) => Self::String64(string64),
+            LegacyString64OrArrString64::ArrString64(arr_string64) => Self::ArrString64(arr_string64),
+        }
+    }
+}
+
+// the conversion to the legacy type can fail for the ones holding a version
+macro_rules! impl_json_format {
+    ($native:ty, $legacy:ty) => {
+        impl_json_format!($native, $legacy, |native| Ok::<_, serde_json::Error>(<$legacy>::from(native)));
+    };
+    ($native:ty, $legacy:ty, $to_legacy:expr) => {
+        impl ToJson for $native {
+            fn to_json(&self, format: JsonFormat) -> Result<String, serde_json::Error> {
+                match format {
+                    JsonFormat::Cip25 => serde_json::to_string_pretty(self),
+                    JsonFormat::Legacy => serde_json::to_string_pretty(&($to_legacy)(self.clone())?),
+                }
+            }
+
+            fn to_json_value(&self, format: JsonFormat) -> Result<serde_json::Value, serde_json::Error> {
+                match format {
+                    JsonFormat::Cip25 => serde_json::to_value(self),
+                    JsonFormat::Legacy => serde_json::to_value(&($to_legacy)(self.clone())?),
+                }
+            }
+        }
+
+        impl FromJson for $native {
+            fn from_json(json: &str, format: JsonFormat) -> Result<Self, serde_json::Error> {
+                match format {
+                    JsonFormat::Cip25 => serde_json::from_str(json),
+                    JsonFormat::Legacy => serde_json::from_str::<$legacy>(json).map(Self::from),
+                }
+            }
+        }
+    };
+}
+
+impl_json_format!(FilesDetails, LegacyFilesDetails);
+impl_json_format!(LabelMetadata, LegacyLabelMetadata, LegacyLabelMetadata::try_from);
+impl_json_format!(LabelMetadataV2, LegacyLabelMetadataV2, LegacyLabelMetadataV2::try_from);
+impl_json_format!(Metadata, LegacyMetadata, LegacyMetadata::try_from);
+impl_json_format!(MetadataDetails, LegacyMetadataDetails);
+impl_json_format!(String64OrArrString64, LegacyString64OrArrString64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::POLICY_ID;
+
+    fn text(len: usize) -> String {
+        "x".repeat(len)
+    }
+
+    #[test]
+    fn string64_json_enforces_the_limit() {
+        let string64: String64 = serde_json::from_str(&format!("\"{}\"", text(64))).unwrap();
+        assert_eq!(string64.get().len(), 64);
+        let err = serde_json::from_str::<String64>(&format!("\"{}\"", text(65))).unwrap_err();
+        assert!(err.to_string().contains("65 not in range 0 - 64"), "{}", err);
+        // the limit is on UTF-8 bytes, not characters
+        assert!(serde_json::from_str::<String64>(&format!("\"{}\"", "é".repeat(33))).is_err());
+    }
+
+    #[test]
+    fn oversize_details_rejected_in_both_formats() {
+        let cip25 = format!(r#"{{"name": "{}", "image": "ipfs://patate"}}"#, text(65));
+        let err = MetadataDetails::from_json(&cip25, JsonFormat::Cip25).unwrap_err();
+        assert!(err.to_string().contains("65 not in range 0 - 64"), "{}", err);
+        let legacy = format!(r#"{{"name": "Patate", "image": {{"ArrString64": ["ipfs://", "{}"]}}, "media_type": null, "description": null, "files": null}}"#, text(65));
+        let err = MetadataDetails::from_json(&legacy, JsonFormat::Legacy).unwrap_err();
+        assert!(err.to_string().contains("65 not in range 0 - 64"), "{}", err);
+        let files = format!(r#"{{"name": "Patate", "mediaType": "{}", "src": "ipfs://patate"}}"#, text(65));
+        assert!(FilesDetails::from_json(&files, JsonFormat::Cip25).is_err());
+    }
+
+    #[test]
+    fn oversize_chunk_rejected() {
+        let chunks = format!(r#"["ipfs://", "{}"]"#, text(65));
+        assert!(String64OrArrString64::from_json(&chunks, JsonFormat::Cip25).is_err());
+        let chunks = format!(r#"["ipfs://", "{}"]"#, text(64));
+        assert!(String64OrArrString64::from_json(&chunks, JsonFormat::Cip25).is_ok());
+    }
+
+    #[test]
+    fn oversize_label_metadata_rejected() {
+        let label_metadata = format!(r#"{{"7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373": {{"Patate": {{"name": "Patate", "image": "ipfs://patate", "description": "{}"}}}}}}"#, text(65));
+        assert!(LabelMetadata::from_json(&label_metadata, JsonFormat::Cip25).is_err());
+        let metadata = format!(r#"{{"721": {}}}"#, label_metadata);
+        assert!(serde_json::from_str::<Metadata>(&metadata).is_err());
+    }
+
+    #[test]
+    fn oversize_royalty_rejected() {
+        let royalty = format!(r#"{{"rate": "0.05", "addr": "{}"}}"#, text(65));
+        assert!(serde_json::from_str::<royalty::RoyaltyMetadata>(&royalty).is_err());
+    }
+
+    #[test]
+    fn oversize_other_properties_rejected() {
+        let key = format!(r#"{{"name": "Patate", "image": "ipfs://patate", "{}": 1}}"#, text(65));
+        let err = MetadataDetails::from_json(&key, JsonFormat::Cip25).unwrap_err();
+        assert!(err.to_string().contains("65 not in range 0 - 64"), "{}", err);
+        let value = format!(r#"{{"name": "Patate", "image": "ipfs://patate", "x": ["{}"]}}"#, text(65));
+        assert!(MetadataDetails::from_json(&value, JsonFormat::Cip25).is_err());
+        let file = format!(r#"{{"name": "Patate", "mediaType": "image/png", "src": "ipfs://patate", "x": "0x{}"}}"#, "00".repeat(65));
+        assert!(FilesDetails::from_json(&file, JsonFormat::Cip25).is_err());
+    }
+
+    #[test]
+    fn oversize_v2_keys_rejected() {
+        let label_metadata = format!(r#"{{"version": 2, "{}": {{"4e465431": {{"name": "Patate", "image": "ipfs://patate"}}}}}}"#, "01".repeat(65));
+        let err = LabelMetadata::from_json(&label_metadata, JsonFormat::Cip25).unwrap_err();
+        assert!(err.to_string().contains("65 not in range 0 - 64"), "{}", err);
+        let label_metadata = format!(r#"{{"version": 2, "{}": {{"{}": {{"name": "Patate", "image": "ipfs://patate"}}}}}}"#, "01".repeat(28), "02".repeat(65));
+        assert!(LabelMetadata::from_json(&label_metadata, JsonFormat::Cip25).is_err());
+    }
+
+    #[test]
+    fn unknown_version_round_trip() {
+        let text = |text: &str| TransactionMetadatum::Text(text.to_owned());
+        for version in [TransactionMetadatum::new_int(3), text("3.0")] {
+            // in key order as JSON objects don't keep the order of the CBOR maps
+            let details = TransactionMetadatum::new_map(vec![(text("image"), text("ipfs://image")), (text("name"), text("NFT"))]);
+            let raw = TransactionMetadatum::new_map(vec![
+                (text("data"), TransactionMetadatum::new_map(vec![(TransactionMetadatum::Bytes(vec![0xab; 28]), TransactionMetadatum::new_map(vec![(TransactionMetadatum::Bytes(b"NFT".to_vec()), details)]))])),
+                (text("future"), TransactionMetadatum::new_list(vec![TransactionMetadatum::new_int(-1), TransactionMetadatum::Bytes(vec![0])])),
+                (text(VERSION_KEY), version.clone()),
+            ]);
+            let label_metadata = LabelMetadata::from_bytes(raw.to_bytes().unwrap()).unwrap();
+            let json = label_metadata.to_json(JsonFormat::Cip25).unwrap();
+            match LabelMetadata::from_json(&json, JsonFormat::Cip25).unwrap() {
+                LabelMetadata::Unknown { version: decoded_version, raw: decoded_raw } => {
+                    assert_eq!(decoded_version, version);
+                    assert_eq!(decoded_raw, raw);
+                },
+                other => panic!("{:?}", other),
+            }
+            let metadata = Metadata::from_json(&format!(r#"{{"721": {}}}"#, json), JsonFormat::Cip25).unwrap();
+            assert_eq!(metadata.to_bytes().unwrap(), Metadata::new(label_metadata).to_bytes().unwrap());
+        }
+        // only versions written as the CIP does are unknown ones, others are still errors
+        for version in ["\"3\"", "\"2.5\"", "0"] {
+            let json = format!(r#"{{"version": {}}}"#, version);
+            let err = LabelMetadata::from_json(&json, JsonFormat::Cip25).unwrap_err();
+            assert!(err.to_string().contains("unsupported CIP-25 version"), "{}: {}", version, err);
+        }
+    }
+
+    fn string64(text: &str) -> String64 {
+        String64::new(text.to_owned()).unwrap()
+    }
+
+    fn details() -> MetadataDetails {
+        let mut details = MetadataDetails::new(string64("NFT 1"), String64OrArrString64::new_arr_string64(vec![string64("ipfs://"), string64("image")]));
+        details.media_type = Some(string64("image/png"));
+        let mut file = FilesDetails::new(string64("song"), string64("audio/mpeg"), String64OrArrString64::new_string64(string64("ipfs://song")));
+        file.other_properties.insert(String::from("track"), TransactionMetadatum::new_int(1));
+        details.files = Some(vec![file]);
+        details.other_properties.insert(String::from("hash"), TransactionMetadatum::Bytes(vec![0xab, 0xcd]));
+        details
+    }
+
+    fn details_json() -> serde_json::Value {
+        serde_json::json!({
+            "name": "NFT 1",
+            "image": ["ipfs://", "image"],
+            "mediaType": "image/png",
+            "files": [{"name": "song", "mediaType": "audio/mpeg", "src": "ipfs://song", "track": 1}],
+            "hash": "0xabcd",
+        })
+    }
+
+    fn legacy_details_json() -> serde_json::Value {
+        serde_json::json!({
+            "name": "NFT 1",
+            "image": {"ArrString64": ["ipfs://", "image"]},
+            "media_type": "image/png",
+            "description": null,
+            "files": [{"name": "song", "media_type": "audio/mpeg", "src": {"String64": "ipfs://song"}, "other_properties": {"track": 1}}],
+            "other_properties": {"hash": "0xabcd"},
+        })
+    }
+
+    fn label_metadata_v1() -> LabelMetadataV1 {
+        let mut assets = BTreeMap::new();
+        assets.insert(string64("NFT1"), details());
+        let mut data = BTreeMap::new();
+        data.insert(string64(POLICY_ID), assets);
+        LabelMetadataV1::new(data)
+    }
+
+    fn label_metadata_v2(version: VersionFormat) -> LabelMetadataV2 {
+        let mut assets = BTreeMap::new();
+        assets.insert(b"NFT1".to_vec(), details());
+        let mut data = BTreeMap::new();
+        data.insert(hex::decode(POLICY_ID).unwrap(), assets);
+        let mut label_metadata_v2 = LabelMetadataV2::new(data).unwrap();
+        label_metadata_v2.version = version;
+        label_metadata_v2
+    }
+
+    // the JSON is the expected one and reads back to the same CBOR
+    fn check_golden<T: ToJson + FromJson + ToBytes>(value: &T, format: JsonFormat, expected: serde_json::Value) {
+        assert_eq!(value.to_json_value(format).unwrap(), expected);
+        let decoded = T::from_json(&expected.to_string(), format).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), value.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn version_1_golden() {
+        let expected = serde_json::json!({POLICY_ID: {"NFT1": details_json()}});
+        check_golden(&LabelMetadata::new_label_metadata_v1(label_metadata_v1()), JsonFormat::Cip25, expected.clone());
+        let mut with_version = label_metadata_v1();
+        with_version.version = Some(VersionFormat::Text);
+        let mut expected_with_version = expected;
+        expected_with_version["version"] = serde_json::json!("1.0");
+        check_golden(&LabelMetadata::new_label_metadata_v1(with_version), JsonFormat::Cip25, expected_with_version);
+        let metadata = Metadata::new(LabelMetadata::new_label_metadata_v1(label_metadata_v1()));
+        check_golden(&metadata, JsonFormat::Cip25, serde_json::json!({"721": {POLICY_ID: {"NFT1": details_json()}}}));
+    }
+
+    #[test]
+    fn version_2_golden() {
+        let label_metadata_v2 = label_metadata_v2(VersionFormat::Uint);
+        check_golden(&label_metadata_v2, JsonFormat::Cip25, serde_json::json!({POLICY_ID: {"4e465431": details_json()}, "version": 2}));
+        check_golden(&LabelMetadata::new_label_metadata_v2(self::label_metadata_v2(VersionFormat::Text)), JsonFormat::Cip25, serde_json::json!({POLICY_ID: {"4e465431": details_json()}, "version": "2.0"}));
+    }
+
+    #[test]
+    fn legacy_golden() {
+        check_golden(&details(), JsonFormat::Legacy, legacy_details_json());
+        let expected = serde_json::json!({"key_721": {"LabelMetadataV1": {POLICY_ID: {"NFT1": legacy_details_json()}}}});
+        check_golden(&Metadata::new(LabelMetadata::new_label_metadata_v1(label_metadata_v1())), JsonFormat::Legacy, expected);
+        let err = LabelMetadata::new_label_metadata_v2(label_metadata_v2(VersionFormat::Uint)).to_json(JsonFormat::Legacy).unwrap_err();
+        assert!(err.to_string().contains("key must be a string"), "{}", err);
+        // JSON written before other properties existed
+        let mut old = legacy_details_json();
+        old.as_object_mut().unwrap().remove("other_properties");
+        old["files"][0].as_object_mut().unwrap().remove("other_properties");
+        let decoded = MetadataDetails::from_json(&old.to_string(), JsonFormat::Legacy).unwrap();
+        assert!(decoded.other_properties.is_empty());
+        assert!(decoded.files.unwrap()[0].other_properties.is_empty());
+    }
+
+    #[test]
+    fn legacy_rejects_what_it_cant_hold() {
+        let mut with_version = label_metadata_v1();
+        with_version.version = Some(VersionFormat::Uint);
+        let unknown = LabelMetadata::new_unknown(TransactionMetadatum::new_int(3), TransactionMetadatum::new_map(Vec::new()));
+        for label_metadata in [LabelMetadata::new_label_metadata_v1(with_version), LabelMetadata::new_label_metadata_v2(label_metadata_v2(VersionFormat::Text)), unknown] {
+            let err = label_metadata.to_json(JsonFormat::Legacy).unwrap_err();
+            assert!(err.to_string().contains("can't be written in the legacy JSON format"), "{}", err);
+            assert!(Metadata::new(label_metadata).to_json_value(JsonFormat::Legacy).is_err());
+        }
+        // the legacy shape never had unknown versions
+        let unknown = r#"{"Unknown": {"version": "03", "raw": "a0"}}"#;
+        assert!(LabelMetadata::from_json(unknown, JsonFormat::Legacy).is_err());
+    }
+
+    #[test]
+    fn version_2_keys_differing_in_case() {
+        let details = details_json();
+        let assets = serde_json::json!({POLICY_ID: {"4e465431": details, "4E465431": details}, "version": 2});
+        let err = LabelMetadata::from_json(&assets.to_string(), JsonFormat::Cip25).unwrap_err();
+        assert!(err.to_string().contains("duplicate asset name \"4e465431\""), "{}", err);
+        let policies = serde_json::json!({POLICY_ID: {"4e465431": details}, POLICY_ID.to_uppercase(): {"4e465432": details}, "version": 2});
+        let err = LabelMetadata::from_json(&policies.to_string(), JsonFormat::Cip25).unwrap_err();
+        assert!(err.to_string().contains("duplicate policy id"), "{}", err);
+    }
+}
@@ -16,6 +16,41 @@ pub mod prelude;
 
 pub mod serialization;
 
+pub mod json;
+
+pub mod metadata_json;
+
+pub mod transaction;
+
+pub mod diagnostic;
+
+pub mod encoding;
+
+pub mod size;
+
+pub mod batch;
+
+pub mod asset_name;
+
+pub mod cip14;
+
+pub mod cip67;
+
+pub mod cip68;
+
+pub mod royalty;
+
+pub mod music;
+
+pub mod native_script;
+
+pub mod mint;
+
+pub mod query;
+
+#[cfg(test)]
+mod test_utils;
+
 use std::collections::BTreeMap;
 
 use std::convert::{From, TryFrom};
@@ -26,7 +61,7 @@ pub type AssetNameV2 = Vec<u8>;
 
 pub type Data = BTreeMap<PolicyIdV2, BTreeMap<AssetNameV2, MetadataDetails>>;
 
-pub type LabelMetadataV1 = BTreeMap<PolicyIdV1, BTreeMap<AssetNameV1, MetadataDetails>>;
+pub type DataV1 = BTreeMap<PolicyIdV1, BTreeMap<AssetNameV1, MetadataDetails>>;
 
 pub type PolicyIdV1 = String64;
 
@@ -36,11 +71,17 @@ pub type PolicyIdV2 = Vec<u8>;
 
 pub type PolicyIdV2s = Vec<PolicyIdV2>;
 
+pub type TransactionMetadatumLabel = u64;
+
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 pub struct FilesDetails {
     pub name: String64,
+    #[serde(rename = "mediaType")]
     pub media_type: String64,
     pub src: String64OrArrString64,
+    // the "<other properties>" of CIP-25, e.g. the song of a CIP-60 music token
+    #[serde(flatten, default, deserialize_with = "json::deserialize_other_properties", skip_serializing_if = "BTreeMap::is_empty")]
+    pub other_properties: BTreeMap<String, TransactionMetadatum>,
 }
 
 impl FilesDetails {
@@ -49,14 +90,22 @@ impl FilesDetails {
             name,
             media_type,
             src,
+            other_properties: BTreeMap::new(),
         }
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+// serde/schemars impls are in json.rs as the JSON shape differs from the CBOR one
+#[derive(Clone, Debug)]
 pub enum LabelMetadata {
     LabelMetadataV1(LabelMetadataV1),
     LabelMetadataV2(LabelMetadataV2),
+    // A version newer than this library. The whole 721 metadatum is kept so that it can
+    // still be re-encoded as is or read with interpret_as_v2.
+    Unknown {
+        version: TransactionMetadatum,
+        raw: TransactionMetadatum,
+    },
 }
 
 impl LabelMetadata {
@@ -67,23 +116,145 @@ impl LabelMetadata {
     pub fn new_label_metadata_v2(label_metadata_v2: LabelMetadataV2) -> Self {
         Self::LabelMetadataV2(label_metadata_v2)
     }
+
+    pub fn new_unknown(version: TransactionMetadatum, raw: TransactionMetadatum) -> Self {
+        Self::Unknown {
+            version,
+            raw,
+        }
+    }
+
+    // Best-effort reading of an unknown version assuming it kept the version 2 shape,
+    // any other field than "data" is ignored. Ok(None) for version 1.
+    pub fn interpret_as_v2(&self) -> Result<Option<LabelMetadataV2>, DeserializeError> {
+        let (version, raw) = match self {
+            Self::LabelMetadataV1(_) => return Ok(None),
+            Self::LabelMetadataV2(label_metadata_v2) => return Ok(Some(label_metadata_v2.clone())),
+            Self::Unknown { version, raw } => (version, raw),
+        };
+        let mut entries = Vec::new();
+        if let TransactionMetadatum::Map(raw_entries) = raw {
+            entries.extend(raw_entries.iter().filter(|(key, _)| *key == TransactionMetadatum::Text(String::from("data"))).take(1).cloned());
+        }
+        entries.push((TransactionMetadatum::Text(String::from(VERSION_KEY)), TransactionMetadatum::Int(2)));
+        let mut label_metadata_v2 = LabelMetadataV2::from_bytes(TransactionMetadatum::Map(entries).to_bytes()?)
+            .map_err(|e| e.annotate("interpret_as_v2"))?;
+        label_metadata_v2.version = match version {
+            TransactionMetadatum::Text(_) => VersionFormat::Text,
+            _ => VersionFormat::Uint,
+        };
+        Ok(Some(label_metadata_v2))
+    }
+
+    // Version 1 policy ids are hex text and asset names are UTF-8 text, version 2 uses the raw
+    // bytes for both. The version is written the same way (number or text) as in the input,
+    // a version 1 without version field gives a number.
+    pub fn convert_to_v2(&self) -> Result<LabelMetadataV2, DeserializeError> {
+        let label_metadata_v1 = match self {
+            Self::LabelMetadataV1(label_metadata_v1) => label_metadata_v1,
+            Self::LabelMetadataV2(label_metadata_v2) => return Ok(label_metadata_v2.clone()),
+            Self::Unknown { version, .. } => return Err(unsupported_version(version).annotate("convert_to_v2")),
+        };
+        (|| -> Result<_, DeserializeError> {
+            let mut data = BTreeMap::new();
+            // the version 1 text of each policy id, hex case is lost in version 2
+            let mut policy_id_texts: BTreeMap<PolicyIdV2, &str> = BTreeMap::new();
+            for (policy_id_text, assets) in label_metadata_v1.data.iter() {
+                let policy_id = hex::decode(policy_id_text.get())
+                    .map_err(|e| DeserializeError::from(cbor_event::Error::CustomError(format!("policy id is not valid hex: {}", e))).annotate_path(PathSegment::PolicyId(String::from(policy_id_text.get()))))?;
+                if let Some(existing) = policy_id_texts.insert(policy_id.clone(), policy_id_text.get()) {
+                    return Err(cbor_event::Error::CustomError(format!("policy ids \"{}\" and \"{}\" are the same policy id in version 2", existing, policy_id_text.get())).into());
+                }
+                let assets = assets
+                    .iter()
+                    .map(|(asset_name, details)| (asset_name.get().as_bytes().to_vec(), details.clone()))
+                    .collect();
+                data.insert(policy_id, assets);
+            }
+            Ok(LabelMetadataV2 {
+                data,
+                version: label_metadata_v1.version.unwrap_or(VersionFormat::Uint),
+            })
+        })().map_err(|e| e.annotate("convert_to_v2"))
+    }
+
+    // see convert_to_v2, asset names that aren't UTF-8 can't be converted
+    pub fn convert_to_v1(&self) -> Result<LabelMetadataV1, DeserializeError> {
+        let label_metadata_v2 = match self {
+            Self::LabelMetadataV1(label_metadata_v1) => return Ok(label_metadata_v1.clone()),
+            Self::LabelMetadataV2(label_metadata_v2) => label_metadata_v2,
+            Self::Unknown { version, .. } => return Err(unsupported_version(version).annotate("convert_to_v1")),
+        };
+        (|| -> Result<_, DeserializeError> {
+            let mut data = BTreeMap::new();
+            for (policy_id, assets) in label_metadata_v2.data.iter() {
+                let policy_id_text = hex::encode(policy_id);
+                let mut assets_v1 = BTreeMap::new();
+                for (asset_name, details) in assets.iter() {
+                    let asset_name_v1 = String::from_utf8(asset_name.clone())
+                        .map_err(|_| cbor_event::Error::CustomError(String::from("asset name is not UTF-8 so it can't be used in version 1")).into())
+                        .and_then(String64::new)
+                        .map_err(|e: DeserializeError| e.annotate_path(PathSegment::AssetName(hex::encode(asset_name))).annotate_path(PathSegment::PolicyId(policy_id_text.clone())))?;
+                    assets_v1.insert(asset_name_v1, details.clone());
+                }
+                let policy_id = String64::new(policy_id_text.clone()).map_err(|e| e.annotate_path(PathSegment::PolicyId(policy_id_text)))?;
+                data.insert(policy_id, assets_v1);
+            }
+            Ok(LabelMetadataV1 {
+                data,
+                version: Some(label_metadata_v2.version),
+            })
+        })().map_err(|e| e.annotate("convert_to_v1"))
+    }
 }
 
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+fn unsupported_version(version: &TransactionMetadatum) -> DeserializeError {
+    match version_from_metadatum(version.clone()) {
+        Err(e) => e,
+        // not reached, the version of unknown metadata isn't a supported one
+        Ok((number, _)) => DeserializeFailure::UnsupportedVersion(Key::Uint(number)).into(),
+    }
+}
+
+// The policy ids are stored in the same map as the optional version field
+// serde/schemars impls are in json.rs as the JSON shape differs from the CBOR one
+#[derive(Clone, Debug)]
+pub struct LabelMetadataV1 {
+    pub data: DataV1,
+    // None when there is no version field
+    pub version: Option<VersionFormat>,
+}
+
+impl LabelMetadataV1 {
+    pub fn new(data: DataV1) -> Self {
+        Self {
+            data,
+            version: None,
+        }
+    }
+}
+
+// serde/schemars impls are in json.rs as the JSON shape differs from the CBOR one
+#[derive(Clone, Debug)]
 pub struct LabelMetadataV2 {
     pub data: Data,
+    pub version: VersionFormat,
 }
 
 impl LabelMetadataV2 {
-    pub fn new(data: Data) -> Self {
-        Self {
+    // policy ids and asset names are bytes metadatums so they are bound to MAX_METADATUM_LEN too
+    pub fn new(data: Data) -> Result<Self, DeserializeError> {
+        check_data_keys(&data).map_err(|e| e.annotate("LabelMetadataV2"))?;
+        Ok(Self {
             data,
-        }
+            version: VersionFormat::Uint,
+        })
     }
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 pub struct Metadata {
+    #[serde(rename = "721")]
     pub key_721: LabelMetadata,
 }
 
@@ -99,9 +270,15 @@ impl Metadata {
 pub struct MetadataDetails {
     pub name: String64,
     pub image: String64OrArrString64,
+    #[serde(rename = "mediaType", default, skip_serializing_if = "Option::is_none")]
     pub media_type: Option<String64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String64OrArrString64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub files: Option<Vec<FilesDetails>>,
+    // the "<other properties>" of CIP-25, e.g. the release of a CIP-60 music token
+    #[serde(flatten, default, deserialize_with = "json::deserialize_other_properties", skip_serializing_if = "BTreeMap::is_empty")]
+    pub other_properties: BTreeMap<String, TransactionMetadatum>,
 }
 
 impl MetadataDetails {
@@ -112,11 +289,43 @@ impl MetadataDetails {
             media_type: None,
             description: None,
             files: None,
+            other_properties: BTreeMap::new(),
         }
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema, Eq, PartialEq, Ord, PartialOrd)]
+// the ledger limit on every text and bytes of transaction metadata, counted in bytes
+pub const MAX_METADATUM_LEN: usize = 64;
+
+// How many maps / lists can be nested in a metadatum when decoding, as values are decoded
+// recursively. The ledger has no such limit, this is the one serde_json has for JSON.
+pub const MAX_METADATUM_DEPTH: usize = 128;
+
+pub fn check_metadatum_len(len: usize) -> Result<(), DeserializeFailure> {
+    if len > MAX_METADATUM_LEN {
+        return Err(DeserializeFailure::RangeCheck{ found: len, min: Some(0), max: Some(MAX_METADATUM_LEN as isize) });
+    }
+    Ok(())
+}
+
+// the policy ids and asset names of version 2 data
+pub(crate) fn check_data_keys<T>(data: &BTreeMap<PolicyIdV2, BTreeMap<AssetNameV2, T>>) -> Result<(), DeserializeError> {
+    for (policy_id, assets) in data.iter() {
+        (|| -> Result<_, DeserializeError> {
+            check_metadatum_len(policy_id.len())?;
+            for asset_name in assets.keys() {
+                check_metadatum_len(asset_name.len()).map_err(|e| DeserializeError::from(e).annotate_path(PathSegment::AssetName(hex::encode(asset_name))))?;
+            }
+            Ok(())
+        })().map_err(|e| e.annotate_path(PathSegment::PolicyId(hex::encode(policy_id))).annotate_path(PathSegment::Field(String::from("data"))))?;
+    }
+    Ok(())
+}
+
+// schemars impl is in json.rs to express the length limit
+// JSON goes through String64::new as well so the limit holds for every from_json
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(try_from = "String", into = "String")]
 pub struct String64(String);
 
 impl String64 {
@@ -125,9 +334,7 @@ impl String64 {
     }
 
     pub fn new(inner: String) -> Result<Self, DeserializeError> {
-        if inner.len() > 64 {
-            return Err(DeserializeError::new("String64", DeserializeFailure::RangeCheck{ found: inner.len(), min: Some(0), max: Some(64) }));
-        }
+        check_metadatum_len(inner.len()).map_err(|e| DeserializeError::new("String64", e))?;
         Ok(Self(inner))
     }
 }
@@ -147,6 +354,7 @@ impl From<String64> for String {
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(untagged)]
 pub enum String64OrArrString64 {
     String64(String64),
     ArrString64(Vec<String64>),
@@ -160,4 +368,51 @@ impl String64OrArrString64 {
     pub fn new_arr_string64(arr_string64: Vec<String64>) -> Self {
         Self::ArrString64(arr_string64)
     }
-}
\ No newline at end of file
+}
+
+// How the version field of the 721 map is written (1 / 2 or "1.0" / "2.0"), kept so that
+// re-encoding gives back the same bytes. The version number itself is given by the
+// LabelMetadata variant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VersionFormat {
+    Uint,
+    Text,
+}
+
+// Generic transaction metadatum as defined in the ledger CDDL. Map entries are kept in
+// their original order (and with any duplicates) as this is not a CIP-25 specific type.
+// Texts and bytes over MAX_METADATUM_LEN built with the variants directly are only
+// rejected when encoding.
+// The serde/schemars impls (cardano-cli "no schema" JSON) are in json.rs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransactionMetadatum {
+    Map(Vec<(TransactionMetadatum, TransactionMetadatum)>),
+    List(Vec<TransactionMetadatum>),
+    Int(i128),
+    Bytes(Vec<u8>),
+    Text(String),
+}
+
+impl TransactionMetadatum {
+    pub fn new_map(map: Vec<(TransactionMetadatum, TransactionMetadatum)>) -> Self {
+        Self::Map(map)
+    }
+
+    pub fn new_list(list: Vec<TransactionMetadatum>) -> Self {
+        Self::List(list)
+    }
+
+    pub fn new_int(int: i128) -> Self {
+        Self::Int(int)
+    }
+
+    pub fn new_bytes(bytes: Vec<u8>) -> Result<Self, DeserializeError> {
+        check_metadatum_len(bytes.len()).map_err(|e| DeserializeError::new("TransactionMetadatum", e))?;
+        Ok(Self::Bytes(bytes))
+    }
+
+    pub fn new_text(text: String) -> Result<Self, DeserializeError> {
+        check_metadatum_len(text.len()).map_err(|e| DeserializeError::new("TransactionMetadatum", e))?;
+        Ok(Self::Text(text))
+    }
+}
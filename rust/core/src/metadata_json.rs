@@ -0,0 +1,423 @@
+// Conversion to/from the JSON metadata formats accepted by
+// `cardano-cli transaction build --metadata-json-file` (see --json-metadata-no-schema
+// and --json-metadata-detailed-schema). Both formats map labels to metadatums:
+//
+// no schema:       { "721": { "<policy>": { "<asset>": { "name": "...", ... } } } }
+// detailed schema: { "721": { "map": [ { "k": { "string": "<policy>" }, "v": ... } ] } }
+
+use super::*;
+use serde_json::{Map, Number, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataJsonSchema {
+    // JSON strings are text (or bytes when "0x"-prefixed), JSON numbers are ints
+    NoSchema,
+    // every metadatum is an object tagged with its type e.g. { "int": 1 }
+    DetailedSchema,
+}
+
+#[derive(Debug)]
+pub enum MetadataJsonError {
+    Json(serde_json::Error),
+    ToplevelNotMap,
+    ToplevelBadKey(String),
+    Schema{
+        label: TransactionMetadatumLabel,
+        reason: String,
+    },
+    Deserialize(DeserializeError),
+    Serialize(SerializeError),
+}
+
+impl std::fmt::Display for MetadataJsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetadataJsonError::Json(e) => write!(f, "Invalid JSON: {}", e),
+            MetadataJsonError::ToplevelNotMap => write!(f, "The JSON metadata top level must be a map (JSON object) from labels to values"),
+            MetadataJsonError::ToplevelBadKey(key) => write!(f, "The JSON metadata top level must be a map with unsigned integer keys, found key \"{}\"", key),
+            MetadataJsonError::Schema{ label, reason } => write!(f, "JSON schema error within label {}: {}", label, reason),
+            MetadataJsonError::Deserialize(e) => e.fmt(f),
+            MetadataJsonError::Serialize(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for MetadataJsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MetadataJsonError::Json(e) => Some(e),
+            MetadataJsonError::Deserialize(e) => e.source(),
+            MetadataJsonError::Serialize(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for MetadataJsonError {
+    fn from(err: serde_json::Error) -> MetadataJsonError {
+        MetadataJsonError::Json(err)
+    }
+}
+
+impl From<DeserializeError> for MetadataJsonError {
+    fn from(err: DeserializeError) -> MetadataJsonError {
+        MetadataJsonError::Deserialize(err)
+    }
+}
+
+impl From<SerializeError> for MetadataJsonError {
+    fn from(err: SerializeError) -> MetadataJsonError {
+        MetadataJsonError::Serialize(err)
+    }
+}
+
+// metadatum ints are bounded by the ledger to the range of a CBOR uint / nint
+const METADATUM_INT_MIN: i128 = -(u64::MAX as i128) - 1;
+const METADATUM_INT_MAX: i128 = u64::MAX as i128;
+
+fn int_from_json(number: &Number) -> Result<i128, String> {
+    match (number.as_u64(), number.as_i64()) {
+        (Some(uint), _) => Ok(uint as i128),
+        (None, Some(int)) => Ok(int as i128),
+        (None, None) => Err(format!("{} is not an integer in the supported range", number)),
+    }
+}
+
+fn int_to_json(int: i128) -> Result<Value, String> {
+    if int >= 0 {
+        u64::try_from(int).map(Value::from).map_err(|_| format!("{} can't be represented as a JSON number", int))
+    } else {
+        i64::try_from(int).map(Value::from).map_err(|_| format!("{} can't be represented as a JSON number", int))
+    }
+}
+
+// cardano-cli only treats lowercase hex after a "0x" prefix as bytes, anything else is text
+fn bytes_from_prefixed_hex(text: &str) -> Option<Vec<u8>> {
+    let hex_str = text.strip_prefix("0x")?;
+    if hex_str.chars().any(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    hex::decode(hex_str).ok()
+}
+
+// cardano-cli rejects longer texts and bytes as the ledger would
+fn text_metadatum(text: &str) -> Result<TransactionMetadatum, String> {
+    if text.len() > MAX_METADATUM_LEN {
+        return Err(format!("text \"{}\" is {} bytes long, metadata texts can't be longer than {} bytes", text, text.len(), MAX_METADATUM_LEN));
+    }
+    Ok(TransactionMetadatum::Text(text.to_owned()))
+}
+
+fn bytes_metadatum(bytes: Vec<u8>) -> Result<TransactionMetadatum, String> {
+    if bytes.len() > MAX_METADATUM_LEN {
+        return Err(format!("bytes 0x{} are {} bytes long, metadata bytes can't be longer than {} bytes", hex::encode(&bytes), bytes.len(), MAX_METADATUM_LEN));
+    }
+    Ok(TransactionMetadatum::Bytes(bytes))
+}
+
+pub fn metadatum_from_json(value: &Value, schema: MetadataJsonSchema) -> Result<TransactionMetadatum, String> {
+    match schema {
+        MetadataJsonSchema::NoSchema => metadatum_from_no_schema_json(value),
+        MetadataJsonSchema::DetailedSchema => metadatum_from_detailed_schema_json(value),
+    }
+}
+
+pub fn metadatum_to_json(metadatum: &TransactionMetadatum, schema: MetadataJsonSchema) -> Result<Value, String> {
+    match schema {
+        MetadataJsonSchema::NoSchema => metadatum_to_no_schema_json(metadatum),
+        MetadataJsonSchema::DetailedSchema => metadatum_to_detailed_schema_json(metadatum),
+    }
+}
+
+fn metadatum_from_no_schema_json(value: &Value) -> Result<TransactionMetadatum, String> {
+    match value {
+        Value::Null => Err(String::from("null is not allowed in metadata")),
+        Value::Bool(_) => Err(String::from("booleans are not allowed in metadata")),
+        Value::Number(number) => int_from_json(number).map(TransactionMetadatum::Int),
+        Value::String(text) => match bytes_from_prefixed_hex(text) {
+            Some(bytes) => bytes_metadatum(bytes),
+            None => text_metadatum(text),
+        },
+        Value::Array(list) => list
+            .iter()
+            .map(metadatum_from_no_schema_json)
+            .collect::<Result<Vec<_>, _>>()
+            .map(TransactionMetadatum::List),
+        Value::Object(map) => {
+            let mut entries = Vec::new();
+            for (key, value) in map.iter() {
+                // keys can only be strings in JSON so decimal numbers and "0x" hex are reinterpreted
+                let key = match (key.parse::<i128>().ok(), bytes_from_prefixed_hex(key)) {
+                    (Some(int), _) if (METADATUM_INT_MIN..=METADATUM_INT_MAX).contains(&int) => TransactionMetadatum::Int(int),
+                    (_, Some(bytes)) => bytes_metadatum(bytes)?,
+                    _ => text_metadatum(key)?,
+                };
+                entries.push((key, metadatum_from_no_schema_json(value)?));
+            }
+            Ok(TransactionMetadatum::Map(entries))
+        },
+    }
+}
+
+fn metadatum_to_no_schema_json(metadatum: &TransactionMetadatum) -> Result<Value, String> {
+    match metadatum {
+        TransactionMetadatum::Map(map) => {
+            let mut object = Map::new();
+            for (key, value) in map.iter() {
+                let key = match key {
+                    TransactionMetadatum::Int(int) => int.to_string(),
+                    TransactionMetadatum::Bytes(bytes) => format!("0x{}", hex::encode(bytes)),
+                    TransactionMetadatum::Text(text) => text.clone(),
+                    // same as cardano-cli: compound keys are rendered as their JSON text
+                    other => metadatum_to_no_schema_json(other)?.to_string(),
+                };
+                object.insert(key, metadatum_to_no_schema_json(value)?);
+            }
+            Ok(Value::Object(object))
+        },
+        TransactionMetadatum::List(list) => list
+            .iter()
+            .map(metadatum_to_no_schema_json)
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        TransactionMetadatum::Int(int) => int_to_json(*int),
+        TransactionMetadatum::Bytes(bytes) => Ok(Value::String(format!("0x{}", hex::encode(bytes)))),
+        TransactionMetadatum::Text(text) => Ok(Value::String(text.clone())),
+    }
+}
+
+fn metadatum_from_detailed_schema_json(value: &Value) -> Result<TransactionMetadatum, String> {
+    let (tag, inner) = match value {
+        Value::Object(object) if object.len() == 1 => object.iter().next().unwrap(),
+        _ => return Err(format!("expected an object with a single key (int, bytes, string, list or map), found {}", value)),
+    };
+    match (tag.as_str(), inner) {
+        ("int", Value::Number(number)) => int_from_json(number).map(TransactionMetadatum::Int),
+        ("bytes", Value::String(hex_str)) => hex::decode(hex_str)
+            .map_err(|e| format!("\"{}\" is not valid hex: {}", hex_str, e))
+            .and_then(bytes_metadatum),
+        ("string", Value::String(text)) => text_metadatum(text),
+        ("list", Value::Array(list)) => list
+            .iter()
+            .map(metadatum_from_detailed_schema_json)
+            .collect::<Result<Vec<_>, _>>()
+            .map(TransactionMetadatum::List),
+        ("map", Value::Array(entries)) => entries
+            .iter()
+            .map(|entry| match entry {
+                Value::Object(kv) if kv.len() == 2 => match (kv.get("k"), kv.get("v")) {
+                    (Some(k), Some(v)) => Ok((metadatum_from_detailed_schema_json(k)?, metadatum_from_detailed_schema_json(v)?)),
+                    _ => Err(format!("expected a map entry of the form {{\"k\": ..., \"v\": ...}}, found {}", entry)),
+                },
+                _ => Err(format!("expected a map entry of the form {{\"k\": ..., \"v\": ...}}, found {}", entry)),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(TransactionMetadatum::Map),
+        _ => Err(format!("unexpected detailed schema value {}", value)),
+    }
+}
+
+fn metadatum_to_detailed_schema_json(metadatum: &TransactionMetadatum) -> Result<Value, String> {
+    let (tag, inner) = match metadatum {
+        TransactionMetadatum::Map(map) => {
+            let mut entries = Vec::new();
+            for (key, value) in map.iter() {
+                let mut entry = Map::new();
+                entry.insert(String::from("k"), metadatum_to_detailed_schema_json(key)?);
+                entry.insert(String::from("v"), metadatum_to_detailed_schema_json(value)?);
+                entries.push(Value::Object(entry));
+            }
+            ("map", Value::Array(entries))
+        },
+        TransactionMetadatum::List(list) => ("list", list
+            .iter()
+            .map(metadatum_to_detailed_schema_json)
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array)?),
+        TransactionMetadatum::Int(int) => ("int", int_to_json(*int)?),
+        TransactionMetadatum::Bytes(bytes) => ("bytes", Value::String(hex::encode(bytes))),
+        TransactionMetadatum::Text(text) => ("string", Value::String(text.clone())),
+    };
+    let mut object = Map::new();
+    object.insert(String::from(tag), inner);
+    Ok(Value::Object(object))
+}
+
+// Parses a whole cardano-cli metadata JSON file into its labels and metadatums
+pub fn transaction_metadata_from_json(json: &str, schema: MetadataJsonSchema) -> Result<BTreeMap<TransactionMetadatumLabel, TransactionMetadatum>, MetadataJsonError> {
+    let object = match serde_json::from_str::<Value>(json)? {
+        Value::Object(object) => object,
+        _ => return Err(MetadataJsonError::ToplevelNotMap),
+    };
+    let mut metadata = BTreeMap::new();
+    for (key, value) in object.iter() {
+        let label = key.parse::<TransactionMetadatumLabel>().map_err(|_| MetadataJsonError::ToplevelBadKey(key.clone()))?;
+        let metadatum = metadatum_from_json(value, schema).map_err(|reason| MetadataJsonError::Schema{ label, reason })?;
+        metadata.insert(label, metadatum);
+    }
+    Ok(metadata)
+}
+
+pub fn transaction_metadata_to_json(metadata: &BTreeMap<TransactionMetadatumLabel, TransactionMetadatum>, schema: MetadataJsonSchema) -> Result<String, MetadataJsonError> {
+    let mut object = Map::new();
+    for (label, metadatum) in metadata.iter() {
+        let value = metadatum_to_json(metadatum, schema).map_err(|reason| MetadataJsonError::Schema{ label: *label, reason })?;
+        object.insert(label.to_string(), value);
+    }
+    Ok(serde_json::to_string_pretty(&Value::Object(object))?)
+}
+
+impl Metadata {
+    // Other labels present in the file are accepted but ignored
+    pub fn from_metadata_json(json: &str, schema: MetadataJsonSchema) -> Result<Self, MetadataJsonError> {
+        let metadata = transaction_metadata_from_json(json, schema)?
+            .into_iter()
+            .map(|(label, metadatum)| (TransactionMetadatum::Int(label as i128), metadatum))
+            .collect();
+        Ok(Metadata::from_bytes(TransactionMetadatum::Map(metadata).to_bytes()?)?)
+    }
+
+    pub fn to_metadata_json(&self, schema: MetadataJsonSchema) -> Result<String, MetadataJsonError> {
+        let mut metadata = BTreeMap::new();
+        metadata.insert(721, TransactionMetadatum::from_bytes(self.key_721.to_bytes()?)?);
+        transaction_metadata_to_json(&metadata, schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rejected_as_too_long(json: Value, schema: MetadataJsonSchema) {
+        let err = metadatum_from_json(&json, schema).unwrap_err();
+        assert!(err.contains("can't be longer than 64 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn no_schema_texts_and_bytes_are_limited() {
+        assert!(metadatum_from_json(&Value::String("x".repeat(64)), MetadataJsonSchema::NoSchema).is_ok());
+        assert_rejected_as_too_long(Value::String("x".repeat(65)), MetadataJsonSchema::NoSchema);
+        assert_rejected_as_too_long(Value::String(format!("0x{}", "00".repeat(65))), MetadataJsonSchema::NoSchema);
+        assert_rejected_as_too_long(serde_json::json!({ "x".repeat(65): 1 }), MetadataJsonSchema::NoSchema);
+        assert_rejected_as_too_long(serde_json::json!([["x".repeat(65)]]), MetadataJsonSchema::NoSchema);
+    }
+
+    #[test]
+    fn detailed_schema_texts_and_bytes_are_limited() {
+        assert!(metadatum_from_json(&serde_json::json!({ "bytes": "00".repeat(64) }), MetadataJsonSchema::DetailedSchema).is_ok());
+        assert_rejected_as_too_long(serde_json::json!({ "string": "x".repeat(65) }), MetadataJsonSchema::DetailedSchema);
+        assert_rejected_as_too_long(serde_json::json!({ "bytes": "00".repeat(65) }), MetadataJsonSchema::DetailedSchema);
+        assert_rejected_as_too_long(serde_json::json!({ "map": [{ "k": { "string": "x".repeat(65) }, "v": { "int": 1 } }] }), MetadataJsonSchema::DetailedSchema);
+    }
+
+    fn text(text: &str) -> TransactionMetadatum {
+        TransactionMetadatum::Text(text.to_owned())
+    }
+
+    // the same metadatum as in the JSON below, with the keys in JSON order
+    fn metadatum() -> TransactionMetadatum {
+        TransactionMetadatum::new_map(vec![
+            (TransactionMetadatum::new_int(-5), text("negative key")),
+            (text("0xABCD"), text("0xABCD")),
+            (TransactionMetadatum::Bytes(vec![0xab, 0xcd]), TransactionMetadatum::Bytes(Vec::new())),
+            (TransactionMetadatum::new_int(1), text("int key")),
+            (text("bytes"), TransactionMetadatum::Bytes(vec![0xab, 0xcd])),
+            (text("ints"), TransactionMetadatum::new_list(vec![
+                TransactionMetadatum::new_int(-1),
+                TransactionMetadatum::new_int(i64::MIN as i128),
+                TransactionMetadatum::new_int(u64::MAX as i128),
+            ])),
+            (text("not hex"), text("0xzz")),
+        ])
+    }
+
+    fn check_round_trip(json: Value, schema: MetadataJsonSchema) {
+        let metadata = transaction_metadata_from_json(&json.to_string(), schema).unwrap();
+        assert_eq!(metadata.keys().cloned().collect::<Vec<_>>(), vec![674, 721]);
+        assert_eq!(metadata[&721], metadatum());
+        let exported = transaction_metadata_to_json(&metadata, schema).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&exported).unwrap(), json);
+    }
+
+    #[test]
+    fn no_schema_round_trip() {
+        // lowercase hex after "0x" is bytes, for keys too, anything else is text
+        check_round_trip(serde_json::json!({
+            "674": { "msg": ["hello"] },
+            "721": {
+                "-5": "negative key",
+                "0xABCD": "0xABCD",
+                "0xabcd": "0x",
+                "1": "int key",
+                "bytes": "0xabcd",
+                "ints": [-1, i64::MIN, u64::MAX],
+                "not hex": "0xzz",
+            },
+        }), MetadataJsonSchema::NoSchema);
+    }
+
+    #[test]
+    fn detailed_schema_round_trip() {
+        let entry = |k: Value, v: Value| serde_json::json!({ "k": k, "v": v });
+        check_round_trip(serde_json::json!({
+            "674": { "map": [entry(serde_json::json!({ "string": "msg" }), serde_json::json!({ "list": [{ "string": "hello" }] }))] },
+            "721": { "map": [
+                entry(serde_json::json!({ "int": -5 }), serde_json::json!({ "string": "negative key" })),
+                entry(serde_json::json!({ "string": "0xABCD" }), serde_json::json!({ "string": "0xABCD" })),
+                entry(serde_json::json!({ "bytes": "abcd" }), serde_json::json!({ "bytes": "" })),
+                entry(serde_json::json!({ "int": 1 }), serde_json::json!({ "string": "int key" })),
+                entry(serde_json::json!({ "string": "bytes" }), serde_json::json!({ "bytes": "abcd" })),
+                entry(serde_json::json!({ "string": "ints" }), serde_json::json!({ "list": [{ "int": -1 }, { "int": i64::MIN }, { "int": u64::MAX }] })),
+                entry(serde_json::json!({ "string": "not hex" }), serde_json::json!({ "string": "0xzz" })),
+            ] },
+        }), MetadataJsonSchema::DetailedSchema);
+    }
+
+    #[test]
+    fn cip25_round_trip() {
+        let bytes = hex::decode(include_str!("../tests/fixtures/v1.hex").trim()).unwrap();
+        let metadata = Metadata::from_bytes(bytes).unwrap();
+        for schema in [MetadataJsonSchema::NoSchema, MetadataJsonSchema::DetailedSchema] {
+            let json = metadata.to_metadata_json(schema).unwrap();
+            let imported = Metadata::from_metadata_json(&json, schema).unwrap();
+            assert_eq!(imported.to_bytes().unwrap(), metadata.to_bytes().unwrap(), "{:?}", schema);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let no_schema = |json: &str| transaction_metadata_from_json(json, MetadataJsonSchema::NoSchema).unwrap_err();
+        assert!(matches!(no_schema("{"), MetadataJsonError::Json(_)));
+        assert!(matches!(no_schema("[]"), MetadataJsonError::ToplevelNotMap));
+        assert!(matches!(no_schema(r#"{"-1": 1}"#), MetadataJsonError::ToplevelBadKey(key) if key == "-1"));
+        assert!(matches!(no_schema(r#"{"721": 1, "x": 1}"#), MetadataJsonError::ToplevelBadKey(key) if key == "x"));
+        // not metadatums
+        for (json, reason) in [(r#"{"1": null}"#, "null"), (r#"{"1": [true]}"#, "booleans"), (r#"{"1": 1.5}"#, "not an integer"), (r#"{"1": 18446744073709551616}"#, "not an integer")] {
+            match no_schema(json) {
+                MetadataJsonError::Schema{ label: 1, reason: found } => assert!(found.contains(reason), "{}: {}", json, found),
+                other => panic!("{}: {}", json, other),
+            }
+        }
+        let detailed = |json: &str| transaction_metadata_from_json(json, MetadataJsonSchema::DetailedSchema).unwrap_err();
+        for json in [r#"{"1": 1}"#, r#"{"1": {"int": 1, "string": "x"}}"#, r#"{"1": {"bytes": "0xab"}}"#, r#"{"1": {"int": "1"}}"#, r#"{"1": {"map": [{"k": {"int": 1}}]}}"#] {
+            assert!(matches!(detailed(json), MetadataJsonError::Schema{ label: 1, .. }), "{}", json);
+        }
+        // valid metadata JSON but not CIP-25
+        let err = Metadata::from_metadata_json(r#"{"721": 1}"#, MetadataJsonSchema::NoSchema).unwrap_err();
+        assert!(matches!(err, MetadataJsonError::Deserialize(_)), "{}", err);
+        // ints beyond what a JSON number holds can't be exported
+        let mut metadata = BTreeMap::new();
+        metadata.insert(1, TransactionMetadatum::new_int(i64::MIN as i128 - 1));
+        let err = transaction_metadata_to_json(&metadata, MetadataJsonSchema::NoSchema).unwrap_err();
+        assert!(err.to_string().starts_with("JSON schema error within label 1"), "{}", err);
+        // nor metadata that can't be encoded
+        let mut details = MetadataDetails::new(String64::new(String::from("NFT")).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("x")).unwrap()));
+        details.other_properties.insert(String::from("x"), text(&"x".repeat(65)));
+        let mut assets = BTreeMap::new();
+        assets.insert(String64::new(String::from("NFT")).unwrap(), details);
+        let mut data = BTreeMap::new();
+        data.insert(String64::new(String::from("ab")).unwrap(), assets);
+        let err = Metadata::new(LabelMetadata::new_label_metadata_v1(LabelMetadataV1::new(data))).to_metadata_json(MetadataJsonSchema::NoSchema).unwrap_err();
+        assert!(matches!(err, MetadataJsonError::Serialize(_)), "{}", err);
+    }
+}
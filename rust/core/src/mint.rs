@@ -0,0 +1,195 @@
+// Checking the 721 metadata of a transaction against what it mints. The metadata only
+// means something for the assets minted by the same transaction, and CIP-25 tokens are
+// NFTs so each is minted once.
+//
+// An asset is taken as an NFT when it has the CIP-67 label 222 or no label at all. Other
+// labelled assets (fungible 333, rich fungible 444, CIP-68 reference 100) can be minted
+// by any quantity and don't need 721 metadata.
+
+use super::*;
+use crate::cip67::TokenClass;
+use crate::query::AssetMetadataQuery;
+
+// the mint field of a transaction body, negative quantities are burns
+pub type Mint = BTreeMap<PolicyIdV2, BTreeMap<AssetNameV2, i64>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MintIssueKind {
+    // described in the metadata but not minted (or burnt) by the transaction
+    NotMinted,
+    // an NFT minted without being described
+    MissingMetadata,
+    // described in the metadata but burnt
+    BurnWithMetadata,
+    // described NFT minted more than once
+    QuantityAboveOne,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MintIssue {
+    pub policy_id: PolicyIdV2,
+    pub asset_name: AssetNameV2,
+    // 0 when the asset isn't in the mint
+    pub quantity: i64,
+    pub kind: MintIssueKind,
+}
+
+fn is_nft(asset_name: &[u8]) -> bool {
+    matches!(TokenClass::of(asset_name), TokenClass::Nft | TokenClass::Unlabelled)
+}
+
+impl LabelMetadata {
+    // The issues of the described assets in the order of the metadata, then the minted NFTs
+    // missing metadata in the order of the mint. Version 1 policy ids that aren't hex can't
+    // be minted and are skipped, as with assets().
+    pub fn check_mint(&self, mint: &Mint) -> Result<Vec<MintIssue>, DeserializeError> {
+        let described = self.assets()?;
+        let mut issues = Vec::new();
+        for (policy_id, asset_name) in &described {
+            let quantity = mint.get(policy_id).and_then(|assets| assets.get(asset_name)).copied().unwrap_or(0);
+            let kind = match quantity {
+                0 => MintIssueKind::NotMinted,
+                quantity if quantity < 0 => MintIssueKind::BurnWithMetadata,
+                quantity if quantity > 1 && is_nft(asset_name) => MintIssueKind::QuantityAboveOne,
+                _ => continue,
+            };
+            issues.push(MintIssue {
+                policy_id: policy_id.clone(),
+                asset_name: asset_name.clone(),
+                quantity,
+                kind,
+            });
+        }
+        for (policy_id, assets) in mint {
+            for (asset_name, quantity) in assets {
+                // an unlabelled asset minted more than once is a fungible token
+                let minted_nft = match TokenClass::of(asset_name) {
+                    TokenClass::Nft => *quantity > 0,
+                    TokenClass::Unlabelled => *quantity == 1,
+                    _ => false,
+                };
+                if minted_nft && !described.iter().any(|(described_policy_id, described_asset_name)| described_policy_id == policy_id && described_asset_name == asset_name) {
+                    issues.push(MintIssue {
+                        policy_id: policy_id.clone(),
+                        asset_name: asset_name.clone(),
+                        quantity: *quantity,
+                        kind: MintIssueKind::MissingMetadata,
+                    });
+                }
+            }
+        }
+        Ok(issues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cip67::{label_prefix, FT_LABEL, NFT_LABEL, REFERENCE_TOKEN_LABEL};
+
+    fn labelled(label: u16, name: &str) -> AssetNameV2 {
+        [&label_prefix(label)[..], name.as_bytes()].concat()
+    }
+
+    fn details() -> MetadataDetails {
+        MetadataDetails::new(String64::new(String::from("NFT")).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("ipfs://nft")).unwrap()))
+    }
+
+    fn metadata_v2(assets: &[(&PolicyIdV2, &AssetNameV2)]) -> LabelMetadata {
+        let mut data: Data = BTreeMap::new();
+        for (policy_id, asset_name) in assets {
+            data.entry((*policy_id).clone()).or_default().insert((*asset_name).clone(), details());
+        }
+        LabelMetadata::new_label_metadata_v2(LabelMetadataV2::new(data).unwrap())
+    }
+
+    fn mint(assets: &[(&PolicyIdV2, &AssetNameV2, i64)]) -> Mint {
+        let mut mint: Mint = BTreeMap::new();
+        for (policy_id, asset_name, quantity) in assets {
+            mint.entry((*policy_id).clone()).or_default().insert((*asset_name).clone(), *quantity);
+        }
+        mint
+    }
+
+    fn issue(policy_id: &PolicyIdV2, asset_name: &AssetNameV2, quantity: i64, kind: MintIssueKind) -> MintIssue {
+        MintIssue {
+            policy_id: policy_id.clone(),
+            asset_name: asset_name.clone(),
+            quantity,
+            kind,
+        }
+    }
+
+    #[test]
+    fn described_assets() {
+        let policy_id = vec![1; 28];
+        let minted = b"minted".to_vec();
+        let not_minted = b"not minted".to_vec();
+        let burnt = b"burnt".to_vec();
+        let unlabelled_many = b"many".to_vec();
+        let nft_many = labelled(NFT_LABEL, "many");
+        let ft_many = labelled(FT_LABEL, "many");
+        let label_metadata = metadata_v2(&[(&policy_id, &minted), (&policy_id, &not_minted), (&policy_id, &burnt), (&policy_id, &unlabelled_many), (&policy_id, &nft_many), (&policy_id, &ft_many)]);
+        let mint = mint(&[(&policy_id, &minted, 1), (&policy_id, &burnt, -1), (&policy_id, &unlabelled_many, 5), (&policy_id, &nft_many, 2), (&policy_id, &ft_many, 100)]);
+        // in the order of the metadata, i.e. of the asset names, the fungible token being
+        // fine with any quantity
+        assert_eq!(label_metadata.check_mint(&mint).unwrap(), vec![
+            issue(&policy_id, &nft_many, 2, MintIssueKind::QuantityAboveOne),
+            issue(&policy_id, &burnt, -1, MintIssueKind::BurnWithMetadata),
+            // described so taken as an NFT
+            issue(&policy_id, &unlabelled_many, 5, MintIssueKind::QuantityAboveOne),
+            issue(&policy_id, &not_minted, 0, MintIssueKind::NotMinted),
+        ]);
+    }
+
+    #[test]
+    fn minted_without_metadata() {
+        let policy_id = vec![1; 28];
+        let other_policy_id = vec![2; 28];
+        let described = b"described".to_vec();
+        let unlabelled_one = b"one".to_vec();
+        let unlabelled_many = b"many".to_vec();
+        let nft_one = labelled(NFT_LABEL, "one");
+        let nft_many = labelled(NFT_LABEL, "many");
+        let nft_burnt = labelled(NFT_LABEL, "burnt");
+        let ft_one = labelled(FT_LABEL, "one");
+        let reference = labelled(REFERENCE_TOKEN_LABEL, "one");
+        let label_metadata = metadata_v2(&[(&policy_id, &described)]);
+        let mint = mint(&[
+            (&policy_id, &described, 1),
+            // an unlabelled asset is an NFT only when minted once
+            (&policy_id, &unlabelled_one, 1),
+            (&policy_id, &unlabelled_many, 1000),
+            (&other_policy_id, &unlabelled_one, 1),
+            // any quantity of an NFT labelled asset, but not a burn
+            (&policy_id, &nft_one, 1),
+            (&policy_id, &nft_many, 3),
+            (&policy_id, &nft_burnt, -1),
+            // no metadata needed
+            (&policy_id, &ft_one, 1),
+            (&policy_id, &reference, 1),
+        ]);
+        // in the order of the mint
+        assert_eq!(label_metadata.check_mint(&mint).unwrap(), vec![
+            issue(&policy_id, &nft_many, 3, MintIssueKind::MissingMetadata),
+            issue(&policy_id, &nft_one, 1, MintIssueKind::MissingMetadata),
+            issue(&policy_id, &unlabelled_one, 1, MintIssueKind::MissingMetadata),
+            issue(&other_policy_id, &unlabelled_one, 1, MintIssueKind::MissingMetadata),
+        ]);
+    }
+
+    #[test]
+    fn version_1_policy_ids() {
+        let policy_id = vec![0x7e; 28];
+        let mut assets = BTreeMap::new();
+        assets.insert(String64::new(String::from("NFT1")).unwrap(), details());
+        let mut data = BTreeMap::new();
+        data.insert(String64::new(hex::encode(&policy_id)).unwrap(), assets.clone());
+        // can't be minted so it isn't reported
+        data.insert(String64::new(String::from("not hex")).unwrap(), assets);
+        let label_metadata = LabelMetadata::new_label_metadata_v1(LabelMetadataV1::new(data));
+        let nft1 = b"NFT1".to_vec();
+        assert!(label_metadata.check_mint(&mint(&[(&policy_id, &nft1, 1)])).unwrap().is_empty());
+        assert_eq!(label_metadata.check_mint(&Mint::new()).unwrap(), vec![issue(&policy_id, &nft1, 0, MintIssueKind::NotMinted)]);
+    }
+}
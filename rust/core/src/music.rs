@@ -0,0 +1,671 @@
+// CIP-60 music tokens, described with other properties of the CIP-25 details.
+//
+// version 1:      { ..., "music_metadata_version": 1, "release_type": ..., "album_title": ...,
+//                   "files": [{ "name": ..., "mediaType": ..., "src": ..., "song_title": ..., ... }] }
+// versions 2, 3:  { ..., "music_metadata_version": 2, "release": { "release_type": ..., "release_title": ..., ... },
+//                   "files": [{ "name": ..., "mediaType": ..., "src": ..., "song": { "song_title": ..., ... } }] }
+//
+// Only the fields needed to list a song are typed, the others (mood, lyrics, isrc, links, ...)
+// are kept as they are in other_properties. Version 3 only adds optional fields to version 2.
+
+use super::*;
+
+pub const MUSIC_METADATA_VERSION_KEY: &str = "music_metadata_version";
+
+const RELEASE_KEY: &str = "release";
+
+const SONG_KEY: &str = "song";
+
+const RELEASE_TYPE_KEY: &str = "release_type";
+
+const RELEASE_TITLE_KEY: &str = "release_title";
+
+// the release title of version 1
+const ALBUM_TITLE_KEY: &str = "album_title";
+
+const SONG_KEYS: [&str; 6] = ["song_title", "song_duration", "track_number", "artists", "genres", "copyright"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MusicMetadataVersion {
+    V1,
+    V2,
+    V3,
+}
+
+impl MusicMetadataVersion {
+    pub fn number(&self) -> u64 {
+        match self {
+            MusicMetadataVersion::V1 => 1,
+            MusicMetadataVersion::V2 => 2,
+            MusicMetadataVersion::V3 => 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Artist {
+    pub name: String64,
+    // e.g. isni, links
+    pub other_properties: BTreeMap<String, TransactionMetadatum>,
+}
+
+impl Artist {
+    pub fn new(name: String64) -> Self {
+        Self {
+            name,
+            other_properties: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Release {
+    // "Single", "Multiple" or "Album/EP"
+    pub release_type: String64,
+    // mandatory from version 2 on, album_title in version 1
+    pub release_title: Option<String64OrArrString64>,
+    // e.g. distributor, visual_artist, copyright, always empty in version 1
+    pub other_properties: BTreeMap<String, TransactionMetadatum>,
+}
+
+impl Release {
+    pub fn new(release_type: String64) -> Self {
+        Self {
+            release_type,
+            release_title: None,
+            other_properties: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Song {
+    pub song_title: String64OrArrString64,
+    // ISO 8601 duration, e.g. "PT3M21S"
+    pub song_duration: String64,
+    // from 1
+    pub track_number: u64,
+    // at least one
+    pub artists: Vec<Artist>,
+    pub genres: Vec<String64>,
+    pub copyright: String64OrArrString64,
+    // e.g. mood, lyrics, isrc, explicit, contributing_artists
+    pub other_properties: BTreeMap<String, TransactionMetadatum>,
+}
+
+impl Song {
+    pub fn new(song_title: String64OrArrString64, song_duration: String64, track_number: u64, artists: Vec<Artist>, genres: Vec<String64>, copyright: String64OrArrString64) -> Self {
+        Self {
+            song_title,
+            song_duration,
+            track_number,
+            artists,
+            genres,
+            copyright,
+            other_properties: BTreeMap::new(),
+        }
+    }
+}
+
+// a file of the details, with its song if it is an audio file of the release
+#[derive(Clone, Debug)]
+pub struct MusicFile {
+    // without the song fields
+    pub file: FilesDetails,
+    pub song: Option<Song>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MusicMetadata {
+    pub version: MusicMetadataVersion,
+    pub release: Release,
+    // all the files of the details, in order
+    pub files: Vec<MusicFile>,
+}
+
+impl MusicMetadata {
+    pub fn new(version: MusicMetadataVersion, release: Release, files: Vec<MusicFile>) -> Self {
+        Self {
+            version,
+            release,
+            files,
+        }
+    }
+
+    pub fn songs(&self) -> impl Iterator<Item = &Song> {
+        self.files.iter().filter_map(|file| file.song.as_ref())
+    }
+
+    // Ok(None) when the details have no music_metadata_version
+    pub fn from_details(details: &MetadataDetails) -> Result<Option<Self>, DeserializeError> {
+        let version = match details.other_properties.get(MUSIC_METADATA_VERSION_KEY) {
+            Some(version) => read_version(version).map_err(|e| field_error(e, MUSIC_METADATA_VERSION_KEY).annotate("MusicMetadata"))?,
+            None => return Ok(None),
+        };
+        let music_metadata = (|| -> Result<_, DeserializeError> {
+            let release = match version {
+                MusicMetadataVersion::V1 => read_release_v1(&details.other_properties)?,
+                _ => match details.other_properties.get(RELEASE_KEY) {
+                    Some(release) => read_release(release).map_err(|e| field_error(e, RELEASE_KEY))?,
+                    None => return Err(DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from(RELEASE_KEY))).into()),
+                },
+            };
+            let mut files = Vec::new();
+            for (index, file) in details.files.iter().flatten().enumerate() {
+                let music_file = read_music_file(file, version).map_err(|e| e.annotate_path(PathSegment::Index(index)).annotate_path(PathSegment::Field(String::from("files"))))?;
+                files.push(music_file);
+            }
+            Ok(Self {
+                version,
+                release,
+                files,
+            })
+        })().map_err(|e| e.annotate("MusicMetadata"))?;
+        music_metadata.validate()?;
+        Ok(Some(music_metadata))
+    }
+
+    // Checks what the types can't: a release title from version 2 on, at least one song,
+    // track numbers from 1 and at least one artist per song.
+    pub fn validate(&self) -> Result<(), DeserializeError> {
+        (|| -> Result<_, DeserializeError> {
+            if self.version >= MusicMetadataVersion::V2 && self.release.release_title.is_none() {
+                return Err(field_error(DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from(RELEASE_TITLE_KEY))).into(), RELEASE_KEY));
+            }
+            if self.songs().next().is_none() {
+                return Err(DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from(SONG_KEY))).into());
+            }
+            for (index, file) in self.files.iter().enumerate() {
+                let song = match &file.song {
+                    Some(song) => song,
+                    None => continue,
+                };
+                let in_song = |e: DeserializeError| match self.version {
+                    MusicMetadataVersion::V1 => e,
+                    _ => e.annotate_path(PathSegment::Field(String::from(SONG_KEY))),
+                }.annotate_path(PathSegment::Index(index)).annotate_path(PathSegment::Field(String::from("files")));
+                if song.track_number == 0 {
+                    return Err(in_song(field_error(DeserializeFailure::RangeCheck{ found: 0, min: Some(1), max: None }.into(), "track_number")));
+                }
+                if song.artists.is_empty() {
+                    return Err(in_song(field_error(DeserializeFailure::RangeCheck{ found: 0, min: Some(1), max: None }.into(), "artists")));
+                }
+            }
+            Ok(())
+        })().map_err(|e| e.annotate("MusicMetadata"))
+    }
+
+    // Writes the version, release and files into the details, replacing their files and
+    // removing the fields of the other versions' layout.
+    pub fn apply(&self, details: &mut MetadataDetails) {
+        let other_properties = &mut details.other_properties;
+        other_properties.insert(String::from(MUSIC_METADATA_VERSION_KEY), TransactionMetadatum::Int(self.version.number() as i128));
+        match self.version {
+            MusicMetadataVersion::V1 => {
+                other_properties.remove(RELEASE_KEY);
+                other_properties.insert(String::from(RELEASE_TYPE_KEY), text(&self.release.release_type));
+                match &self.release.release_title {
+                    Some(release_title) => other_properties.insert(String::from(ALBUM_TITLE_KEY), chunked(release_title)),
+                    None => other_properties.remove(ALBUM_TITLE_KEY),
+                };
+                other_properties.extend(self.release.other_properties.clone());
+            },
+            _ => {
+                other_properties.remove(RELEASE_TYPE_KEY);
+                other_properties.remove(ALBUM_TITLE_KEY);
+                other_properties.insert(String::from(RELEASE_KEY), write_release(&self.release));
+            },
+        }
+        let files = self
+            .files
+            .iter()
+            .map(|music_file| {
+                let mut file = music_file.file.clone();
+                file.other_properties.remove(SONG_KEY);
+                for key in SONG_KEYS {
+                    file.other_properties.remove(key);
+                }
+                if let Some(song) = &music_file.song {
+                    match self.version {
+                        MusicMetadataVersion::V1 => file.other_properties.extend(song_entries(song)),
+                        _ => {
+                            file.other_properties.insert(String::from(SONG_KEY), TransactionMetadatum::Map(song_entries(song).into_iter().map(|(key, value)| (TransactionMetadatum::Text(key), value)).collect()));
+                        },
+                    }
+                }
+                file
+            })
+            .collect::<Vec<_>>();
+        details.files = if files.is_empty() { None } else { Some(files) };
+    }
+}
+
+fn field_error(e: DeserializeError, field: &str) -> DeserializeError {
+    e.annotate(field).annotate_path(PathSegment::Field(String::from(field)))
+}
+
+fn metadatum_type(value: &TransactionMetadatum) -> CBORType {
+    match value {
+        TransactionMetadatum::Map(_) => CBORType::Map,
+        TransactionMetadatum::List(_) => CBORType::Array,
+        TransactionMetadatum::Int(int) if *int < 0 => CBORType::NegativeInteger,
+        TransactionMetadatum::Int(_) => CBORType::UnsignedInteger,
+        TransactionMetadatum::Bytes(_) => CBORType::Bytes,
+        TransactionMetadatum::Text(_) => CBORType::Text,
+    }
+}
+
+fn expected(expected: CBORType, value: &TransactionMetadatum) -> DeserializeError {
+    DeserializeFailure::CBOR(cbor_event::Error::Expected(expected, metadatum_type(value))).into()
+}
+
+// decodes value with the CBOR decoder of T
+fn decode<T: Deserialize>(value: &TransactionMetadatum) -> Result<T, DeserializeError> {
+    let mut raw = Deserializer::from(std::io::Cursor::new(value.to_bytes()?));
+    T::deserialize(&mut raw)
+}
+
+fn read_version(value: &TransactionMetadatum) -> Result<MusicMetadataVersion, DeserializeError> {
+    match value {
+        TransactionMetadatum::Int(1) => Ok(MusicMetadataVersion::V1),
+        TransactionMetadatum::Int(2) => Ok(MusicMetadataVersion::V2),
+        TransactionMetadatum::Int(3) => Ok(MusicMetadataVersion::V3),
+        TransactionMetadatum::Int(other) => Err(DeserializeFailure::RangeCheck{ found: usize::try_from(*other).unwrap_or(0), min: Some(1), max: Some(3) }.into()),
+        other => Err(expected(CBORType::UnsignedInteger, other)),
+    }
+}
+
+// text keys only, in order
+fn map_entries(value: &TransactionMetadatum) -> Result<Vec<(&str, &TransactionMetadatum)>, DeserializeError> {
+    let entries = match value {
+        TransactionMetadatum::Map(entries) => entries,
+        other => return Err(expected(CBORType::Map, other)),
+    };
+    let mut text_entries: Vec<(&str, &TransactionMetadatum)> = Vec::with_capacity(entries.len());
+    for (key, value) in entries {
+        let key = match key {
+            TransactionMetadatum::Text(key) => key.as_str(),
+            other => return Err(DeserializeFailure::UnexpectedKeyType(metadatum_type(other)).into()),
+        };
+        if text_entries.iter().any(|(existing, _)| *existing == key) {
+            return Err(DeserializeFailure::DuplicateKey(Key::Str(key.to_owned())).into());
+        }
+        text_entries.push((key, value));
+    }
+    Ok(text_entries)
+}
+
+fn read_list<T>(value: &TransactionMetadatum, read: impl Fn(&TransactionMetadatum) -> Result<T, DeserializeError>) -> Result<Vec<T>, DeserializeError> {
+    match value {
+        TransactionMetadatum::List(list) => list
+            .iter()
+            .enumerate()
+            .map(|(index, element)| read(element).map_err(|e| e.annotate_path(PathSegment::Index(index))))
+            .collect(),
+        other => Err(expected(CBORType::Array, other)),
+    }
+}
+
+fn read_release_v1(other_properties: &BTreeMap<String, TransactionMetadatum>) -> Result<Release, DeserializeError> {
+    let release_type = match other_properties.get(RELEASE_TYPE_KEY) {
+        Some(release_type) => decode(release_type).map_err(|e| field_error(e, RELEASE_TYPE_KEY))?,
+        None => return Err(DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from(RELEASE_TYPE_KEY))).into()),
+    };
+    let release_title = match other_properties.get(ALBUM_TITLE_KEY) {
+        Some(album_title) => Some(decode(album_title).map_err(|e| field_error(e, ALBUM_TITLE_KEY))?),
+        None => None,
+    };
+    Ok(Release {
+        release_type,
+        release_title,
+        other_properties: BTreeMap::new(),
+    })
+}
+
+fn read_release(value: &TransactionMetadatum) -> Result<Release, DeserializeError> {
+    let mut release_type = None;
+    let mut release_title = None;
+    let mut other_properties = BTreeMap::new();
+    for (key, value) in map_entries(value)? {
+        match key {
+            RELEASE_TYPE_KEY => release_type = Some(decode(value).map_err(|e| field_error(e, key))?),
+            RELEASE_TITLE_KEY => release_title = Some(decode(value).map_err(|e| field_error(e, key))?),
+            other_key => {
+                other_properties.insert(other_key.to_owned(), value.clone());
+            },
+        }
+    }
+    Ok(Release {
+        release_type: release_type.ok_or_else(|| DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from(RELEASE_TYPE_KEY))))?,
+        release_title,
+        other_properties,
+    })
+}
+
+fn write_release(release: &Release) -> TransactionMetadatum {
+    let mut entries = vec![(TransactionMetadatum::Text(String::from(RELEASE_TYPE_KEY)), text(&release.release_type))];
+    if let Some(release_title) = &release.release_title {
+        entries.push((TransactionMetadatum::Text(String::from(RELEASE_TITLE_KEY)), chunked(release_title)));
+    }
+    entries.extend(release.other_properties.iter().map(|(key, value)| (TransactionMetadatum::Text(key.clone()), value.clone())));
+    TransactionMetadatum::Map(entries)
+}
+
+fn read_artist(value: &TransactionMetadatum) -> Result<Artist, DeserializeError> {
+    let mut name = None;
+    let mut other_properties = BTreeMap::new();
+    for (key, value) in map_entries(value)? {
+        match key {
+            "name" => name = Some(decode(value).map_err(|e| field_error(e, key))?),
+            other_key => {
+                other_properties.insert(other_key.to_owned(), value.clone());
+            },
+        }
+    }
+    Ok(Artist {
+        name: name.ok_or_else(|| DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from("name"))))?,
+        other_properties,
+    })
+}
+
+fn write_artist(artist: &Artist) -> TransactionMetadatum {
+    let mut entries = vec![(TransactionMetadatum::Text(String::from("name")), text(&artist.name))];
+    entries.extend(artist.other_properties.iter().map(|(key, value)| (TransactionMetadatum::Text(key.clone()), value.clone())));
+    TransactionMetadatum::Map(entries)
+}
+
+// the song fields out of entries, what is left goes to other_properties
+fn read_song<'a>(entries: impl Iterator<Item = (&'a str, &'a TransactionMetadatum)>) -> Result<Song, DeserializeError> {
+    let mut song_title = None;
+    let mut song_duration = None;
+    let mut track_number = None;
+    let mut artists = None;
+    let mut genres = None;
+    let mut copyright = None;
+    let mut other_properties = BTreeMap::new();
+    for (key, value) in entries {
+        match key {
+            "song_title" => song_title = Some(decode(value).map_err(|e| field_error(e, key))?),
+            "song_duration" => song_duration = Some(decode(value).map_err(|e| field_error(e, key))?),
+            "track_number" => track_number = Some(decode::<u64>(value).map_err(|e| field_error(e, key))?),
+            "artists" => artists = Some(read_list(value, read_artist).map_err(|e| field_error(e, key))?),
+            "genres" => genres = Some(read_list(value, decode).map_err(|e| field_error(e, key))?),
+            "copyright" => copyright = Some(decode(value).map_err(|e| field_error(e, key))?),
+            other_key => {
+                other_properties.insert(other_key.to_owned(), value.clone());
+            },
+        }
+    }
+    let missing = |key: &str| DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from(key)));
+    Ok(Song {
+        song_title: song_title.ok_or_else(|| missing("song_title"))?,
+        song_duration: song_duration.ok_or_else(|| missing("song_duration"))?,
+        track_number: track_number.ok_or_else(|| missing("track_number"))?,
+        artists: artists.ok_or_else(|| missing("artists"))?,
+        genres: genres.ok_or_else(|| missing("genres"))?,
+        copyright: copyright.ok_or_else(|| missing("copyright"))?,
+        other_properties,
+    })
+}
+
+fn song_entries(song: &Song) -> Vec<(String, TransactionMetadatum)> {
+    let mut entries = vec![
+        (String::from("song_title"), chunked(&song.song_title)),
+        (String::from("song_duration"), text(&song.song_duration)),
+        (String::from("track_number"), TransactionMetadatum::Int(song.track_number as i128)),
+        (String::from("artists"), TransactionMetadatum::List(song.artists.iter().map(write_artist).collect())),
+        (String::from("genres"), TransactionMetadatum::List(song.genres.iter().map(text).collect())),
+        (String::from("copyright"), chunked(&song.copyright)),
+    ];
+    entries.extend(song.other_properties.iter().map(|(key, value)| (key.clone(), value.clone())));
+    entries
+}
+
+fn read_music_file(file: &FilesDetails, version: MusicMetadataVersion) -> Result<MusicFile, DeserializeError> {
+    let mut without_song = file.clone();
+    let song = match version {
+        MusicMetadataVersion::V1 => {
+            // the song fields are those of the file in version 1
+            if !SONG_KEYS.iter().any(|key| file.other_properties.contains_key(*key)) {
+                None
+            } else {
+                without_song.other_properties.clear();
+                let song = read_song(file.other_properties.iter().map(|(key, value)| (key.as_str(), value)))?;
+                Some(song)
+            }
+        },
+        _ => match without_song.other_properties.remove(SONG_KEY) {
+            Some(song) => Some((|| -> Result<_, DeserializeError> {
+                read_song(map_entries(&song)?.into_iter())
+            })().map_err(|e| field_error(e, SONG_KEY))?),
+            None => None,
+        },
+    };
+    Ok(MusicFile {
+        file: without_song,
+        song,
+    })
+}
+
+fn text(text: &String64) -> TransactionMetadatum {
+    TransactionMetadatum::Text(text.get().clone())
+}
+
+fn chunked(text: &String64OrArrString64) -> TransactionMetadatum {
+    match text {
+        String64OrArrString64::String64(string64) => TransactionMetadatum::Text(string64.get().clone()),
+        String64OrArrString64::ArrString64(chunks) => TransactionMetadatum::List(chunks.iter().map(|chunk| TransactionMetadatum::Text(chunk.get().clone())).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::EncodingOptions;
+
+    // a single with a cover image file and an audio file
+    fn details_v1() -> MetadataDetails {
+        serde_json::from_str(r#"{
+            "name": "Song 1",
+            "image": "ipfs://cover",
+            "music_metadata_version": 1,
+            "release_type": "Single",
+            "album_title": "Album 1",
+            "files": [
+                { "name": "Cover", "mediaType": "image/png", "src": "ipfs://cover" },
+                {
+                    "name": "Song 1",
+                    "mediaType": "audio/mpeg",
+                    "src": "ipfs://song1",
+                    "artists": [{ "name": "Artist 1", "isni": "0000000000000001" }],
+                    "copyright": "℗ 2023 Label",
+                    "genres": ["Electronic"],
+                    "mood": "Chill",
+                    "song_duration": "PT3M21S",
+                    "song_title": ["Song ", "1"],
+                    "track_number": 1
+                }
+            ]
+        }"#).unwrap()
+    }
+
+    fn details_v2(version: u64) -> MetadataDetails {
+        serde_json::from_str(&r#"{
+            "name": "Song 1",
+            "image": "ipfs://cover",
+            "music_metadata_version": VERSION,
+            "release": { "release_type": "Single", "release_title": "Album 1", "distributor": "Distributor" },
+            "files": [
+                { "name": "Cover", "mediaType": "image/png", "src": "ipfs://cover" },
+                {
+                    "name": "Song 1",
+                    "mediaType": "audio/mpeg",
+                    "src": "ipfs://song1",
+                    "song": {
+                        "song_title": ["Song ", "1"],
+                        "song_duration": "PT3M21S",
+                        "track_number": 1,
+                        "artists": [{ "name": "Artist 1", "isni": "0000000000000001" }],
+                        "genres": ["Electronic"],
+                        "copyright": "℗ 2023 Label",
+                        "mood": "Chill"
+                    }
+                }
+            ]
+        }"#.replace("VERSION", &version.to_string())).unwrap()
+    }
+
+    fn music_metadata(details: &MetadataDetails) -> MusicMetadata {
+        MusicMetadata::from_details(details).unwrap().unwrap()
+    }
+
+    fn applied(music_metadata: &MusicMetadata, details: &MetadataDetails) -> MetadataDetails {
+        let mut details = details.clone();
+        music_metadata.apply(&mut details);
+        details
+    }
+
+    // the same details whatever the order of the map keys, which apply doesn't keep
+    fn same_details(a: &MetadataDetails, b: &MetadataDetails) -> bool {
+        a.to_bytes_with(&EncodingOptions::CoreDeterministic).unwrap() == b.to_bytes_with(&EncodingOptions::CoreDeterministic).unwrap()
+    }
+
+    fn file_property<'a>(details: &'a MetadataDetails, index: usize, key: &str) -> Option<&'a TransactionMetadatum> {
+        details.files.as_ref().unwrap()[index].other_properties.get(key)
+    }
+
+    fn check_song(music_metadata: &MusicMetadata) {
+        assert_eq!(music_metadata.release.release_type.get(), "Single");
+        assert!(matches!(&music_metadata.release.release_title, Some(String64OrArrString64::String64(title)) if title.get() == "Album 1"));
+        assert_eq!(music_metadata.files.len(), 2);
+        assert!(music_metadata.files[0].song.is_none());
+        let song = music_metadata.files[1].song.as_ref().unwrap();
+        assert!(matches!(&song.song_title, String64OrArrString64::ArrString64(chunks) if chunks.len() == 2));
+        assert_eq!(song.song_duration.get(), "PT3M21S");
+        assert_eq!(song.track_number, 1);
+        assert_eq!(song.artists.len(), 1);
+        assert_eq!(song.artists[0].name.get(), "Artist 1");
+        assert!(song.artists[0].other_properties.contains_key("isni"));
+        assert_eq!(song.genres.len(), 1);
+        // the file keeps only its own fields
+        assert_eq!(song.other_properties.keys().collect::<Vec<_>>(), vec!["mood"]);
+        assert!(music_metadata.files[1].file.other_properties.is_empty());
+        assert!(matches!(&music_metadata.files[1].file.src, String64OrArrString64::String64(src) if src.get() == "ipfs://song1"));
+    }
+
+    #[test]
+    fn v1_flat() {
+        let music_metadata = music_metadata(&details_v1());
+        assert_eq!(music_metadata.version, MusicMetadataVersion::V1);
+        check_song(&music_metadata);
+        assert!(music_metadata.release.other_properties.is_empty());
+    }
+
+    #[test]
+    fn v2_and_v3_nested() {
+        for (number, version) in [(2, MusicMetadataVersion::V2), (3, MusicMetadataVersion::V3)] {
+            let music_metadata = music_metadata(&details_v2(number));
+            assert_eq!(music_metadata.version, version);
+            check_song(&music_metadata);
+            assert_eq!(music_metadata.release.other_properties.keys().collect::<Vec<_>>(), vec!["distributor"]);
+        }
+    }
+
+    #[test]
+    fn without_music_metadata_version() {
+        let details = MetadataDetails::new(String64::new(String::from("NFT 1")).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("ipfs://nft1")).unwrap()));
+        assert!(MusicMetadata::from_details(&details).unwrap().is_none());
+    }
+
+    #[test]
+    fn apply_round_trips() {
+        for details in [details_v1(), details_v2(2), details_v2(3)] {
+            let music_metadata = music_metadata(&details);
+            assert!(same_details(&applied(&music_metadata, &details), &details));
+        }
+    }
+
+    #[test]
+    fn apply_converts_between_layouts() {
+        let mut music_metadata = music_metadata(&details_v1());
+        music_metadata.version = MusicMetadataVersion::V2;
+        // the album title becomes the release title
+        let details = applied(&music_metadata, &details_v1());
+        for key in [RELEASE_TYPE_KEY, ALBUM_TITLE_KEY] {
+            assert!(!details.other_properties.contains_key(key));
+        }
+        assert!(file_property(&details, 1, "song_title").is_none());
+        assert!(file_property(&details, 1, SONG_KEY).is_some());
+        let converted = self::music_metadata(&details);
+        assert_eq!(converted.version, MusicMetadataVersion::V2);
+        check_song(&converted);
+
+        let mut music_metadata = music_metadata.clone();
+        music_metadata.version = MusicMetadataVersion::V1;
+        let v1 = applied(&music_metadata, &details);
+        assert!(!v1.other_properties.contains_key(RELEASE_KEY));
+        assert!(file_property(&v1, 1, SONG_KEY).is_none());
+        assert!(same_details(&v1, &details_v1()));
+    }
+
+    fn error_message(details: &MetadataDetails) -> String {
+        MusicMetadata::from_details(details).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn validate() {
+        assert!(music_metadata(&details_v1()).validate().is_ok());
+
+        // the album title is optional in version 1 only
+        let mut details = details_v1();
+        details.other_properties.remove(ALBUM_TITLE_KEY);
+        assert!(music_metadata(&details).release.release_title.is_none());
+        let mut music_metadata = self::music_metadata(&details);
+        music_metadata.version = MusicMetadataVersion::V2;
+        assert!(music_metadata.validate().is_err());
+
+        // no song
+        let mut music_metadata = self::music_metadata(&details_v2(2));
+        music_metadata.files[1].song = None;
+        assert!(music_metadata.validate().is_err());
+
+        let mut music_metadata = self::music_metadata(&details_v2(2));
+        music_metadata.files[1].song.as_mut().unwrap().track_number = 0;
+        assert!(music_metadata.validate().is_err());
+
+        let mut music_metadata = self::music_metadata(&details_v2(2));
+        music_metadata.files[1].song.as_mut().unwrap().artists.clear();
+        assert!(music_metadata.validate().is_err());
+    }
+
+    #[test]
+    fn invalid_details() {
+        let mut details = details_v2(2);
+        details.other_properties.insert(String::from(MUSIC_METADATA_VERSION_KEY), TransactionMetadatum::Int(4));
+        assert!(MusicMetadata::from_details(&details).is_err());
+        details.other_properties.insert(String::from(MUSIC_METADATA_VERSION_KEY), TransactionMetadatum::Text(String::from("2")));
+        assert!(MusicMetadata::from_details(&details).is_err());
+
+        let mut details = details_v2(2);
+        details.other_properties.remove(RELEASE_KEY);
+        assert!(error_message(&details).contains(RELEASE_KEY));
+
+        // from_details validates too
+        let mut details = details_v1();
+        let file = &mut details.files.as_mut().unwrap()[1];
+        file.other_properties.insert(String::from("track_number"), TransactionMetadatum::Int(0));
+        assert!(error_message(&details).contains("files/1/track_number"));
+
+        // a song field of the wrong type
+        let mut details = details_v2(3);
+        let song = details.files.as_mut().unwrap()[1].other_properties.get_mut(SONG_KEY).unwrap();
+        if let TransactionMetadatum::Map(entries) = song {
+            for (key, value) in entries.iter_mut() {
+                if *key == TransactionMetadatum::Text(String::from("track_number")) {
+                    *value = TransactionMetadatum::Text(String::from("1"));
+                }
+            }
+        }
+        assert!(error_message(&details).contains("files/1/song/track_number"));
+    }
+}
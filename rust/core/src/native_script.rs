@@ -0,0 +1,385 @@
+// Native scripts of the ledger, the usual minting policies of CIP-25 tokens:
+//
+// native_script = [ 0, addr_keyhash        ; sig
+//                 / 1, [ * native_script ] ; all
+//                 / 2, [ * native_script ] ; any
+//                 / 3, n: uint, [ * native_script ] ; at least n of k
+//                 / 4, uint                ; invalid before, i.e. after this slot
+//                 / 5, uint                ; invalid hereafter, i.e. before this slot
+//                 ]
+//
+// The policy id is the blake2b-224 of the script CBOR prefixed with the 0 native script tag.
+// A time-locked policy ends with a "before" condition, after which nothing can be minted.
+
+use super::*;
+
+pub const KEY_HASH_SIZE: usize = 28;
+
+pub const POLICY_ID_SIZE: usize = 28;
+
+// prefix of the script CBOR when hashing it, telling native scripts from plutus ones
+const NATIVE_SCRIPT_TAG: u8 = 0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NativeScript {
+    // signed by the key of this hash
+    Sig(Vec<u8>),
+    All(Vec<NativeScript>),
+    Any(Vec<NativeScript>),
+    NOfK {
+        n: u64,
+        native_scripts: Vec<NativeScript>,
+    },
+    // valid from this slot on (invalid before)
+    After(u64),
+    // valid until this slot, excluded (invalid hereafter)
+    Before(u64),
+}
+
+impl NativeScript {
+    pub fn new_sig(key_hash: Vec<u8>) -> Result<Self, DeserializeError> {
+        check_key_hash(&key_hash).map_err(|e| e.annotate("NativeScript"))?;
+        Ok(Self::Sig(key_hash))
+    }
+
+    pub fn new_all(native_scripts: Vec<NativeScript>) -> Self {
+        Self::All(native_scripts)
+    }
+
+    pub fn new_any(native_scripts: Vec<NativeScript>) -> Self {
+        Self::Any(native_scripts)
+    }
+
+    pub fn new_n_of_k(n: u64, native_scripts: Vec<NativeScript>) -> Self {
+        Self::NOfK {
+            n,
+            native_scripts,
+        }
+    }
+
+    pub fn new_after(slot: u64) -> Self {
+        Self::After(slot)
+    }
+
+    pub fn new_before(slot: u64) -> Self {
+        Self::Before(slot)
+    }
+
+    pub fn policy_id(&self) -> PolicyIdV2 {
+        let mut serializer = Serializer::new_vec();
+        // writing to a Vec can't fail
+        self.serialize(&mut serializer).unwrap();
+        blake2b_simd::Params::new()
+            .hash_length(POLICY_ID_SIZE)
+            .to_state()
+            .update(&[NATIVE_SCRIPT_TAG])
+            .update(&serializer.finalize())
+            .finalize()
+            .as_bytes()
+            .to_vec()
+    }
+
+    // whether the time conditions allow a transaction at this slot, assuming all the keys sign
+    pub fn is_valid_at(&self, slot: u64) -> bool {
+        match self {
+            NativeScript::Sig(_) => true,
+            NativeScript::All(native_scripts) => native_scripts.iter().all(|native_script| native_script.is_valid_at(slot)),
+            NativeScript::Any(native_scripts) => native_scripts.iter().any(|native_script| native_script.is_valid_at(slot)),
+            NativeScript::NOfK { n, native_scripts } => native_scripts.iter().filter(|native_script| native_script.is_valid_at(slot)).count() as u64 >= *n,
+            NativeScript::After(after) => slot >= *after,
+            NativeScript::Before(before) => slot < *before,
+        }
+    }
+
+    // The first slot from which the script can't be satisfied anymore, None when it can still
+    // be at some point whatever the slot. Some(0) for a script that never can.
+    pub fn locked_from(&self) -> Option<u64> {
+        // the validity only changes at the slots written in the script
+        let mut starts = vec![0];
+        self.collect_slots(&mut starts);
+        starts.sort_unstable();
+        starts.dedup();
+        let mut locked_from = None;
+        for start in starts.into_iter().rev() {
+            if self.is_valid_at(start) {
+                return locked_from;
+            }
+            locked_from = Some(start);
+        }
+        locked_from
+    }
+
+    // whether nothing can be minted with this policy in any slot after this one
+    pub fn is_locked_after(&self, slot: u64) -> bool {
+        match self.locked_from() {
+            Some(locked_from) => locked_from <= slot.saturating_add(1),
+            None => false,
+        }
+    }
+
+    fn collect_slots(&self, slots: &mut Vec<u64>) {
+        match self {
+            NativeScript::Sig(_) => (),
+            NativeScript::All(native_scripts) | NativeScript::Any(native_scripts) | NativeScript::NOfK { native_scripts, .. } => {
+                for native_script in native_scripts {
+                    native_script.collect_slots(slots);
+                }
+            },
+            NativeScript::After(slot) | NativeScript::Before(slot) => slots.push(*slot),
+        }
+    }
+}
+
+fn check_key_hash(key_hash: &[u8]) -> Result<(), DeserializeError> {
+    if key_hash.len() != KEY_HASH_SIZE {
+        return Err(DeserializeFailure::RangeCheck{ found: key_hash.len(), min: Some(KEY_HASH_SIZE as isize), max: Some(KEY_HASH_SIZE as isize) }.into());
+    }
+    Ok(())
+}
+
+// a policy of the metadata with the given script it is derived from, if any
+#[derive(Clone, Debug)]
+pub struct PolicyCheck {
+    // text for version 1, hex for version 2
+    pub policy_id: String,
+    // None when the policy id isn't the one of any of the given scripts
+    pub native_script: Option<NativeScript>,
+    // whether nothing can be minted with the policy after the checked slot, false without a script
+    pub locked: bool,
+}
+
+impl Metadata {
+    // Every policy of the 721 metadata, in order, with the script among native_scripts it is
+    // derived from and whether it is locked after slot. Version 1 policy ids that aren't hex
+    // are reported without a script.
+    pub fn check_policies(&self, native_scripts: &[NativeScript], slot: u64) -> Result<Vec<PolicyCheck>, DeserializeError> {
+        let policy_ids: Vec<(String, Option<PolicyIdV2>)> = match &self.key_721 {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => label_metadata_v1
+                .data
+                .keys()
+                .map(|policy_id| (policy_id.get().clone(), hex::decode(policy_id.get()).ok()))
+                .collect(),
+            label_metadata => match label_metadata.interpret_as_v2()? {
+                Some(label_metadata_v2) => label_metadata_v2
+                    .data
+                    .keys()
+                    .map(|policy_id| (hex::encode(policy_id), Some(policy_id.clone())))
+                    .collect(),
+                None => Vec::new(),
+            },
+        };
+        let derived: Vec<(PolicyIdV2, &NativeScript)> = native_scripts
+            .iter()
+            .map(|native_script| (native_script.policy_id(), native_script))
+            .collect();
+        Ok(policy_ids
+            .into_iter()
+            .map(|(policy_id, bytes)| {
+                let native_script = bytes.and_then(|bytes| derived.iter().find(|(derived_id, _)| *derived_id == bytes).map(|(_, native_script)| (*native_script).clone()));
+                let locked = native_script.as_ref().is_some_and(|native_script| native_script.is_locked_after(slot));
+                PolicyCheck {
+                    policy_id,
+                    native_script,
+                    locked,
+                }
+            })
+            .collect())
+    }
+}
+
+impl cbor_event::se::Serialize for NativeScript {
+    fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
+        match self {
+            NativeScript::Sig(key_hash) => {
+                serializer.write_array(cbor_event::Len::Len(2))?;
+                serializer.write_unsigned_integer(0u64)?;
+                serializer.write_bytes(key_hash)?;
+            },
+            NativeScript::All(native_scripts) | NativeScript::Any(native_scripts) => {
+                serializer.write_array(cbor_event::Len::Len(2))?;
+                serializer.write_unsigned_integer(if let NativeScript::All(_) = self { 1u64 } else { 2u64 })?;
+                serialize_native_scripts(native_scripts, serializer)?;
+            },
+            NativeScript::NOfK { n, native_scripts } => {
+                serializer.write_array(cbor_event::Len::Len(3))?;
+                serializer.write_unsigned_integer(3u64)?;
+                serializer.write_unsigned_integer(*n)?;
+                serialize_native_scripts(native_scripts, serializer)?;
+            },
+            NativeScript::After(slot) => {
+                serializer.write_array(cbor_event::Len::Len(2))?;
+                serializer.write_unsigned_integer(4u64)?;
+                serializer.write_unsigned_integer(*slot)?;
+            },
+            NativeScript::Before(slot) => {
+                serializer.write_array(cbor_event::Len::Len(2))?;
+                serializer.write_unsigned_integer(5u64)?;
+                serializer.write_unsigned_integer(*slot)?;
+            },
+        }
+        Ok(serializer)
+    }
+}
+
+fn serialize_native_scripts<'se, W: Write>(native_scripts: &[NativeScript], serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
+    serializer.write_array(cbor_event::Len::Len(native_scripts.len() as u64))?;
+    for native_script in native_scripts {
+        native_script.serialize(serializer)?;
+    }
+    Ok(serializer)
+}
+
+impl Deserialize for NativeScript {
+    fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
+        (|| -> Result<_, DeserializeError> {
+            let len = raw.array()?;
+            let mut read_len = CBORReadLen::new(len);
+            read_len.read_elems(2)?;
+            let native_script = match raw.unsigned_integer()? {
+                0 => {
+                    let key_hash = (|| -> Result<_, DeserializeError> {
+                        let key_hash = raw.bytes()?;
+                        check_key_hash(&key_hash)?;
+                        Ok(key_hash)
+                    })().map_err(|e| e.annotate("key_hash").annotate_path(PathSegment::Index(1)))?;
+                    NativeScript::Sig(key_hash)
+                },
+                1 => NativeScript::All(deserialize_native_scripts(raw).map_err(|e| e.annotate_path(PathSegment::Index(1)))?),
+                2 => NativeScript::Any(deserialize_native_scripts(raw).map_err(|e| e.annotate_path(PathSegment::Index(1)))?),
+                3 => {
+                    read_len.read_elems(1)?;
+                    let n = (|| -> Result<_, DeserializeError> {
+                        Ok(raw.unsigned_integer()?)
+                    })().map_err(|e| e.annotate("n").annotate_path(PathSegment::Index(1)))?;
+                    let native_scripts = deserialize_native_scripts(raw).map_err(|e| e.annotate_path(PathSegment::Index(2)))?;
+                    NativeScript::NOfK {
+                        n,
+                        native_scripts,
+                    }
+                },
+                4 => NativeScript::After((|| -> Result<_, DeserializeError> {
+                    Ok(raw.unsigned_integer()?)
+                })().map_err(|e| e.annotate("after").annotate_path(PathSegment::Index(1)))?),
+                5 => NativeScript::Before((|| -> Result<_, DeserializeError> {
+                    Ok(raw.unsigned_integer()?)
+                })().map_err(|e| e.annotate("before").annotate_path(PathSegment::Index(1)))?),
+                _ => return Err(DeserializeFailure::NoVariantMatched.into()),
+            };
+            match len {
+                cbor_event::Len::Len(_) => (),
+                cbor_event::Len::Indefinite => match raw.special()? {
+                    CBORSpecial::Break => (),
+                    _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                },
+            }
+            read_len.finish()?;
+            Ok(native_script)
+        })().map_err(|e| e.annotate("NativeScript"))
+    }
+}
+
+fn deserialize_native_scripts<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Vec<NativeScript>, DeserializeError> {
+    let mut native_scripts = Vec::new();
+    let len = raw.array()?;
+    while match len { cbor_event::Len::Len(n) => native_scripts.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+        if raw.cbor_type()? == CBORType::Special {
+            match raw.special()? {
+                CBORSpecial::Break => break,
+                _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+            }
+        }
+        let index = native_scripts.len();
+        native_scripts.push(NativeScript::deserialize(raw).map_err(|e| e.annotate_path(PathSegment::Index(index)))?);
+    }
+    Ok(native_scripts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_HASH: &str = "5805823e303fb28231a736a3eb4420261bb42019dc3605dd83cccd38";
+
+    fn sig() -> NativeScript {
+        NativeScript::new_sig(hex::decode(KEY_HASH).unwrap()).unwrap()
+    }
+
+    // script CBOR and blake2b-224 of 00 || CBOR, hashed with an implementation other than
+    // blake2b_simd (Python's hashlib.blake2b(digest_size=28))
+    #[test]
+    fn policy_id_vectors() {
+        let vectors = [
+            (sig(), format!("8200581c{}", KEY_HASH), "5690b2d89fffde1db536294b245b99cbe6987f9c4957b98d3a9f8c2a"),
+            (NativeScript::new_all(vec![sig(), NativeScript::new_before(12345678)]), format!("820182 8200581c{} 82051a00bc614e", KEY_HASH), "1e6d2ff5673f39d355f593556d6eee1c87607cb18d80e1d24971cb5a"),
+            (NativeScript::new_all(Vec::new()), String::from("820180"), "d441227553a0f1a965fee7d60a0f724b368dd1bddbc208730fccebcf"),
+        ];
+        for (native_script, cbor, policy_id) in vectors.iter() {
+            let cbor = cbor.replace(' ', "");
+            assert_eq!(hex::encode(native_script.to_bytes().unwrap()), cbor);
+            assert_eq!(NativeScript::from_bytes(hex::decode(&cbor).unwrap()).unwrap(), *native_script);
+            assert_eq!(hex::encode(native_script.policy_id()), *policy_id);
+        }
+    }
+
+    #[test]
+    fn locked_from() {
+        let before = NativeScript::new_before;
+        let after = NativeScript::new_after;
+        let cases = [
+            (sig(), None),
+            (before(100), Some(100)),
+            (before(0), Some(0)),
+            (after(100), None),
+            (NativeScript::new_all(Vec::new()), None),
+            (NativeScript::new_any(Vec::new()), Some(0)),
+            (NativeScript::new_all(vec![sig(), before(100)]), Some(100)),
+            (NativeScript::new_all(vec![after(50), before(100)]), Some(100)),
+            // never valid
+            (NativeScript::new_all(vec![after(200), before(100)]), Some(0)),
+            (NativeScript::new_any(vec![before(100), before(300)]), Some(300)),
+            (NativeScript::new_any(vec![before(100), after(300)]), None),
+            (NativeScript::new_any(vec![before(100), sig()]), None),
+            (NativeScript::new_n_of_k(2, vec![before(100), before(200), before(300)]), Some(200)),
+            (NativeScript::new_n_of_k(2, vec![before(100), after(200)]), Some(0)),
+            (NativeScript::new_n_of_k(1, vec![before(100), after(200)]), None),
+            (NativeScript::new_n_of_k(0, Vec::new()), None),
+            (NativeScript::new_all(vec![NativeScript::new_any(vec![before(100), sig()]), before(500)]), Some(500)),
+            (NativeScript::new_all(vec![NativeScript::new_n_of_k(1, vec![before(100), before(700)]), before(500)]), Some(500)),
+        ];
+        for (native_script, locked_from) in cases.iter() {
+            assert_eq!(native_script.locked_from(), *locked_from, "{:?}", native_script);
+        }
+    }
+
+    #[test]
+    fn locked_after() {
+        let native_script = NativeScript::new_all(vec![sig(), NativeScript::new_before(100)]);
+        // slot 99 is the last one a transaction can mint in
+        assert!(!native_script.is_locked_after(98));
+        assert!(native_script.is_locked_after(99));
+        assert!(native_script.is_locked_after(u64::MAX));
+        assert!(!sig().is_locked_after(u64::MAX));
+    }
+
+    #[test]
+    fn check_policies() {
+        let locked = NativeScript::new_all(vec![sig(), NativeScript::new_before(100)]);
+        let unknown = vec![0; POLICY_ID_SIZE];
+        let mut data = BTreeMap::new();
+        for policy_id in [sig().policy_id(), locked.policy_id(), unknown.clone()] {
+            let mut assets = BTreeMap::new();
+            assets.insert(b"NFT1".to_vec(), MetadataDetails::new(String64::new(String::from("NFT 1")).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("ipfs://nft1")).unwrap())));
+            data.insert(policy_id, assets);
+        }
+        let metadata = Metadata::new(LabelMetadata::new_label_metadata_v2(LabelMetadataV2::new(data).unwrap()));
+        let checks = metadata.check_policies(&[sig(), locked.clone()], 200).unwrap();
+        let check = |policy_id: &PolicyIdV2| checks.iter().find(|check| check.policy_id == hex::encode(policy_id)).unwrap();
+        assert_eq!(checks.len(), 3);
+        assert_eq!(check(&sig().policy_id()).native_script, Some(sig()));
+        assert!(!check(&sig().policy_id()).locked);
+        assert_eq!(check(&locked.policy_id()).native_script, Some(locked.clone()));
+        assert!(check(&locked.policy_id()).locked);
+        assert_eq!(check(&unknown).native_script, None);
+        assert!(!check(&unknown).locked);
+    }
+}
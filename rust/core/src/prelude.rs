@@ -6,6 +6,7 @@ use crate::serialization::CBORReadLen;
 pub enum Key {
     Str(String),
     Uint(u64),
+    Bytes(Vec<u8>),
 }
 
 impl std::fmt::Display for Key {
@@ -13,6 +14,7 @@ impl std::fmt::Display for Key {
         match self {
             Key::Str(x) => write!(f, "\"{}\"", x),
             Key::Uint(x) => write!(f, "{}", x),
+            Key::Bytes(x) => write!(f, "0x{}", hex::encode(x)),
         }
     }
 }
@@ -29,6 +31,10 @@ pub enum DeserializeFailure {
         found: Key,
         expected: Key,
     },
+    // node APIs give assets as the hex policy id followed by the hex asset name
+    InvalidAssetId(String),
+    // CIP-27 rates are decimal texts between 0 and 1
+    InvalidRoyaltyRate(String),
     MandatoryFieldMissing(Key),
     NoVariantMatched,
     RangeCheck{
@@ -42,38 +48,118 @@ pub enum DeserializeFailure {
     },
     UnknownKey(Key),
     UnexpectedKeyType(cbor_event::Type),
+    UnsupportedVersion(Key),
+}
+
+// Where in the decoded data an error happened, e.g. [Field("721"), PolicyId(..), AssetName(..), Field("files"), Index(0), Field("src")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    // map key as written on-chain
+    Field(String),
+    Index(usize),
+    // text for version 1, hex for version 2
+    PolicyId(String),
+    // text for version 1, hex for version 2
+    AssetName(String),
+}
+
+impl std::fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathSegment::Field(x) => write!(f, "{}", x),
+            PathSegment::Index(x) => write!(f, "{}", x),
+            PathSegment::PolicyId(x) => write!(f, "{}", x),
+            PathSegment::AssetName(x) => write!(f, "{}", x),
+        }
+    }
 }
 
-// we might want to add more info like which field,
 #[derive(Debug)]
 pub struct DeserializeError {
-    location: Option<String>,
+    // names of the types / fields being decoded
+    location: Option<Box<str>>,
+    // the data being decoded, outermost first
+    path: Box<[PathSegment]>,
+    // position in the input where decoding stopped
+    offset: Option<u64>,
     failure: DeserializeFailure,
 }
 
 impl DeserializeError {
     pub fn new<T: Into<String>>(location: T, failure: DeserializeFailure) -> Self {
         Self {
-            location: Some(location.into()),
+            location: Some(location.into().into_boxed_str()),
+            path: Box::new([]),
+            offset: None,
             failure,
         }
     }
 
-    pub fn annotate<T: Into<String>>(self, location: T) -> Self {
-        match self.location {
-            Some(loc) => Self::new(format!("{}.{}", location.into(), loc), self.failure),
-            None => Self::new(location, self.failure),
-        }
+    pub fn annotate<T: Into<String>>(mut self, location: T) -> Self {
+        self.location = match self.location {
+            Some(loc) => Some(format!("{}.{}", location.into(), loc).into_boxed_str()),
+            None => Some(location.into().into_boxed_str()),
+        };
+        self
+    }
+
+    pub fn annotate_path(mut self, segment: PathSegment) -> Self {
+        let mut path = self.path.into_vec();
+        path.insert(0, segment);
+        self.path = path.into_boxed_slice();
+        self
+    }
+
+    // keeps the offset if one was already set closer to the error
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.offset.get_or_insert(offset);
+        self
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    pub fn failure(&self) -> &DeserializeFailure {
+        &self.failure
+    }
+
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
     }
 }
 
 impl std::fmt::Display for DeserializeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.location {
-            Some(loc) => write!(f, "Deserialization failed in {} because: ", loc),
-            None => write!(f, "Deserialization: "),
+            Some(loc) => write!(f, "Deserialization failed in {}", loc),
+            None => write!(f, "Deserialization failed"),
         }?;
-        match &self.failure {
+        if !self.path.is_empty() {
+            write!(f, " at {}", self.path.iter().map(ToString::to_string).collect::<Vec<_>>().join("/"))?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " (byte {})", offset)?;
+        }
+        write!(f, " because: ")?;
+        self.failure.fmt(f)
+    }
+}
+
+// the failure is already part of the message so the source is the underlying CBOR error, if any
+impl std::error::Error for DeserializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.failure.source()
+    }
+}
+
+impl std::fmt::Display for DeserializeFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
             DeserializeFailure::BreakInDefiniteLen => write!(f, "Encountered CBOR Break while reading definite length sequence"),
             DeserializeFailure::CBOR(e) => e.fmt(f),
             DeserializeFailure::DefiniteLenMismatch(found, expected) => {
@@ -87,6 +173,8 @@ impl std::fmt::Display for DeserializeError {
             DeserializeFailure::EndingBreakMissing => write!(f, "Missing ending CBOR Break"),
             DeserializeFailure::ExpectedNull => write!(f, "Expected null, found other type"),
             DeserializeFailure::FixedValueMismatch{ found, expected } => write!(f, "Expected fixed value {} found {}", expected, found),
+            DeserializeFailure::InvalidAssetId(asset_id) => write!(f, "Invalid asset id \"{}\", expected the hex policy id followed by the hex asset name", asset_id),
+            DeserializeFailure::InvalidRoyaltyRate(rate) => write!(f, "Invalid royalty rate \"{}\", expected a decimal between 0 and 1", rate),
             DeserializeFailure::MandatoryFieldMissing(key) => write!(f, "Mandatory field {} not found", key),
             DeserializeFailure::NoVariantMatched => write!(f, "No variant matched"),
             DeserializeFailure::RangeCheck{ found, min, max } => match (min, max) {
@@ -98,6 +186,16 @@ impl std::fmt::Display for DeserializeError {
             DeserializeFailure::TagMismatch{ found, expected } => write!(f, "Expected tag {}, found {}", expected, found),
             DeserializeFailure::UnknownKey(key) => write!(f, "Found unexpected key {}", key),
             DeserializeFailure::UnexpectedKeyType(ty) => write!(f, "Found unexpected key of CBOR type {:?}", ty),
+            DeserializeFailure::UnsupportedVersion(version) => write!(f, "Unsupported CIP-25 version {}", version),
+        }
+    }
+}
+
+impl std::error::Error for DeserializeFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeserializeFailure::CBOR(e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -106,6 +204,8 @@ impl From<DeserializeFailure> for DeserializeError {
     fn from(failure: DeserializeFailure) -> DeserializeError {
         DeserializeError {
             location: None,
+            path: Box::new([]),
+            offset: None,
             failure,
         }
     }
@@ -113,13 +213,53 @@ impl From<DeserializeFailure> for DeserializeError {
 
 impl From<cbor_event::Error> for DeserializeError {
     fn from(err: cbor_event::Error) -> DeserializeError {
-        DeserializeError {
-            location: None,
-            failure: DeserializeFailure::CBOR(err),
-        }
+        DeserializeFailure::CBOR(err).into()
+    }
+}
+
+#[derive(Debug)]
+pub struct SerializeError(cbor_event::Error);
+
+impl std::fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Serialization failed because: {}", self.0)
+    }
+}
+
+impl std::error::Error for SerializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+impl From<cbor_event::Error> for SerializeError {
+    fn from(err: cbor_event::Error) -> SerializeError {
+        SerializeError(err)
+    }
+}
+
+// for values that are re-encoded in order to be decoded as another type
+impl From<SerializeError> for DeserializeError {
+    fn from(err: SerializeError) -> DeserializeError {
+        DeserializeFailure::CBOR(err.0).into()
     }
 }
 
+// What to do when a policy id or an asset name appears twice in the same map.
+// Duplicates are invalid CBOR metadata but they can be found on-chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    #[default]
+    Error,
+    KeepFirst,
+    KeepLast,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DeserializeOptions {
+    pub duplicate_keys: DuplicateKeys,
+}
+
 // same as cbor_event::de::Deserialize but with our DeserializeError
 pub trait Deserialize {
     fn deserialize<R: BufRead + Seek>(
@@ -133,6 +273,19 @@ impl<T: cbor_event::de::Deserialize> Deserialize for T {
     }
 }
 
+// for types whose decoding can be configured, Deserialize uses the default options
+pub trait DeserializeWithOptions {
+    fn deserialize_with_options<R: BufRead + Seek>(
+        raw: &mut Deserializer<R>,
+        options: &DeserializeOptions,
+    ) -> Result<Self, DeserializeError> where Self: Sized;
+
+    fn from_bytes_with_options(data: Vec<u8>, options: &DeserializeOptions) -> Result<Self, DeserializeError> where Self: Sized {
+        let mut raw = Deserializer::from(std::io::Cursor::new(data));
+        Self::deserialize_with_options(&mut raw, options).map_err(|e| e.with_offset(raw.as_mut_ref().position()))
+    }
+}
+
 pub trait FromBytes {
     fn from_bytes(data: Vec<u8>) -> Result<Self, DeserializeError> where Self: Sized;
 }
@@ -140,7 +293,7 @@ pub trait FromBytes {
 impl<T: Deserialize + Sized> FromBytes for T {
     fn from_bytes(data: Vec<u8>) -> Result<Self, DeserializeError> {
         let mut raw = Deserializer::from(std::io::Cursor::new(data));
-        Self::deserialize(&mut raw)
+        Self::deserialize(&mut raw).map_err(|e| e.with_offset(raw.as_mut_ref().position()))
     }
 }
 
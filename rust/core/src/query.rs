@@ -0,0 +1,188 @@
+// Looking up the metadata of an asset by its on-chain policy id and asset name, whichever
+// standard it was published with (CIP-25 version 1 / 2 or a CIP-68 reference datum).
+
+use super::*;
+use crate::cip67;
+use crate::cip68::Cip68Metadata;
+
+pub trait AssetMetadataQuery {
+    // policy ids and asset names as raw bytes, in the order of the metadata
+    fn assets(&self) -> Result<Vec<(PolicyIdV2, AssetNameV2)>, DeserializeError>;
+
+    // Ok(None) when the asset isn't described
+    fn asset_details(&self, policy_id: &[u8], asset_name: &[u8]) -> Result<Option<MetadataDetails>, DeserializeError>;
+}
+
+impl AssetMetadataQuery for Metadata {
+    fn assets(&self) -> Result<Vec<(PolicyIdV2, AssetNameV2)>, DeserializeError> {
+        self.key_721.assets()
+    }
+
+    fn asset_details(&self, policy_id: &[u8], asset_name: &[u8]) -> Result<Option<MetadataDetails>, DeserializeError> {
+        self.key_721.asset_details(policy_id, asset_name)
+    }
+}
+
+// Version 1 policy ids are hex text and asset names UTF-8 text, entries that don't match
+// this can't be looked up. Unknown versions are read with interpret_as_v2.
+impl AssetMetadataQuery for LabelMetadata {
+    fn assets(&self) -> Result<Vec<(PolicyIdV2, AssetNameV2)>, DeserializeError> {
+        match self {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => Ok(label_metadata_v1
+                .data
+                .iter()
+                .filter_map(|(policy_id, assets)| hex::decode(policy_id.get()).ok().map(|policy_id| (policy_id, assets)))
+                .flat_map(|(policy_id, assets)| assets.keys().map(move |asset_name| (policy_id.clone(), asset_name.get().as_bytes().to_vec())))
+                .collect()),
+            _ => Ok(match self.interpret_as_v2()? {
+                Some(label_metadata_v2) => label_metadata_v2
+                    .data
+                    .iter()
+                    .flat_map(|(policy_id, assets)| assets.keys().map(move |asset_name| (policy_id.clone(), asset_name.clone())))
+                    .collect(),
+                None => Vec::new(),
+            }),
+        }
+    }
+
+    fn asset_details(&self, policy_id: &[u8], asset_name: &[u8]) -> Result<Option<MetadataDetails>, DeserializeError> {
+        match self {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => {
+                let asset_name = match std::str::from_utf8(asset_name) {
+                    Ok(asset_name) => asset_name,
+                    Err(_) => return Ok(None),
+                };
+                Ok(label_metadata_v1
+                    .data
+                    .iter()
+                    .find(|(policy_id_v1, _)| hex::decode(policy_id_v1.get()).ok().as_deref() == Some(policy_id))
+                    .and_then(|(_, assets)| assets.iter().find(|(asset_name_v1, _)| asset_name_v1.get() == asset_name))
+                    .map(|(_, details)| details.clone()))
+            },
+            _ => Ok(self
+                .interpret_as_v2()?
+                .and_then(|label_metadata_v2| label_metadata_v2.data.get(policy_id).and_then(|assets| assets.get(asset_name)).cloned())),
+        }
+    }
+}
+
+// Asset names are those of the reference tokens. A user token (CIP-67 label 222, 333 or 444)
+// can be looked up too, it gets the details of its reference token.
+impl AssetMetadataQuery for Cip68Metadata {
+    fn assets(&self) -> Result<Vec<(PolicyIdV2, AssetNameV2)>, DeserializeError> {
+        Ok(self
+            .data
+            .iter()
+            .flat_map(|(policy_id, assets)| assets.keys().map(move |asset_name| (policy_id.clone(), asset_name.clone())))
+            .collect())
+    }
+
+    fn asset_details(&self, policy_id: &[u8], asset_name: &[u8]) -> Result<Option<MetadataDetails>, DeserializeError> {
+        let reference = cip67::reference_asset_name(asset_name);
+        let asset_name = reference.as_deref().unwrap_or(asset_name);
+        match self.data.get(policy_id).and_then(|assets| assets.get(asset_name)) {
+            Some(datum) => Ok(Some(datum.details()?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cip67::{label_prefix, NFT_LABEL, REFERENCE_TOKEN_LABEL};
+    use crate::cip68::{Cip68Datum, PlutusData};
+
+    const POLICY_ID: [u8; 28] = [0xab; 28];
+
+    fn details(name: &str) -> MetadataDetails {
+        MetadataDetails::new(String64::new(String::from(name)).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("ipfs://image")).unwrap()))
+    }
+
+    fn name(details: Option<MetadataDetails>) -> Option<String> {
+        details.map(|details| String::from(details.name.get()))
+    }
+
+    fn labelled(label: u16, name: &[u8]) -> Vec<u8> {
+        [label_prefix(label).as_slice(), name].concat()
+    }
+
+    #[test]
+    fn version_1() {
+        // the hex case of version 1 policy ids doesn't matter for the lookup
+        let policy_id_text = hex::encode_upper(POLICY_ID);
+        let mut assets = BTreeMap::new();
+        assets.insert(String64::new(String::from("NFT")).unwrap(), details("NFT"));
+        let mut data = BTreeMap::new();
+        data.insert(String64::new(policy_id_text).unwrap(), assets);
+        data.insert(String64::new(String::from("not hex")).unwrap(), BTreeMap::new());
+        let metadata = Metadata::new(LabelMetadata::new_label_metadata_v1(LabelMetadataV1::new(data)));
+        assert_eq!(metadata.assets().unwrap(), vec![(POLICY_ID.to_vec(), b"NFT".to_vec())]);
+        assert_eq!(name(metadata.asset_details(&POLICY_ID, b"NFT").unwrap()).as_deref(), Some("NFT"));
+        assert!(metadata.asset_details(&POLICY_ID, b"nft").unwrap().is_none());
+        assert!(metadata.asset_details(&POLICY_ID, &[0xff]).unwrap().is_none());
+        assert!(metadata.asset_details(&[0xcd; 28], b"NFT").unwrap().is_none());
+    }
+
+    #[test]
+    fn version_2() {
+        let mut assets = BTreeMap::new();
+        assets.insert(vec![0xff], details("not UTF-8"));
+        let mut data = BTreeMap::new();
+        data.insert(POLICY_ID.to_vec(), assets);
+        let metadata = Metadata::new(LabelMetadata::new_label_metadata_v2(LabelMetadataV2::new(data).unwrap()));
+        assert_eq!(metadata.assets().unwrap(), vec![(POLICY_ID.to_vec(), vec![0xff])]);
+        assert_eq!(name(metadata.asset_details(&POLICY_ID, &[0xff]).unwrap()).as_deref(), Some("not UTF-8"));
+        assert!(metadata.asset_details(&POLICY_ID, &[0xfe]).unwrap().is_none());
+    }
+
+    #[test]
+    fn unknown_version() {
+        let mut assets = BTreeMap::new();
+        assets.insert(b"NFT".to_vec(), details("NFT"));
+        let mut data = BTreeMap::new();
+        data.insert(POLICY_ID.to_vec(), assets);
+        let raw = TransactionMetadatum::from_bytes(LabelMetadataV2::new(data).unwrap().to_bytes().unwrap()).unwrap();
+        let raw = match raw {
+            TransactionMetadatum::Map(entries) => TransactionMetadatum::Map(entries
+                .into_iter()
+                .map(|(key, value)| match key {
+                    TransactionMetadatum::Text(key) if key == VERSION_KEY => (TransactionMetadatum::Text(key), TransactionMetadatum::new_int(3)),
+                    _ => (key, value),
+                })
+                .collect()),
+            other => panic!("{:?}", other),
+        };
+        let label_metadata = LabelMetadata::new_unknown(TransactionMetadatum::new_int(3), raw);
+        assert_eq!(label_metadata.assets().unwrap(), vec![(POLICY_ID.to_vec(), b"NFT".to_vec())]);
+        assert_eq!(name(label_metadata.asset_details(&POLICY_ID, b"NFT").unwrap()).as_deref(), Some("NFT"));
+    }
+
+    #[test]
+    fn cip68() {
+        let reference = labelled(REFERENCE_TOKEN_LABEL, b"NFT");
+        let datum = Cip68Datum::from_details(&details("NFT"), 1, PlutusData::new_integer(0));
+        let mut assets = BTreeMap::new();
+        assets.insert(reference.clone(), datum);
+        let mut data = BTreeMap::new();
+        data.insert(POLICY_ID.to_vec(), assets);
+        let metadata = Cip68Metadata::new(data);
+        assert_eq!(metadata.assets().unwrap(), vec![(POLICY_ID.to_vec(), reference.clone())]);
+        // the user token gets the details of its reference token
+        assert_eq!(name(metadata.asset_details(&POLICY_ID, &reference).unwrap()).as_deref(), Some("NFT"));
+        assert_eq!(name(metadata.asset_details(&POLICY_ID, &labelled(NFT_LABEL, b"NFT")).unwrap()).as_deref(), Some("NFT"));
+        assert!(metadata.asset_details(&POLICY_ID, &labelled(NFT_LABEL, b"other")).unwrap().is_none());
+        assert!(metadata.asset_details(&POLICY_ID, b"NFT").unwrap().is_none());
+    }
+
+    #[test]
+    fn cip68_invalid_datum() {
+        let reference = labelled(REFERENCE_TOKEN_LABEL, b"NFT");
+        let mut assets = BTreeMap::new();
+        assets.insert(reference.clone(), Cip68Datum::new(vec![(PlutusData::new_bytes(b"name".to_vec()), PlutusData::new_bytes(b"NFT".to_vec()))], 1));
+        let mut data = BTreeMap::new();
+        data.insert(POLICY_ID.to_vec(), assets);
+        let err = Cip68Metadata::new(data).asset_details(&POLICY_ID, &reference).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::MandatoryFieldMissing(_)), "{}", err);
+    }
+}
@@ -0,0 +1,315 @@
+// CIP-27 royalties, minted once per policy as a token with an empty asset name and this
+// metadata under label 777:
+//
+// royalty_metadata = { rate: string64, addr: string64 / [* string64] }
+//
+// The first version of the CIP used "pct" instead of "rate", both are read and the same one
+// is written back. The serde/schemars impls are in json.rs next to the other JSON shapes.
+
+use super::*;
+
+pub const ROYALTY_LABEL: u64 = 777;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RateKey {
+    #[default]
+    Rate,
+    Pct,
+}
+
+impl RateKey {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateKey::Rate => "rate",
+            RateKey::Pct => "pct",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RoyaltyMetadata {
+    // decimal text between 0 and 1, e.g. "0.05" for 5%
+    pub rate: String64,
+    // bech32 payment address, split in chunks when longer than 64 bytes
+    pub addr: String64OrArrString64,
+    pub rate_key: RateKey,
+}
+
+impl RoyaltyMetadata {
+    pub fn new(rate: String64, addr: String64OrArrString64) -> Result<Self, DeserializeError> {
+        check_rate(rate.get()).map_err(|e| e.annotate("rate").annotate("RoyaltyMetadata"))?;
+        Ok(Self {
+            rate,
+            addr,
+            rate_key: RateKey::Rate,
+        })
+    }
+
+    // the address with its chunks joined
+    pub fn address(&self) -> String {
+        match &self.addr {
+            String64OrArrString64::String64(addr) => addr.get().clone(),
+            String64OrArrString64::ArrString64(chunks) => chunks.iter().map(|chunk| chunk.get().as_str()).collect(),
+        }
+    }
+
+    pub fn rate_value(&self) -> f64 {
+        self.rate.get().parse().unwrap_or(0.0)
+    }
+}
+
+// digits with an optional fractional part, "1", "0.2", ".2" and "1.000" are fine but not
+// "1.01", "-0.1", "2e-1" or "20%"
+pub(crate) fn check_rate(rate: &str) -> Result<(), DeserializeError> {
+    let invalid = || DeserializeError::from(DeserializeFailure::InvalidRoyaltyRate(rate.to_owned()));
+    let (integer, fraction) = match rate.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (rate, ""),
+    };
+    let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if integer.is_empty() && fraction.is_empty() || !is_digits(integer) || !is_digits(fraction) {
+        return Err(invalid());
+    }
+    match integer.trim_start_matches('0') {
+        "" => Ok(()),
+        "1" if fraction.bytes().all(|byte| byte == b'0') => Ok(()),
+        _ => Err(invalid()),
+    }
+}
+
+// The labels of a transaction metadata this library knows about, either can be missing
+// (e.g. the 777 token is usually minted on its own). Other labels are skipped.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct TokenMetadata {
+    #[serde(rename = "721", default, skip_serializing_if = "Option::is_none")]
+    pub key_721: Option<LabelMetadata>,
+    #[serde(rename = "777", default, skip_serializing_if = "Option::is_none")]
+    pub key_777: Option<RoyaltyMetadata>,
+}
+
+impl TokenMetadata {
+    pub fn new(key_721: Option<LabelMetadata>, key_777: Option<RoyaltyMetadata>) -> Self {
+        Self {
+            key_721,
+            key_777,
+        }
+    }
+
+    // the 721 part alone, None without one
+    pub fn metadata(&self) -> Option<Metadata> {
+        self.key_721.clone().map(Metadata::new)
+    }
+}
+
+impl From<Metadata> for TokenMetadata {
+    fn from(metadata: Metadata) -> Self {
+        Self::new(Some(metadata.key_721), None)
+    }
+}
+
+impl cbor_event::se::Serialize for RoyaltyMetadata {
+    fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer.write_map(cbor_event::Len::Len(2))?;
+        serializer.write_text(self.rate_key.as_str())?;
+        self.rate.serialize(serializer)?;
+        serializer.write_text("addr")?;
+        self.addr.serialize(serializer)?;
+        Ok(serializer)
+    }
+}
+
+impl Deserialize for RoyaltyMetadata {
+    fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
+        (|| -> Result<_, DeserializeError> {
+            let len = raw.map()?;
+            let mut read_len = CBORReadLen::new(len);
+            read_len.read_elems(2)?;
+            let mut rate = None;
+            let mut addr = None;
+            let mut read = 0;
+            while match len { cbor_event::Len::Len(n) => read < n as usize, cbor_event::Len::Indefinite => true, } {
+                match raw.cbor_type()? {
+                    CBORType::Text => match raw.text()?.as_str() {
+                        key @ ("rate" | "pct") => {
+                            if rate.is_some() {
+                                return Err(DeserializeFailure::DuplicateKey(Key::Str(key.to_owned())).into());
+                            }
+                            let rate_key = if key == "rate" { RateKey::Rate } else { RateKey::Pct };
+                            rate = Some((|| -> Result<_, DeserializeError> {
+                                let rate = String64::deserialize(raw)?;
+                                check_rate(rate.get())?;
+                                Ok((rate, rate_key))
+                            })().map_err(|e| e.annotate("rate").annotate_path(PathSegment::Field(key.to_owned())))?);
+                        },
+                        "addr" =>  {
+                            if addr.is_some() {
+                                return Err(DeserializeFailure::DuplicateKey(Key::Str("addr".into())).into());
+                            }
+                            addr = Some(String64OrArrString64::deserialize(raw).map_err(|e| e.annotate("addr").annotate_path(PathSegment::Field(String::from("addr"))))?);
+                        },
+                        unknown_key => return Err(DeserializeFailure::UnknownKey(Key::Str(unknown_key.to_owned())).into()),
+                    },
+                    CBORType::Special => match len {
+                        cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
+                        cbor_event::Len::Indefinite => match raw.special()? {
+                            CBORSpecial::Break => break,
+                            _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                        },
+                    },
+                    other_type => return Err(DeserializeFailure::UnexpectedKeyType(other_type).into()),
+                }
+                read += 1;
+            }
+            let (rate, rate_key) = match rate {
+                Some(x) => x,
+                None => return Err(DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from("rate"))).into()),
+            };
+            let addr = match addr {
+                Some(x) => x,
+                None => return Err(DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from("addr"))).into()),
+            };
+            read_len.finish()?;
+            Ok(Self {
+                rate,
+                addr,
+                rate_key,
+            })
+        })().map_err(|e| e.annotate("RoyaltyMetadata"))
+    }
+}
+
+impl cbor_event::se::Serialize for TokenMetadata {
+    fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer.write_map(cbor_event::Len::Len(match &self.key_721 { Some(_) => 1, None => 0 } + match &self.key_777 { Some(_) => 1, None => 0 }))?;
+        if let Some(field) = &self.key_721 {
+            serializer.write_unsigned_integer(721u64)?;
+            field.serialize(serializer)?;
+        }
+        if let Some(field) = &self.key_777 {
+            serializer.write_unsigned_integer(ROYALTY_LABEL)?;
+            field.serialize(serializer)?;
+        }
+        Ok(serializer)
+    }
+}
+
+impl Deserialize for TokenMetadata {
+    fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
+        Self::deserialize_with_options(raw, &DeserializeOptions::default())
+    }
+}
+
+impl DeserializeWithOptions for TokenMetadata {
+    fn deserialize_with_options<R: BufRead + Seek>(raw: &mut Deserializer<R>, options: &DeserializeOptions) -> Result<Self, DeserializeError> {
+        (|| -> Result<_, DeserializeError> {
+            let len = raw.map()?;
+            let mut key_721 = None;
+            let mut key_777 = None;
+            let mut read = 0;
+            while match len { cbor_event::Len::Len(n) => read < n as usize, cbor_event::Len::Indefinite => true, } {
+                match raw.cbor_type()? {
+                    CBORType::UnsignedInteger => match raw.unsigned_integer()? {
+                        721 =>  {
+                            if key_721.is_some() {
+                                return Err(DeserializeFailure::DuplicateKey(Key::Uint(721)).into());
+                            }
+                            key_721 = Some(LabelMetadata::deserialize_with_options(raw, options).map_err(|e| e.annotate("key_721").annotate_path(PathSegment::Field(String::from("721"))))?);
+                        },
+                        ROYALTY_LABEL =>  {
+                            if key_777.is_some() {
+                                return Err(DeserializeFailure::DuplicateKey(Key::Uint(ROYALTY_LABEL)).into());
+                            }
+                            key_777 = Some(RoyaltyMetadata::deserialize(raw).map_err(|e| e.annotate("key_777").annotate_path(PathSegment::Field(ROYALTY_LABEL.to_string())))?);
+                        },
+                        _unknown_key => {
+                            /* we must be permissive as we are looking at a subset of metadata here */
+                            TransactionMetadatum::deserialize(raw)?;
+                        },
+                    },
+                    CBORType::Special => match len {
+                        cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
+                        cbor_event::Len::Indefinite => match raw.special()? {
+                            CBORSpecial::Break => break,
+                            _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                        },
+                    },
+                    _other_type => {
+                        /* we must be permissive as we are looking at a subset of metadata here */
+                        TransactionMetadatum::deserialize(raw)?;
+                        TransactionMetadatum::deserialize(raw)?;
+                    },
+                }
+                read += 1;
+            }
+            Ok(Self {
+                key_721,
+                key_777,
+            })
+        })().map_err(|e| e.annotate("TokenMetadata"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR: &str = "addr1qxyz";
+
+    fn text(text: &str) -> String {
+        format!("{:02x}{}", 0x60 + text.len(), hex::encode(text))
+    }
+
+    // {rate_key: rate, "addr": ADDR}
+    fn royalty(rate_key: &str, rate: &str) -> Vec<u8> {
+        hex::decode(format!("a2{}{}{}{}", text(rate_key), text(rate), text("addr"), text(ADDR))).unwrap()
+    }
+
+    #[test]
+    fn rates() {
+        for rate in ["0", "1", "0.2", ".2", "00.05", "1.000", "0.", "0.123456789"] {
+            assert!(check_rate(rate).is_ok(), "{}", rate);
+        }
+        for rate in ["", ".", "1.01", "2", "10", "-0.1", "+0.1", "2e-1", "20%", "0,1", " 0.1", "0.1.1", "１"] {
+            let err = check_rate(rate).unwrap_err();
+            assert!(matches!(err.failure(), DeserializeFailure::InvalidRoyaltyRate(found) if found == rate), "{}", rate);
+        }
+    }
+
+    #[test]
+    fn rate_and_pct_keys_round_trip() {
+        for (rate_key, expected) in [("rate", RateKey::Rate), ("pct", RateKey::Pct)] {
+            let bytes = royalty(rate_key, "0.05");
+            let royalty_metadata = RoyaltyMetadata::from_bytes(bytes.clone()).unwrap();
+            assert_eq!(royalty_metadata.rate_key, expected);
+            assert_eq!(royalty_metadata.rate_value(), 0.05);
+            assert_eq!(royalty_metadata.address(), ADDR);
+            assert_eq!(royalty_metadata.to_bytes().unwrap(), bytes);
+        }
+        let built = RoyaltyMetadata::new(String64::new(String::from("0.05")).unwrap(), String64OrArrString64::new_string64(String64::new(ADDR.to_owned()).unwrap())).unwrap();
+        assert_eq!(built.to_bytes().unwrap(), royalty("rate", "0.05"));
+    }
+
+    #[test]
+    fn invalid_royalties() {
+        let err = RoyaltyMetadata::from_bytes(royalty("rate", "5%")).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::InvalidRoyaltyRate(_)), "{}", err);
+        assert_eq!(err.path(), [PathSegment::Field(String::from("rate"))]);
+        let err = RoyaltyMetadata::from_bytes(royalty("fee", "0.05")).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::UnknownKey(Key::Str(key)) if key == "fee"), "{}", err);
+        // {"rate": "0.05", "pct": "0.05"}
+        let both = hex::decode(format!("a2{}{}{}{}", text("rate"), text("0.05"), text("pct"), text("0.05"))).unwrap();
+        let err = RoyaltyMetadata::from_bytes(both).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::DuplicateKey(Key::Str(key)) if key == "pct"), "{}", err);
+        assert!(RoyaltyMetadata::new(String64::new(String::from("1.5")).unwrap(), String64OrArrString64::new_string64(String64::new(ADDR.to_owned()).unwrap())).is_err());
+    }
+
+    #[test]
+    fn json_rate_keys() {
+        let royalty_metadata: RoyaltyMetadata = serde_json::from_str(&format!(r#"{{"pct": "0.1", "addr": "{}"}}"#, ADDR)).unwrap();
+        assert_eq!(royalty_metadata.rate_key, RateKey::Pct);
+        assert_eq!(serde_json::to_value(&royalty_metadata).unwrap(), serde_json::json!({ "pct": "0.1", "addr": ADDR }));
+        assert!(serde_json::from_str::<RoyaltyMetadata>(&format!(r#"{{"rate": "0.1", "pct": "0.1", "addr": "{}"}}"#, ADDR)).is_err());
+        assert!(serde_json::from_str::<RoyaltyMetadata>(&format!(r#"{{"addr": "{}"}}"#, ADDR)).is_err());
+        assert!(serde_json::from_str::<RoyaltyMetadata>(&format!(r#"{{"rate": "1.1", "addr": "{}"}}"#, ADDR)).is_err());
+    }
+}
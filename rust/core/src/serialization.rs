@@ -56,27 +56,35 @@ pub trait SerializeEmbeddedGroup {
 }
 
 pub trait ToBytes {
-  fn to_bytes(&self) -> Vec<u8>;
+  fn to_bytes(&self) -> Result<Vec<u8>, SerializeError>;
+
+  fn to_bytes_with(&self, options: &crate::encoding::EncodingOptions) -> Result<Vec<u8>, SerializeError>;
 }
 
 impl<T: cbor_event::se::Serialize> ToBytes for T {
-  fn to_bytes(&self) -> Vec<u8> {
+  fn to_bytes(&self) -> Result<Vec<u8>, SerializeError> {
       let mut buf = Serializer::new_vec();
-      self.serialize(&mut buf).unwrap();
-      buf.finalize()
+      self.serialize(&mut buf)?;
+      Ok(buf.finalize())
+  }
+
+  fn to_bytes_with(&self, options: &crate::encoding::EncodingOptions) -> Result<Vec<u8>, SerializeError> {
+      crate::encoding::reencode(self.to_bytes()?, options)
   }
 }use super::*;
+use crate::transaction::skip;
 use std::io::{Seek, SeekFrom};
 
 impl cbor_event::se::Serialize for FilesDetails {
     fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
-        serializer.write_map(cbor_event::Len::Len(3))?;
+        serializer.write_map(cbor_event::Len::Len(3 + self.other_properties.len() as u64))?;
         serializer.write_text(&"src")?;
         self.src.serialize(serializer)?;
         serializer.write_text(&"name")?;
         self.name.serialize(serializer)?;
         serializer.write_text(&"mediaType")?;
         self.media_type.serialize(serializer)?;
+        serialize_other_properties(serializer, &self.other_properties)?;
         Ok(serializer)
     }
 }
@@ -90,6 +98,7 @@ impl Deserialize for FilesDetails {
             let mut src = None;
             let mut name = None;
             let mut media_type = None;
+            let mut other_properties = BTreeMap::new();
             let mut read = 0;
             while match len { cbor_event::Len::Len(n) => read < n as usize, cbor_event::Len::Indefinite => true, } {
                 match raw.cbor_type()? {
@@ -103,7 +112,7 @@ impl Deserialize for FilesDetails {
                             }
                             src = Some((|| -> Result<_, DeserializeError> {
                                 Ok(String64OrArrString64::deserialize(raw)?)
-                            })().map_err(|e| e.annotate("src"))?);
+                            })().map_err(|e| e.annotate("src").annotate_path(PathSegment::Field(String::from("src"))))?);
                         },
                         "name" =>  {
                             if name.is_some() {
@@ -111,7 +120,7 @@ impl Deserialize for FilesDetails {
                             }
                             name = Some((|| -> Result<_, DeserializeError> {
                                 Ok(String64::deserialize(raw)?)
-                            })().map_err(|e| e.annotate("name"))?);
+                            })().map_err(|e| e.annotate("name").annotate_path(PathSegment::Field(String::from("name"))))?);
                         },
                         "mediaType" =>  {
                             if media_type.is_some() {
@@ -119,9 +128,9 @@ impl Deserialize for FilesDetails {
                             }
                             media_type = Some((|| -> Result<_, DeserializeError> {
                                 Ok(String64::deserialize(raw)?)
-                            })().map_err(|e| e.annotate("media_type"))?);
+                            })().map_err(|e| e.annotate("media_type").annotate_path(PathSegment::Field(String::from("mediaType"))))?);
                         },
-                        unknown_key => return Err(DeserializeFailure::UnknownKey(Key::Str(unknown_key.to_owned())).into()),
+                        other_key => deserialize_other_property(raw, &mut other_properties, other_key)?,
                     },
                     CBORType::Special => match len {
                         cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
@@ -151,79 +160,283 @@ impl Deserialize for FilesDetails {
                 name,
                 media_type,
                 src,
+                other_properties,
             })
         })().map_err(|e| e.annotate("FilesDetails"))
     }
 }
 
+// The ledger rejects metadata with a longer text or bytes, this is checked again when
+// encoding for the values that didn't go through a constructor.
+fn check_encoded_len(len: usize) -> cbor_event::Result<()> {
+    check_metadatum_len(len).map_err(|e| cbor_event::Error::CustomError(format!("metadata text or bytes too long: {}", e)))
+}
+
+pub(crate) fn write_metadatum_text<'se, W: Write>(serializer: &'se mut Serializer<W>, text: &str) -> cbor_event::Result<&'se mut Serializer<W>> {
+    check_encoded_len(text.len())?;
+    serializer.write_text(text)
+}
+
+pub(crate) fn write_metadatum_bytes<'se, W: Write>(serializer: &'se mut Serializer<W>, bytes: &[u8]) -> cbor_event::Result<&'se mut Serializer<W>> {
+    check_encoded_len(bytes.len())?;
+    serializer.write_bytes(bytes)
+}
+
+// other properties come after the CIP-25 fields, in key order
+fn serialize_other_properties<'se, W: Write>(serializer: &'se mut Serializer<W>, other_properties: &BTreeMap<String, TransactionMetadatum>) -> cbor_event::Result<&'se mut Serializer<W>> {
+    for (key, value) in other_properties.iter() {
+        write_metadatum_text(serializer, key)?;
+        value.serialize(serializer)?;
+    }
+    Ok(serializer)
+}
+
+fn deserialize_other_property<R: BufRead + Seek>(raw: &mut Deserializer<R>, other_properties: &mut BTreeMap<String, TransactionMetadatum>, key: &str) -> Result<(), DeserializeError> {
+    if other_properties.contains_key(key) {
+        return Err(DeserializeFailure::DuplicateKey(Key::Str(key.to_owned())).into());
+    }
+    check_metadatum_len(key.len()).map_err(|e| DeserializeError::from(e).annotate_path(PathSegment::Field(key.to_owned())))?;
+    let value = TransactionMetadatum::deserialize(raw).map_err(|e| e.annotate_path(PathSegment::Field(key.to_owned())))?;
+    other_properties.insert(key.to_owned(), value);
+    Ok(())
+}
+
+pub(crate) const VERSION_KEY: &str = "version";
+
+fn serialize_version<W: Write>(serializer: &mut Serializer<W>, version: u64, format: VersionFormat) -> cbor_event::Result<&mut Serializer<W>> {
+    serializer.write_text(VERSION_KEY)?;
+    match format {
+        VersionFormat::Uint => serializer.write_unsigned_integer(version),
+        VersionFormat::Text => serializer.write_text(format!("{}.0", version)),
+    }
+}
+
+// The version of a label, if any, and whether it has a "data" key. They decide how the
+// label is decoded so they are looked up first, without decoding the rest so that errors
+// elsewhere are reported where they are by the decoding of the right shape.
+pub(crate) fn label_shape<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<(Option<Result<TransactionMetadatum, DeserializeError>>, bool), DeserializeError> {
+    let mut version = None;
+    let mut has_data = false;
+    if raw.cbor_type()? != CBORType::Map {
+        return Ok((version, has_data));
+    }
+    let len = raw.map()?;
+    let mut read = 0;
+    while match len { cbor_event::Len::Len(n) => read < n, cbor_event::Len::Indefinite => true, } {
+        match raw.cbor_type()? {
+            CBORType::Special if len == cbor_event::Len::Indefinite && raw.special_break()? => break,
+            CBORType::Text => match raw.text()?.as_str() {
+                VERSION_KEY if version.is_none() => {
+                    let value_position = raw.as_mut_ref().stream_position().unwrap();
+                    version = Some(TransactionMetadatum::deserialize(raw).map_err(|e| e.with_offset(raw.as_mut_ref().stream_position().unwrap())));
+                    if version.as_ref().is_some_and(Result::is_err) {
+                        raw.as_mut_ref().seek(SeekFrom::Start(value_position)).unwrap();
+                        skip(raw)?;
+                    }
+                },
+                "data" => {
+                    has_data = true;
+                    skip(raw)?;
+                },
+                _ => skip(raw)?,
+            },
+            _ => {
+                skip(raw)?;
+                skip(raw)?;
+            },
+        }
+        read += 1;
+    }
+    Ok((version, has_data))
+}
+
+// the version number and the way it was written, for the versions this library supports
+pub(crate) fn version_from_metadatum(version: TransactionMetadatum) -> Result<(u64, VersionFormat), DeserializeError> {
+    match version {
+        TransactionMetadatum::Int(number @ 1..=2) => Ok((number as u64, VersionFormat::Uint)),
+        TransactionMetadatum::Int(number) => match u64::try_from(number) {
+            Ok(number) => Err(DeserializeFailure::UnsupportedVersion(Key::Uint(number)).into()),
+            Err(_) => Err(DeserializeFailure::UnsupportedVersion(Key::Str(number.to_string())).into()),
+        },
+        TransactionMetadatum::Text(text) => match text.as_str() {
+            "1.0" => Ok((1, VersionFormat::Text)),
+            "2.0" => Ok((2, VersionFormat::Text)),
+            _ => Err(DeserializeFailure::UnsupportedVersion(Key::Str(text)).into()),
+        },
+        _ => Err(DeserializeFailure::NoVariantMatched.into()),
+    }
+}
+
+// A version newer than this library, written as the CIP does: an int or an "N.0" text
+// above 2. Anything else is an invalid version rather than an unknown one.
+pub(crate) fn is_newer_version(version: &TransactionMetadatum) -> bool {
+    match version {
+        TransactionMetadatum::Int(number) => *number > 2,
+        TransactionMetadatum::Text(text) => text
+            .strip_suffix(".0")
+            .and_then(|number| number.parse::<u64>().ok())
+            .is_some_and(|number| number > 2 && format!("{}.0", number) == *text),
+        _ => false,
+    }
+}
+
+// the key reported in the error is only built when there is a duplicate
+fn insert_entry<K: Ord, V>(table: &mut BTreeMap<K, V>, key: K, value: V, duplicate_keys: DuplicateKeys, error_key: impl FnOnce(&K) -> Key) -> Result<(), DeserializeError> {
+    match table.entry(key) {
+        std::collections::btree_map::Entry::Vacant(entry) => {
+            entry.insert(value);
+        },
+        std::collections::btree_map::Entry::Occupied(mut entry) => match duplicate_keys {
+            DuplicateKeys::Error => return Err(DeserializeFailure::DuplicateKey(error_key(entry.key())).into()),
+            DuplicateKeys::KeepFirst => (),
+            DuplicateKeys::KeepLast => {
+                entry.insert(value);
+            },
+        },
+    }
+    Ok(())
+}
+
 impl cbor_event::se::Serialize for LabelMetadata {
     fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
         match self {
             LabelMetadata::LabelMetadataV1(label_metadata_v1) => {
-                serializer.write_map(cbor_event::Len::Len(label_metadata_v1.len() as u64))?;
-                for (key, value) in label_metadata_v1.iter() {
-                    key.serialize(serializer)?;
-                    serializer.write_map(cbor_event::Len::Len(value.len() as u64))?;
-                    for (key, value) in value.iter() {
-                        key.serialize(serializer)?;
-                        value.serialize(serializer)?;
-                    }
-                }
-                Ok(serializer)
+                label_metadata_v1.serialize(serializer)
             },
             LabelMetadata::LabelMetadataV2(label_metadata_v2) => {
                 label_metadata_v2.serialize(serializer)
             },
+            LabelMetadata::Unknown { raw, .. } => {
+                raw.serialize(serializer)
+            },
         }
     }
 }
 
 impl Deserialize for LabelMetadata {
     fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
+        Self::deserialize_with_options(raw, &DeserializeOptions::default())
+    }
+}
+
+impl DeserializeWithOptions for LabelMetadata {
+    fn deserialize_with_options<R: BufRead + Seek>(raw: &mut Deserializer<R>, options: &DeserializeOptions) -> Result<Self, DeserializeError> {
         (|| -> Result<_, DeserializeError> {
+            // the version field decides the variant but it can come after the policies
             let initial_position = raw.as_mut_ref().seek(SeekFrom::Current(0)).unwrap();
-            match (|raw: &mut Deserializer<_>| -> Result<_, DeserializeError> {
-                let mut label_metadata_v1_table = BTreeMap::new();
-                let label_metadata_v1_len = raw.map()?;
-                while match label_metadata_v1_len { cbor_event::Len::Len(n) => label_metadata_v1_table.len() < n as usize, cbor_event::Len::Indefinite => true, } {
-                    if raw.cbor_type()? == CBORType::Special {
-                        assert_eq!(raw.special()?, CBORSpecial::Break);
-                        break;
+            let (version, _) = label_shape(raw)?;
+            raw.as_mut_ref().seek(SeekFrom::Start(initial_position)).unwrap();
+            let version = match version {
+                Some(version) => {
+                    let version = version.map_err(|e| e.annotate("version").annotate_path(PathSegment::Field(String::from(VERSION_KEY))))?;
+                    match version_from_metadatum(version.clone()) {
+                        Ok((number, _)) => Some(number),
+                        Err(_) if is_newer_version(&version) => {
+                            return Ok(Self::Unknown {
+                                version,
+                                raw: TransactionMetadatum::deserialize(raw)?,
+                            });
+                        },
+                        Err(e) => return Err(e.annotate("version").annotate_path(PathSegment::Field(String::from(VERSION_KEY)))),
                     }
-                    let label_metadata_v1_key = String64::deserialize(raw)?;
-                    let mut label_metadata_v1_value_table = BTreeMap::new();
-                    let label_metadata_v1_value_len = raw.map()?;
-                    while match label_metadata_v1_value_len { cbor_event::Len::Len(n) => label_metadata_v1_value_table.len() < n as usize, cbor_event::Len::Indefinite => true, } {
-                        if raw.cbor_type()? == CBORType::Special {
-                            assert_eq!(raw.special()?, CBORSpecial::Break);
-                            break;
-                        }
-                        let label_metadata_v1_value_key = String64::deserialize(raw)?;
-                        let label_metadata_v1_value_value = MetadataDetails::deserialize(raw)?;
-                        if label_metadata_v1_value_table.insert(label_metadata_v1_value_key.clone(), label_metadata_v1_value_value).is_some() {
-                            return Err(DeserializeFailure::DuplicateKey(Key::Str(String::from("some complicated/unsupported type"))).into());
-                        }
+                },
+                None => None,
+            };
+            match version {
+                Some(2) => Ok(Self::LabelMetadataV2(LabelMetadataV2::deserialize_with_options(raw, options)?)),
+                _ => Ok(Self::LabelMetadataV1(LabelMetadataV1::deserialize_with_options(raw, options)?)),
+            }
+        })().map_err(|e| e.annotate("LabelMetadata"))
+    }
+}
+
+impl cbor_event::se::Serialize for LabelMetadataV1 {
+    fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
+        serializer.write_map(cbor_event::Len::Len(self.data.len() as u64 + match &self.version { Some(_) => 1, None => 0 }))?;
+        for (key, value) in self.data.iter() {
+            key.serialize(serializer)?;
+            serializer.write_map(cbor_event::Len::Len(value.len() as u64))?;
+            for (key, value) in value.iter() {
+                key.serialize(serializer)?;
+                value.serialize(serializer)?;
+            }
+        }
+        if let Some(version) = &self.version {
+            serialize_version(serializer, 1, *version)?;
+        }
+        Ok(serializer)
+    }
+}
+
+impl Deserialize for LabelMetadataV1 {
+    fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
+        Self::deserialize_with_options(raw, &DeserializeOptions::default())
+    }
+}
+
+impl DeserializeWithOptions for LabelMetadataV1 {
+    fn deserialize_with_options<R: BufRead + Seek>(raw: &mut Deserializer<R>, options: &DeserializeOptions) -> Result<Self, DeserializeError> {
+        (|| -> Result<_, DeserializeError> {
+            let mut data_table = BTreeMap::new();
+            let mut version = None;
+            let len = raw.map()?;
+            let mut read = 0;
+            while match len { cbor_event::Len::Len(n) => read < n as usize, cbor_event::Len::Indefinite => true, } {
+                if raw.cbor_type()? == CBORType::Special {
+                    match len {
+                        cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
+                        cbor_event::Len::Indefinite => match raw.special()? {
+                            CBORSpecial::Break => break,
+                            _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                        },
                     }
-                    let label_metadata_v1_value = label_metadata_v1_value_table;
-                    if label_metadata_v1_table.insert(label_metadata_v1_key.clone(), label_metadata_v1_value).is_some() {
-                        return Err(DeserializeFailure::DuplicateKey(Key::Str(String::from("some complicated/unsupported type"))).into());
+                }
+                let data_key = String64::deserialize(raw)?;
+                if data_key.get() == VERSION_KEY {
+                    if version.is_some() {
+                        return Err(DeserializeFailure::DuplicateKey(Key::Str(VERSION_KEY.into())).into());
                     }
+                    version = Some((|| -> Result<_, DeserializeError> {
+                        match version_from_metadatum(TransactionMetadatum::deserialize(raw)?)? {
+                            (1, format) => Ok(format),
+                            (found, _) => Err(DeserializeFailure::FixedValueMismatch{ found: Key::Uint(found), expected: Key::Uint(1) }.into()),
+                        }
+                    })().map_err(|e| e.annotate("version").annotate_path(PathSegment::Field(String::from("version"))))?);
+                } else {
+                    let data_value = (|| -> Result<_, DeserializeError> {
+                        let mut data_value_table = BTreeMap::new();
+                        let data_value_len = raw.map()?;
+                        let mut data_value_read = 0;
+                        while match data_value_len { cbor_event::Len::Len(n) => data_value_read < n as usize, cbor_event::Len::Indefinite => true, } {
+                            if raw.cbor_type()? == CBORType::Special {
+                                match data_value_len {
+                                    cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
+                                    cbor_event::Len::Indefinite => match raw.special()? {
+                                        CBORSpecial::Break => break,
+                                        _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                                    },
+                                }
+                            }
+                            let data_value_key = String64::deserialize(raw)?;
+                            let asset_name = PathSegment::AssetName(data_value_key.get().clone());
+                            let data_value_value = MetadataDetails::deserialize(raw).map_err(|e| e.annotate_path(asset_name.clone()))?;
+                            insert_entry(&mut data_value_table, data_value_key, data_value_value, options.duplicate_keys, |key| Key::Str(key.get().clone()))
+                                .map_err(|e| e.annotate_path(asset_name))?;
+                            data_value_read += 1;
+                        }
+                        Ok(data_value_table)
+                    })().map_err(|e| e.annotate_path(PathSegment::PolicyId(data_key.get().clone())))?;
+                    let policy_id = PathSegment::PolicyId(data_key.get().clone());
+                    insert_entry(&mut data_table, data_key, data_value, options.duplicate_keys, |key| Key::Str(key.get().clone()))
+                        .map_err(|e| e.annotate_path(policy_id))?;
                 }
-                Ok(label_metadata_v1_table)
-            })(raw)
-            {
-                Ok(label_metadata_v1) => return Ok(Self::LabelMetadataV1(label_metadata_v1)),
-                Err(_) => raw.as_mut_ref().seek(SeekFrom::Start(initial_position)).unwrap(),
-            };
-            match (|raw: &mut Deserializer<_>| -> Result<_, DeserializeError> {
-                Ok(LabelMetadataV2::deserialize(raw)?)
-            })(raw)
-            {
-                Ok(label_metadata_v2) => return Ok(Self::LabelMetadataV2(label_metadata_v2)),
-                Err(_) => raw.as_mut_ref().seek(SeekFrom::Start(initial_position)).unwrap(),
-            };
-            Err(DeserializeError::new("LabelMetadata", DeserializeFailure::NoVariantMatched.into()))
-        })().map_err(|e| e.annotate("LabelMetadata"))
+                read += 1;
+            }
+            Ok(Self {
+                data: data_table,
+                version,
+            })
+        })().map_err(|e| e.annotate("LabelMetadataV1"))
     }
 }
 
@@ -233,27 +446,32 @@ impl cbor_event::se::Serialize for LabelMetadataV2 {
         serializer.write_text(&"data")?;
         serializer.write_map(cbor_event::Len::Len(self.data.len() as u64))?;
         for (key, value) in self.data.iter() {
-            serializer.write_bytes(&key)?;
+            write_metadatum_bytes(serializer, key)?;
             serializer.write_map(cbor_event::Len::Len(value.len() as u64))?;
             for (key, value) in value.iter() {
-                serializer.write_bytes(&key)?;
+                write_metadatum_bytes(serializer, key)?;
                 value.serialize(serializer)?;
             }
         }
-        serializer.write_text(&"version")?;
-        serializer.write_unsigned_integer(2u64)?;
+        serialize_version(serializer, 2, self.version)?;
         Ok(serializer)
     }
 }
 
 impl Deserialize for LabelMetadataV2 {
     fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
+        Self::deserialize_with_options(raw, &DeserializeOptions::default())
+    }
+}
+
+impl DeserializeWithOptions for LabelMetadataV2 {
+    fn deserialize_with_options<R: BufRead + Seek>(raw: &mut Deserializer<R>, options: &DeserializeOptions) -> Result<Self, DeserializeError> {
         (|| -> Result<_, DeserializeError> {
             let len = raw.map()?;
             let mut read_len = CBORReadLen::new(len);
             read_len.read_elems(2)?;
             let mut data = None;
-            let mut version_present = false;
+            let mut version = None;
             let mut read = 0;
             while match len { cbor_event::Len::Len(n) => read < n as usize, cbor_event::Len::Indefinite => true, } {
                 match raw.cbor_type()? {
@@ -268,44 +486,61 @@ impl Deserialize for LabelMetadataV2 {
                             data = Some((|| -> Result<_, DeserializeError> {
                                 let mut data_table = BTreeMap::new();
                                 let data_len = raw.map()?;
-                                while match data_len { cbor_event::Len::Len(n) => data_table.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+                                let mut data_read = 0;
+                                while match data_len { cbor_event::Len::Len(n) => data_read < n as usize, cbor_event::Len::Indefinite => true, } {
                                     if raw.cbor_type()? == CBORType::Special {
-                                        assert_eq!(raw.special()?, CBORSpecial::Break);
-                                        break;
+                                        match data_len {
+                                            cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
+                                            cbor_event::Len::Indefinite => match raw.special()? {
+                                                CBORSpecial::Break => break,
+                                                _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                                            },
+                                        }
                                     }
                                     let data_key = raw.bytes()? as Vec<u8>;
-                                    let mut data_value_table = BTreeMap::new();
-                                    let data_value_len = raw.map()?;
-                                    while match data_value_len { cbor_event::Len::Len(n) => data_value_table.len() < n as usize, cbor_event::Len::Indefinite => true, } {
-                                        if raw.cbor_type()? == CBORType::Special {
-                                            assert_eq!(raw.special()?, CBORSpecial::Break);
-                                            break;
-                                        }
-                                        let data_value_key = raw.bytes()? as Vec<u8>;
-                                        let data_value_value = MetadataDetails::deserialize(raw)?;
-                                        if data_value_table.insert(data_value_key.clone(), data_value_value).is_some() {
-                                            return Err(DeserializeFailure::DuplicateKey(Key::Str(String::from("some complicated/unsupported type"))).into());
+                                    let data_value = (|| -> Result<_, DeserializeError> {
+                                        check_metadatum_len(data_key.len())?;
+                                        let mut data_value_table = BTreeMap::new();
+                                        let data_value_len = raw.map()?;
+                                        let mut data_value_read = 0;
+                                        while match data_value_len { cbor_event::Len::Len(n) => data_value_read < n as usize, cbor_event::Len::Indefinite => true, } {
+                                            if raw.cbor_type()? == CBORType::Special {
+                                                match data_value_len {
+                                                    cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
+                                                    cbor_event::Len::Indefinite => match raw.special()? {
+                                                        CBORSpecial::Break => break,
+                                                        _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                                                    },
+                                                }
+                                            }
+                                            let data_value_key = raw.bytes()? as Vec<u8>;
+                                            let asset_name = PathSegment::AssetName(hex::encode(&data_value_key));
+                                            check_metadatum_len(data_value_key.len()).map_err(|e| DeserializeError::from(e).annotate_path(asset_name.clone()))?;
+                                            let data_value_value = MetadataDetails::deserialize(raw).map_err(|e| e.annotate_path(asset_name.clone()))?;
+                                            insert_entry(&mut data_value_table, data_value_key, data_value_value, options.duplicate_keys, |key| Key::Bytes(key.clone()))
+                                                .map_err(|e| e.annotate_path(asset_name))?;
+                                            data_value_read += 1;
                                         }
-                                    }
-                                    let data_value = data_value_table;
-                                    if data_table.insert(data_key.clone(), data_value).is_some() {
-                                        return Err(DeserializeFailure::DuplicateKey(Key::Str(String::from("some complicated/unsupported type"))).into());
-                                    }
+                                        Ok(data_value_table)
+                                    })().map_err(|e| e.annotate_path(PathSegment::PolicyId(hex::encode(&data_key))))?;
+                                    let policy_id = PathSegment::PolicyId(hex::encode(&data_key));
+                                    insert_entry(&mut data_table, data_key, data_value, options.duplicate_keys, |key| Key::Bytes(key.clone()))
+                                        .map_err(|e| e.annotate_path(policy_id))?;
+                                    data_read += 1;
                                 }
                                 Ok(data_table)
-                            })().map_err(|e| e.annotate("data"))?);
+                            })().map_err(|e| e.annotate("data").annotate_path(PathSegment::Field(String::from("data"))))?);
                         },
                         "version" =>  {
-                            if version_present {
+                            if version.is_some() {
                                 return Err(DeserializeFailure::DuplicateKey(Key::Str("version".into())).into());
                             }
-                            version_present = (|| -> Result<_, DeserializeError> {
-                                let version_value = raw.unsigned_integer()?;
-                                if version_value != 2 {
-                                    return Err(DeserializeFailure::FixedValueMismatch{ found: Key::Uint(version_value), expected: Key::Uint(2) }.into());
+                            version = Some((|| -> Result<_, DeserializeError> {
+                                match version_from_metadatum(TransactionMetadatum::deserialize(raw)?)? {
+                                    (2, format) => Ok(format),
+                                    (found, _) => Err(DeserializeFailure::FixedValueMismatch{ found: Key::Uint(found), expected: Key::Uint(2) }.into()),
                                 }
-                                Ok(true)
-                            })().map_err(|e| e.annotate("version"))?;
+                            })().map_err(|e| e.annotate("version").annotate_path(PathSegment::Field(String::from("version"))))?);
                         },
                         unknown_key => return Err(DeserializeFailure::UnknownKey(Key::Str(unknown_key.to_owned())).into()),
                     },
@@ -324,12 +559,14 @@ impl Deserialize for LabelMetadataV2 {
                 Some(x) => x,
                 None => return Err(DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from("data"))).into()),
             };
-            if !version_present {
-                return Err(DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from("version"))).into());
-            }
+            let version = match version {
+                Some(x) => x,
+                None => return Err(DeserializeFailure::MandatoryFieldMissing(Key::Str(String::from("version"))).into()),
+            };
             ();
             Ok(Self {
                 data,
+                version,
             })
         })().map_err(|e| e.annotate("LabelMetadataV2"))
     }
@@ -346,6 +583,12 @@ impl cbor_event::se::Serialize for Metadata {
 
 impl Deserialize for Metadata {
     fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
+        Self::deserialize_with_options(raw, &DeserializeOptions::default())
+    }
+}
+
+impl DeserializeWithOptions for Metadata {
+    fn deserialize_with_options<R: BufRead + Seek>(raw: &mut Deserializer<R>, options: &DeserializeOptions) -> Result<Self, DeserializeError> {
         (|| -> Result<_, DeserializeError> {
             let len = raw.map()?;
             let mut read_len = CBORReadLen::new(len);
@@ -360,10 +603,13 @@ impl Deserialize for Metadata {
                                 return Err(DeserializeFailure::DuplicateKey(Key::Uint(721)).into());
                             }
                             key_721 = Some((|| -> Result<_, DeserializeError> {
-                                Ok(LabelMetadata::deserialize(raw)?)
-                            })().map_err(|e| e.annotate("key_721"))?);
+                                Ok(LabelMetadata::deserialize_with_options(raw, options)?)
+                            })().map_err(|e| e.annotate("key_721").annotate_path(PathSegment::Field(String::from("721"))))?);
+                        },
+                        _unknown_key => {
+                            /* we must be permissive as we are looking at a subset of metadata here */
+                            TransactionMetadatum::deserialize(raw)?;
                         },
-                        _unknown_key => /* we must be permissive as we are looking at a subset of metadata here */(),
                     },
                     CBORType::Special => match len {
                         cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
@@ -372,7 +618,11 @@ impl Deserialize for Metadata {
                             _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
                         },
                     },
-                    _other_type => /* we must be permissive as we are looking at a subset of metadata here */(),
+                    _other_type => {
+                        /* we must be permissive as we are looking at a subset of metadata here */
+                        TransactionMetadatum::deserialize(raw)?;
+                        TransactionMetadatum::deserialize(raw)?;
+                    },
                 }
                 read += 1;
             }
@@ -390,7 +640,7 @@ impl Deserialize for Metadata {
 
 impl cbor_event::se::Serialize for MetadataDetails {
     fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
-        serializer.write_map(cbor_event::Len::Len(2 + match &self.media_type { Some(_) => 1, None => 0 } + match &self.description { Some(_) => 1, None => 0 } + match &self.files { Some(_) => 1, None => 0 }))?;
+        serializer.write_map(cbor_event::Len::Len(2 + match &self.media_type { Some(_) => 1, None => 0 } + match &self.description { Some(_) => 1, None => 0 } + match &self.files { Some(_) => 1, None => 0 } + self.other_properties.len() as u64))?;
         serializer.write_text(&"name")?;
         self.name.serialize(serializer)?;
         if let Some(field) = &self.files {
@@ -410,6 +660,7 @@ impl cbor_event::se::Serialize for MetadataDetails {
             serializer.write_text(&"description")?;
             field.serialize(serializer)?;
         }
+        serialize_other_properties(serializer, &self.other_properties)?;
         Ok(serializer)
     }
 }
@@ -425,6 +676,7 @@ impl Deserialize for MetadataDetails {
             let mut image = None;
             let mut media_type = None;
             let mut description = None;
+            let mut other_properties = BTreeMap::new();
             let mut read = 0;
             while match len { cbor_event::Len::Len(n) => read < n as usize, cbor_event::Len::Indefinite => true, } {
                 match raw.cbor_type()? {
@@ -438,7 +690,7 @@ impl Deserialize for MetadataDetails {
                             }
                             name = Some((|| -> Result<_, DeserializeError> {
                                 Ok(String64::deserialize(raw)?)
-                            })().map_err(|e| e.annotate("name"))?);
+                            })().map_err(|e| e.annotate("name").annotate_path(PathSegment::Field(String::from("name"))))?);
                         },
                         "files" =>  {
                             if files.is_some() {
@@ -450,13 +702,19 @@ impl Deserialize for MetadataDetails {
                                 let len = raw.array()?;
                                 while match len { cbor_event::Len::Len(n) => files_arr.len() < n as usize, cbor_event::Len::Indefinite => true, } {
                                     if raw.cbor_type()? == CBORType::Special {
-                                        assert_eq!(raw.special()?, CBORSpecial::Break);
-                                        break;
+                                        match len {
+                                            cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
+                                            cbor_event::Len::Indefinite => match raw.special()? {
+                                                CBORSpecial::Break => break,
+                                                _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                                            },
+                                        }
                                     }
-                                    files_arr.push(FilesDetails::deserialize(raw)?);
+                                    let index = files_arr.len();
+                                    files_arr.push(FilesDetails::deserialize(raw).map_err(|e| e.annotate_path(PathSegment::Index(index)))?);
                                 }
                                 Ok(files_arr)
-                            })().map_err(|e| e.annotate("files"))?);
+                            })().map_err(|e| e.annotate("files").annotate_path(PathSegment::Field(String::from("files"))))?);
                         },
                         "image" =>  {
                             if image.is_some() {
@@ -464,7 +722,7 @@ impl Deserialize for MetadataDetails {
                             }
                             image = Some((|| -> Result<_, DeserializeError> {
                                 Ok(String64OrArrString64::deserialize(raw)?)
-                            })().map_err(|e| e.annotate("image"))?);
+                            })().map_err(|e| e.annotate("image").annotate_path(PathSegment::Field(String::from("image"))))?);
                         },
                         "mediaType" =>  {
                             if media_type.is_some() {
@@ -473,7 +731,7 @@ impl Deserialize for MetadataDetails {
                             media_type = Some((|| -> Result<_, DeserializeError> {
                                 read_len.read_elems(1)?;
                                 Ok(String64::deserialize(raw)?)
-                            })().map_err(|e| e.annotate("media_type"))?);
+                            })().map_err(|e| e.annotate("media_type").annotate_path(PathSegment::Field(String::from("mediaType"))))?);
                         },
                         "description" =>  {
                             if description.is_some() {
@@ -482,9 +740,12 @@ impl Deserialize for MetadataDetails {
                             description = Some((|| -> Result<_, DeserializeError> {
                                 read_len.read_elems(1)?;
                                 Ok(String64OrArrString64::deserialize(raw)?)
-                            })().map_err(|e| e.annotate("description"))?);
+                            })().map_err(|e| e.annotate("description").annotate_path(PathSegment::Field(String::from("description"))))?);
+                        },
+                        other_key => {
+                            read_len.read_elems(1)?;
+                            deserialize_other_property(raw, &mut other_properties, other_key)?;
                         },
-                        unknown_key => return Err(DeserializeFailure::UnknownKey(Key::Str(unknown_key.to_owned())).into()),
                     },
                     CBORType::Special => match len {
                         cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
@@ -512,6 +773,7 @@ impl Deserialize for MetadataDetails {
                 media_type,
                 description,
                 files,
+                other_properties,
             })
         })().map_err(|e| e.annotate("MetadataDetails"))
     }
@@ -519,16 +781,14 @@ impl Deserialize for MetadataDetails {
 
 impl cbor_event::se::Serialize for String64 {
     fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
-        serializer.write_text(&self.0)
+        write_metadatum_text(serializer, &self.0)
     }
 }
 
 impl Deserialize for String64 {
     fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
         let inner = raw.text()? as String;
-        if inner.len() > 64 {
-            return Err(DeserializeError::new("String64", DeserializeFailure::RangeCheck{ found: inner.len(), min: Some(0), max: Some(64) }));
-        }
+        check_metadatum_len(inner.len()).map_err(|e| DeserializeError::new("String64", e))?;
         Ok(Self(inner))
     }
 }
@@ -566,8 +826,13 @@ impl Deserialize for String64OrArrString64 {
                 let len = raw.array()?;
                 while match len { cbor_event::Len::Len(n) => arr_string64_arr.len() < n as usize, cbor_event::Len::Indefinite => true, } {
                     if raw.cbor_type()? == CBORType::Special {
-                        assert_eq!(raw.special()?, CBORSpecial::Break);
-                        break;
+                        match len {
+                            cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
+                            cbor_event::Len::Indefinite => match raw.special()? {
+                                CBORSpecial::Break => break,
+                                _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                            },
+                        }
                     }
                     arr_string64_arr.push(String64::deserialize(raw)?);
                 }
@@ -580,4 +845,410 @@ impl Deserialize for String64OrArrString64 {
             Err(DeserializeError::new("String64OrArrString64", DeserializeFailure::NoVariantMatched.into()))
         })().map_err(|e| e.annotate("String64OrArrString64"))
     }
-}
\ No newline at end of file
+}
+
+impl cbor_event::se::Serialize for TransactionMetadatum {
+    fn serialize<'se, W: Write>(&self, serializer: &'se mut Serializer<W>) -> cbor_event::Result<&'se mut Serializer<W>> {
+        match self {
+            TransactionMetadatum::Map(map) => {
+                serializer.write_map(cbor_event::Len::Len(map.len() as u64))?;
+                for (key, value) in map.iter() {
+                    key.serialize(serializer)?;
+                    value.serialize(serializer)?;
+                }
+                Ok(serializer)
+            },
+            TransactionMetadatum::List(list) => {
+                serializer.write_array(cbor_event::Len::Len(list.len() as u64))?;
+                for element in list.iter() {
+                    element.serialize(serializer)?;
+                }
+                Ok(serializer)
+            },
+            TransactionMetadatum::Int(int) => {
+                if *int >= 0 {
+                    let uint = u64::try_from(*int).map_err(|_| cbor_event::Error::CustomError(format!("{} does not fit in a CBOR uint", int)))?;
+                    serializer.write_unsigned_integer(uint)
+                } else {
+                    let nint_arg = u64::try_from(-*int - 1).map_err(|_| cbor_event::Error::InvalidNint(*int))?;
+                    serializer.write_negative_integer_sz(*int, cbor_event::Sz::canonical(nint_arg))
+                }
+            },
+            TransactionMetadatum::Bytes(bytes) => {
+                write_metadatum_bytes(serializer, bytes)
+            },
+            TransactionMetadatum::Text(text) => {
+                write_metadatum_text(serializer, text)
+            },
+        }
+    }
+}
+
+impl Deserialize for TransactionMetadatum {
+    fn deserialize<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Self, DeserializeError> {
+        deserialize_metadatum(raw, 0).map_err(|e| e.annotate("TransactionMetadatum"))
+    }
+}
+
+// depth is the number of maps / lists this value is in
+fn deserialize_metadatum<R: BufRead + Seek>(raw: &mut Deserializer<R>, depth: usize) -> Result<TransactionMetadatum, DeserializeError> {
+    match raw.cbor_type()? {
+        CBORType::Map | CBORType::Array if depth == MAX_METADATUM_DEPTH => {
+            Err(DeserializeError::new("nesting", DeserializeFailure::RangeCheck{ found: depth + 1, min: None, max: Some(MAX_METADATUM_DEPTH as isize) }))
+        },
+        CBORType::Map => {
+            let mut map = Vec::new();
+            let len = raw.map()?;
+            while match len { cbor_event::Len::Len(n) => map.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+                if raw.cbor_type()? == CBORType::Special {
+                    match raw.special()? {
+                        CBORSpecial::Break => break,
+                        _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                    }
+                }
+                let key = deserialize_metadatum(raw, depth + 1)?;
+                let value = deserialize_metadatum(raw, depth + 1)?;
+                map.push((key, value));
+            }
+            Ok(TransactionMetadatum::Map(map))
+        },
+        CBORType::Array => {
+            let mut list = Vec::new();
+            let len = raw.array()?;
+            while match len { cbor_event::Len::Len(n) => list.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+                if raw.cbor_type()? == CBORType::Special {
+                    match raw.special()? {
+                        CBORSpecial::Break => break,
+                        _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                    }
+                }
+                list.push(deserialize_metadatum(raw, depth + 1)?);
+            }
+            Ok(TransactionMetadatum::List(list))
+        },
+        CBORType::UnsignedInteger => Ok(TransactionMetadatum::Int(raw.unsigned_integer()? as i128)),
+        CBORType::NegativeInteger => Ok(TransactionMetadatum::Int(raw.negative_integer_sz()?.0)),
+        CBORType::Bytes => {
+            let bytes = raw.bytes()?;
+            check_metadatum_len(bytes.len())?;
+            Ok(TransactionMetadatum::Bytes(bytes))
+        },
+        CBORType::Text => {
+            let text = raw.text()?;
+            check_metadatum_len(text.len())?;
+            Ok(TransactionMetadatum::Text(text))
+        },
+        _ => Err(DeserializeFailure::NoVariantMatched.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    // a version 1 document with an "x" other property whose definite list [1] can be patched
+    fn metadata_with_other_property() -> Vec<u8> {
+        let mut details = MetadataDetails::new(String64::new(String::from("Patate")).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("ipfs://patate")).unwrap()));
+        details.other_properties.insert(String::from("x"), TransactionMetadatum::new_list(vec![TransactionMetadatum::new_int(1)]));
+        let mut assets = BTreeMap::new();
+        assets.insert(String64::new(String::from("Patate")).unwrap(), details);
+        let mut data = BTreeMap::new();
+        data.insert(String64::new(POLICY_ID.to_owned()).unwrap(), assets);
+        Metadata::new(LabelMetadata::new_label_metadata_v1(LabelMetadataV1::new(data))).to_bytes().unwrap()
+    }
+
+    fn replace(bytes: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+        let at = bytes.windows(from.len()).position(|window| window == from).unwrap();
+        [&bytes[..at], to, &bytes[at + from.len()..]].concat()
+    }
+
+    // n lists in one another around a 1
+    fn nested_lists(n: usize) -> Vec<u8> {
+        [vec![0x81; n], vec![0x01]].concat()
+    }
+
+    #[test]
+    fn metadatum_nesting_is_limited() {
+        assert!(TransactionMetadatum::from_bytes(nested_lists(MAX_METADATUM_DEPTH)).is_ok());
+        let err = TransactionMetadatum::from_bytes(nested_lists(MAX_METADATUM_DEPTH + 1)).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::RangeCheck{ found: 129, max: Some(128), .. }), "{}", err);
+        // maps count as well
+        let maps = [0xa1, 0x01].repeat(MAX_METADATUM_DEPTH + 1);
+        assert!(TransactionMetadatum::from_bytes([maps, vec![0x01]].concat()).is_err());
+        // in an other property, instead of overflowing the stack
+        let bytes = metadata_with_other_property();
+        assert!(Metadata::from_bytes(replace(&bytes, &[0x81, 0x01], &nested_lists(MAX_METADATUM_DEPTH))).is_ok());
+        for depth in [MAX_METADATUM_DEPTH + 1, 5000] {
+            let err = Metadata::from_bytes(replace(&bytes, &[0x81, 0x01], &nested_lists(depth))).unwrap_err();
+            assert!(matches!(err.failure(), DeserializeFailure::RangeCheck{ min: None, .. }), "{}", err);
+        }
+    }
+
+    #[test]
+    fn indefinite_metadatum_without_break_is_an_error() {
+        for bytes in [vec![0x9f, 0xf5, 0xff], vec![0xbf, 0x01, 0x02, 0xf4, 0xff]] {
+            let err = TransactionMetadatum::from_bytes(bytes).unwrap_err();
+            assert!(matches!(err.failure(), DeserializeFailure::EndingBreakMissing), "{}", err);
+        }
+        assert!(matches!(TransactionMetadatum::from_bytes(vec![0x9f, 0x01, 0xff]), Ok(TransactionMetadatum::List(list)) if list.len() == 1));
+    }
+
+    #[test]
+    fn other_property_without_break_is_an_error() {
+        let bytes = metadata_with_other_property();
+        assert!(Metadata::from_bytes(bytes.clone()).is_ok());
+        // "x": [_ true]
+        let err = Metadata::from_bytes(replace(&bytes, &[0x61, 0x78, 0x81, 0x01], &[0x61, 0x78, 0x9f, 0xf5, 0xff])).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::EndingBreakMissing), "{}", err);
+    }
+
+    #[test]
+    fn special_value_as_a_key_is_an_error() {
+        // {721: {null: {}}}, {721: {_ null: {}}}, {721: {policy_id: {null: ..}}}
+        let cases = [
+            ("a11902d1a1f6a0", DeserializeFailure::BreakInDefiniteLen),
+            ("a11902d1bff6a0ff", DeserializeFailure::EndingBreakMissing),
+            ("a11902d1a1781c37656165323861663232303862653835366637613131393636386165a1f6a0", DeserializeFailure::BreakInDefiniteLen),
+            ("a11902d1a1781c37656165323861663232303862653835366637613131393636386165bff6a0ff", DeserializeFailure::EndingBreakMissing),
+        ];
+        for (hex, failure) in cases.iter() {
+            let err = Metadata::from_bytes(hex::decode(hex).unwrap()).unwrap_err();
+            assert_eq!(std::mem::discriminant(err.failure()), std::mem::discriminant(failure), "{}: {}", hex, err);
+        }
+    }
+
+    #[test]
+    fn special_value_in_indefinite_array_is_an_error() {
+        // "image": [_ true] and "files": [_ true]
+        let bytes = metadata_with_other_property();
+        let err = Metadata::from_bytes(replace(&bytes, &unchecked_text("ipfs://patate"), &[0x9f, 0xf5, 0xff])).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::NoVariantMatched), "{}", err);
+        let err = Metadata::from_bytes(replace(&bytes, &[0x61, 0x78, 0x81, 0x01], &[&unchecked_text("files")[..], &[0x9f, 0xf5, 0xff]].concat())).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::EndingBreakMissing), "{}", err);
+    }
+
+    // {"name": name, "image": "x"}
+    fn details(name: &str) -> Vec<u8> {
+        [&[0xa2][..], &unchecked_text("name"), &unchecked_text(name), &unchecked_text("image"), &unchecked_text("x")].concat()
+    }
+
+    fn decode_with(bytes: &[u8], duplicate_keys: DuplicateKeys) -> Result<Metadata, DeserializeError> {
+        Metadata::from_bytes_with_options(bytes.to_vec(), &DeserializeOptions { duplicate_keys })
+    }
+
+    // (policy id, asset name, name) of every asset
+    fn names(metadata: &Metadata) -> Vec<(String, String, String)> {
+        let name = |details: &MetadataDetails| details.name.get().clone();
+        match &metadata.key_721 {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => label_metadata_v1.data.iter()
+                .flat_map(|(policy_id, assets)| assets.iter().map(move |(asset_name, details)| (policy_id.get().clone(), asset_name.get().clone(), name(details))))
+                .collect(),
+            LabelMetadata::LabelMetadataV2(label_metadata_v2) => label_metadata_v2.data.iter()
+                .flat_map(|(policy_id, assets)| assets.iter().map(move |(asset_name, details)| (hex::encode(policy_id), hex::encode(asset_name), name(details))))
+                .collect(),
+            LabelMetadata::Unknown { .. } => panic!("unknown version"),
+        }
+    }
+
+    fn check_duplicates(bytes: &[u8], key: Key, first: Vec<(String, String, String)>, last: Vec<(String, String, String)>) {
+        let err = decode_with(bytes, DuplicateKeys::Error).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::DuplicateKey(found) if format!("{:?}", found) == format!("{:?}", key)), "{}", err);
+        assert_eq!(Metadata::from_bytes(bytes.to_vec()).unwrap_err().to_string(), err.to_string());
+        assert_eq!(names(&decode_with(bytes, DuplicateKeys::KeepFirst).unwrap()), first);
+        assert_eq!(names(&decode_with(bytes, DuplicateKeys::KeepLast).unwrap()), last);
+    }
+
+    fn asset(policy_id: &str, asset_name: &str, name: &str) -> (String, String, String) {
+        (policy_id.to_owned(), asset_name.to_owned(), name.to_owned())
+    }
+
+    #[test]
+    fn duplicate_v1_keys() {
+        // {721: {POLICY_ID: {"A": "first"}, POLICY_ID: {"B": "second"}}}, the whole assets
+        // of a policy are kept or replaced
+        let bytes = [
+            &hex::decode("a11902d1a2").unwrap()[..],
+            &unchecked_text(POLICY_ID), &[0xa1], &unchecked_text("A"), &details("first"),
+            &unchecked_text(POLICY_ID), &[0xa1], &unchecked_text("B"), &details("second"),
+        ].concat();
+        check_duplicates(&bytes, Key::Str(POLICY_ID.to_owned()), vec![asset(POLICY_ID, "A", "first")], vec![asset(POLICY_ID, "B", "second")]);
+        // {721: {POLICY_ID: {"A": "first", "A": "second", "B": "third"}}}
+        let bytes = [
+            &hex::decode("a11902d1a1").unwrap()[..],
+            &unchecked_text(POLICY_ID), &[0xa3],
+            &unchecked_text("A"), &details("first"), &unchecked_text("A"), &details("second"), &unchecked_text("B"), &details("third"),
+        ].concat();
+        check_duplicates(&bytes, Key::Str(String::from("A")), vec![asset(POLICY_ID, "A", "first"), asset(POLICY_ID, "B", "third")], vec![asset(POLICY_ID, "A", "second"), asset(POLICY_ID, "B", "third")]);
+    }
+
+    #[test]
+    fn duplicate_v2_keys() {
+        let policy_id = hex::decode(POLICY_ID).unwrap();
+        let label = |data: Vec<u8>| [&hex::decode("a11902d1a2").unwrap()[..], &unchecked_text("data"), &data, &unchecked_text(VERSION_KEY), &[0x02]].concat();
+        // {policy_id: {h'01': "first"}, policy_id: {h'02': "second"}}
+        let bytes = label([
+            &[0xa2][..],
+            &unchecked_bytes(&policy_id), &[0xa1], &unchecked_bytes(&[1]), &details("first"),
+            &unchecked_bytes(&policy_id), &[0xa1], &unchecked_bytes(&[2]), &details("second"),
+        ].concat());
+        check_duplicates(&bytes, Key::Bytes(policy_id.clone()), vec![asset(POLICY_ID, "01", "first")], vec![asset(POLICY_ID, "02", "second")]);
+        // {policy_id: {h'01': "first", h'01': "second", h'02': "third"}}
+        let bytes = label([
+            &[0xa1][..],
+            &unchecked_bytes(&policy_id), &[0xa3],
+            &unchecked_bytes(&[1]), &details("first"), &unchecked_bytes(&[1]), &details("second"), &unchecked_bytes(&[2]), &details("third"),
+        ].concat());
+        check_duplicates(&bytes, Key::Bytes(vec![1]), vec![asset(POLICY_ID, "01", "first"), asset(POLICY_ID, "02", "third")], vec![asset(POLICY_ID, "01", "second"), asset(POLICY_ID, "02", "third")]);
+    }
+
+    #[test]
+    fn constructors_enforce_the_limit() {
+        assert!(TransactionMetadatum::new_text("x".repeat(64)).is_ok());
+        assert_too_long(&TransactionMetadatum::new_text("x".repeat(65)).unwrap_err(), 65);
+        assert!(TransactionMetadatum::new_bytes(vec![0; 64]).is_ok());
+        assert_too_long(&TransactionMetadatum::new_bytes(vec![0; 65]).unwrap_err(), 65);
+        // UTF-8 bytes, not characters
+        assert_too_long(&String64::new("é".repeat(33)).unwrap_err(), 66);
+        let mut data = BTreeMap::new();
+        data.insert(vec![1; 28], BTreeMap::from([(vec![2; 65], MetadataDetails::new(String64::new(String::from("NFT 1")).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("x")).unwrap())))]));
+        let err = LabelMetadataV2::new(data).unwrap_err();
+        assert_too_long(&err, 65);
+        assert_eq!(err.path(), [PathSegment::Field(String::from("data")), PathSegment::PolicyId(hex::encode([1; 28])), PathSegment::AssetName(hex::encode([2; 65]))]);
+    }
+
+    #[test]
+    fn encoding_rejects_values_built_directly() {
+        let mut metadata = Metadata::from_bytes(metadata_with_other_property()).unwrap();
+        if let LabelMetadata::LabelMetadataV1(label_metadata_v1) = &mut metadata.key_721 {
+            let details = label_metadata_v1.data.values_mut().next().unwrap().values_mut().next().unwrap();
+            details.other_properties.insert(String::from("x"), TransactionMetadatum::Text("x".repeat(65)));
+        }
+        let err = metadata.to_bytes().unwrap_err();
+        assert!(err.to_string().contains("metadata text or bytes too long: 65 not in range 0 - 64"), "{}", err);
+        assert!(TransactionMetadatum::Bytes(vec![0; 65]).to_bytes().is_err());
+        assert!(TransactionMetadatum::List(vec![TransactionMetadatum::Text("x".repeat(65))]).to_bytes().is_err());
+    }
+
+    #[test]
+    fn decoding_rejects_long_other_properties() {
+        let bytes = metadata_with_other_property();
+        // "x": [1] becomes "x": "xx...x"
+        let long_text = unchecked_text(&"x".repeat(65));
+        let err = Metadata::from_bytes(replace(&bytes, &[0x61, 0x78, 0x81, 0x01], &[&[0x61, 0x78][..], &long_text].concat())).unwrap_err();
+        assert_too_long(&err, 65);
+        assert_eq!(err.path().last(), Some(&PathSegment::Field(String::from("x"))));
+        // the "x" key becomes 65 bytes long
+        let long_key = [&long_text[..], &[0x81, 0x01]].concat();
+        let err = Metadata::from_bytes(replace(&bytes, &[0x61, 0x78, 0x81, 0x01], &long_key)).unwrap_err();
+        assert_too_long(&err, 65);
+    }
+
+    #[test]
+    fn decoding_rejects_long_policy_ids_and_asset_names() {
+        assert!(Metadata::from_bytes(metadata_v2(&[1; 28], b"NFT1")).is_ok());
+        let err = Metadata::from_bytes(metadata_v2(&[1; 65], b"NFT1")).unwrap_err();
+        assert_too_long(&err, 65);
+        assert_eq!(err.path(), [PathSegment::Field(String::from("721")), PathSegment::Field(String::from("data")), PathSegment::PolicyId(hex::encode([1; 65]))]);
+        let err = Metadata::from_bytes(metadata_v2(&[1; 28], &[2; 65])).unwrap_err();
+        assert_too_long(&err, 65);
+        assert_eq!(err.path().last(), Some(&PathSegment::AssetName(hex::encode([2; 65]))));
+    }
+
+    // {721: {"data": {}, "version": version}}
+    fn label_with_version(version: TransactionMetadatum) -> Vec<u8> {
+        let label = TransactionMetadatum::new_map(vec![
+            (TransactionMetadatum::new_text(String::from("data")).unwrap(), TransactionMetadatum::new_map(Vec::new())),
+            (TransactionMetadatum::new_text(String::from(VERSION_KEY)).unwrap(), version),
+        ]);
+        [&hex::decode("a11902d1").unwrap()[..], &label.to_bytes().unwrap()].concat()
+    }
+
+    #[test]
+    fn newer_versions_are_kept_as_unknown() {
+        let newer = [TransactionMetadatum::new_int(3), TransactionMetadatum::new_int(10), TransactionMetadatum::new_text(String::from("3.0")).unwrap()];
+        for version in newer {
+            let bytes = label_with_version(version.clone());
+            let metadata = Metadata::from_bytes(bytes.clone()).unwrap();
+            assert!(matches!(&metadata.key_721, LabelMetadata::Unknown { version: found, .. } if *found == version), "{:?}", version);
+            assert_eq!(metadata.to_bytes().unwrap(), bytes);
+            assert!(metadata.key_721.interpret_as_v2().unwrap().is_some());
+        }
+        for version in [TransactionMetadatum::new_int(2), TransactionMetadatum::new_text(String::from("2.0")).unwrap()] {
+            assert!(matches!(Metadata::from_bytes(label_with_version(version.clone())).unwrap().key_721, LabelMetadata::LabelMetadataV2(_)), "{:?}", version);
+        }
+    }
+
+    #[test]
+    fn invalid_versions_are_errors() {
+        let invalid = [
+            TransactionMetadatum::new_int(0),
+            TransactionMetadatum::new_int(-1),
+            TransactionMetadatum::new_text(String::from("banana")).unwrap(),
+            TransactionMetadatum::new_text(String::from("1")).unwrap(),
+            TransactionMetadatum::new_text(String::from("3")).unwrap(),
+            TransactionMetadatum::new_text(String::from("03.0")).unwrap(),
+            TransactionMetadatum::new_text(String::from("+3.0")).unwrap(),
+            TransactionMetadatum::new_text(String::from("2.5")).unwrap(),
+        ];
+        for version in invalid {
+            let err = Metadata::from_bytes(label_with_version(version.clone())).unwrap_err();
+            assert!(matches!(err.failure(), DeserializeFailure::UnsupportedVersion(_)), "{:?}: {}", version, err);
+            assert_eq!(err.path(), [PathSegment::Field(String::from("721")), PathSegment::Field(String::from(VERSION_KEY))]);
+            let decoded = Metadata::from_bytes_diagnostic(label_with_version(version.clone()));
+            assert!(matches!(decoded.errors[0].failure(), DeserializeFailure::UnsupportedVersion(_)), "{:?}", version);
+            assert!(!matches!(decoded.value.map(|value| value.key_721), Some(LabelMetadata::Unknown { .. })), "{:?}", version);
+        }
+        let err = Metadata::from_bytes(label_with_version(TransactionMetadatum::new_list(Vec::new()))).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::NoVariantMatched), "{}", err);
+    }
+
+    fn label_metadata_v1(policy_ids: &[&str], version: Option<VersionFormat>) -> LabelMetadata {
+        let details = MetadataDetails::new(String64::new(String::from("NFT")).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("x")).unwrap()));
+        let mut assets = BTreeMap::new();
+        assets.insert(String64::new(String::from("NFT")).unwrap(), details);
+        let mut label_metadata_v1 = LabelMetadataV1::new(policy_ids.iter().map(|policy_id| (String64::new(policy_id.to_string()).unwrap(), assets.clone())).collect());
+        label_metadata_v1.version = version;
+        LabelMetadata::new_label_metadata_v1(label_metadata_v1)
+    }
+
+    #[test]
+    fn conversions_between_versions() {
+        for (version, version_v2) in [(None, VersionFormat::Uint), (Some(VersionFormat::Uint), VersionFormat::Uint), (Some(VersionFormat::Text), VersionFormat::Text)] {
+            let label_metadata_v1 = label_metadata_v1(&[POLICY_ID], version);
+            let label_metadata_v2 = label_metadata_v1.convert_to_v2().unwrap();
+            assert_eq!(label_metadata_v2.version, version_v2);
+            assert_eq!(label_metadata_v2.data.keys().cloned().collect::<Vec<_>>(), vec![hex::decode(POLICY_ID).unwrap()]);
+            assert!(label_metadata_v2.data[&hex::decode(POLICY_ID).unwrap()].contains_key(b"NFT".as_slice()));
+            let label_metadata_v2 = LabelMetadata::new_label_metadata_v2(label_metadata_v2);
+            assert_eq!(label_metadata_v2.convert_to_v2().unwrap().to_bytes().unwrap(), label_metadata_v2.to_bytes().unwrap());
+            // the version format is kept, a version 1 without version field gets a number
+            let back = label_metadata_v2.convert_to_v1().unwrap();
+            assert_eq!(back.version, Some(version_v2));
+            let mut expected = label_metadata_v1.convert_to_v1().unwrap();
+            expected.version = Some(version_v2);
+            assert_eq!(back.to_bytes().unwrap(), expected.to_bytes().unwrap());
+        }
+    }
+
+    #[test]
+    fn conversion_errors() {
+        let err = label_metadata_v1(&["not hex"], None).convert_to_v2().unwrap_err();
+        assert_eq!(err.path(), [PathSegment::PolicyId(String::from("not hex"))]);
+        let err = label_metadata_v1(&[POLICY_ID, &POLICY_ID.to_uppercase()], None).convert_to_v2().unwrap_err();
+        assert!(err.to_string().contains("are the same policy id in version 2"), "{}", err);
+        // asset names that aren't UTF-8 or policy ids too long once in hex
+        for (policy_id, asset_name) in [(hex::decode(POLICY_ID).unwrap(), vec![0xff]), (vec![0xab; 33], b"NFT".to_vec())] {
+            let mut assets = BTreeMap::new();
+            assets.insert(asset_name.clone(), MetadataDetails::new(String64::new(String::from("NFT")).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("x")).unwrap())));
+            let mut data = BTreeMap::new();
+            data.insert(policy_id.clone(), assets);
+            let err = LabelMetadata::new_label_metadata_v2(LabelMetadataV2::new(data).unwrap()).convert_to_v1().unwrap_err();
+            assert_eq!(err.path()[0], PathSegment::PolicyId(hex::encode(&policy_id)), "{}", err);
+        }
+        let unknown = Metadata::from_bytes(label_with_version(TransactionMetadatum::new_int(3))).unwrap().key_721;
+        for err in [unknown.convert_to_v1().unwrap_err(), unknown.convert_to_v2().unwrap_err()] {
+            assert!(matches!(err.failure(), DeserializeFailure::UnsupportedVersion(Key::Uint(3))), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// Encoded sizes, to pack as many assets as possible in a transaction.
+// Sizes are those of to_bytes(), other encodings (see encoding.rs) can differ.
+
+use super::*;
+use cbor_event::Sz;
+
+// maxTxSize of the mainnet protocol parameters. The metadata is only part of the transaction.
+pub const MAX_TX_SIZE: usize = 16384;
+
+// counts the bytes written instead of keeping them
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+pub trait EncodedSize {
+    fn encoded_size(&self) -> Result<usize, SerializeError>;
+
+    fn fits_budget(&self, budget: usize) -> Result<bool, SerializeError> {
+        Ok(self.encoded_size()? <= budget)
+    }
+}
+
+impl<T: cbor_event::se::Serialize> EncodedSize for T {
+    fn encoded_size(&self) -> Result<usize, SerializeError> {
+        let mut serializer = Serializer::new(ByteCounter(0));
+        self.serialize(&mut serializer)?;
+        Ok(serializer.finalize().0)
+    }
+}
+
+// size of the type and length / value header
+fn head_size(value: u64) -> usize {
+    1 + Sz::canonical(value).bytes_following()
+}
+
+fn text_size(text: &str) -> usize {
+    head_size(text.len() as u64) + text.len()
+}
+
+fn bytes_size(bytes: &[u8]) -> usize {
+    head_size(bytes.len() as u64) + bytes.len()
+}
+
+// the map header can get longer with one more entry
+fn map_growth(len: usize) -> usize {
+    head_size(len as u64 + 1) - head_size(len as u64)
+}
+
+// the size change of replacing an asset, it can be negative
+fn replaced_size(existing: &MetadataDetails, details: &MetadataDetails) -> Result<isize, SerializeError> {
+    Ok(details.encoded_size()? as isize - existing.encoded_size()? as isize)
+}
+
+impl LabelMetadataV1 {
+    // how much the encoded size grows when inserting this asset
+    pub fn added_size(&self, policy_id: &PolicyIdV1, asset_name: &AssetNameV1, details: &MetadataDetails) -> Result<isize, SerializeError> {
+        let details_size = details.encoded_size()?;
+        let added = match self.data.get(policy_id) {
+            Some(assets) => match assets.get(asset_name) {
+                Some(existing) => return replaced_size(existing, details),
+                None => map_growth(assets.len()) + text_size(asset_name.get()) + details_size,
+            },
+            None => {
+                let label_len = self.data.len() + self.version.map_or(0, |_| 1);
+                map_growth(label_len) + text_size(policy_id.get()) + head_size(1) + text_size(asset_name.get()) + details_size
+            },
+        };
+        Ok(added as isize)
+    }
+}
+
+impl LabelMetadataV2 {
+    // how much the encoded size grows when inserting this asset
+    pub fn added_size(&self, policy_id: &PolicyIdV2, asset_name: &AssetNameV2, details: &MetadataDetails) -> Result<isize, SerializeError> {
+        let details_size = details.encoded_size()?;
+        let added = match self.data.get(policy_id) {
+            Some(assets) => match assets.get(asset_name) {
+                Some(existing) => return replaced_size(existing, details),
+                None => map_growth(assets.len()) + bytes_size(asset_name) + details_size,
+            },
+            None => map_growth(self.data.len()) + bytes_size(policy_id) + head_size(1) + bytes_size(asset_name) + details_size,
+        };
+        Ok(added as isize)
+    }
+}
+
+impl LabelMetadata {
+    // Same as the added_size of the version. Version 1 policy ids are matched whatever the case
+    // of their hex (new ones are lowercase) and asset names must be UTF-8 text there.
+    pub fn added_size(&self, policy_id: &[u8], asset_name: &[u8], details: &MetadataDetails) -> Result<isize, SerializeError> {
+        match self {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => {
+                let policy_id_v1 = match label_metadata_v1.data.keys().find(|policy_id_v1| hex::decode(policy_id_v1.get()).ok().as_deref() == Some(policy_id)) {
+                    Some(policy_id_v1) => policy_id_v1.clone(),
+                    None => String64::new(hex::encode(policy_id)).map_err(|e| cbor_event::Error::CustomError(e.to_string()))?,
+                };
+                let asset_name = std::str::from_utf8(asset_name)
+                    .map_err(|_| cbor_event::Error::CustomError(format!("asset name 0x{} is not UTF-8 so it can't be used in version 1", hex::encode(asset_name))))?;
+                let asset_name = String64::new(asset_name.to_owned()).map_err(|e| cbor_event::Error::CustomError(e.to_string()))?;
+                label_metadata_v1.added_size(&policy_id_v1, &asset_name, details)
+            },
+            LabelMetadata::LabelMetadataV2(label_metadata_v2) => label_metadata_v2.added_size(&policy_id.to_vec(), &asset_name.to_vec(), details),
+            LabelMetadata::Unknown { .. } => Err(cbor_event::Error::CustomError(String::from("the layout of an unknown CIP-25 version isn't known")).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY_ID: [u8; 28] = [0xab; 28];
+
+    fn details(name: &str) -> MetadataDetails {
+        MetadataDetails::new(String64::new(String::from(name)).unwrap(), String64OrArrString64::new_string64(String64::new(String::from("ipfs://image")).unwrap()))
+    }
+
+    fn insert(label_metadata: &mut LabelMetadata, policy_id: &[u8], asset_name: &[u8], details: MetadataDetails) {
+        match label_metadata {
+            LabelMetadata::LabelMetadataV1(label_metadata_v1) => {
+                let policy_id_v1 = label_metadata_v1.data.keys().find(|policy_id_v1| hex::decode(policy_id_v1.get()).unwrap() == policy_id).cloned();
+                let policy_id_v1 = policy_id_v1.unwrap_or_else(|| String64::new(hex::encode(policy_id)).unwrap());
+                let asset_name = String64::new(String::from_utf8(asset_name.to_vec()).unwrap()).unwrap();
+                label_metadata_v1.data.entry(policy_id_v1).or_default().insert(asset_name, details);
+            },
+            LabelMetadata::LabelMetadataV2(label_metadata_v2) => {
+                label_metadata_v2.data.entry(policy_id.to_vec()).or_default().insert(asset_name.to_vec(), details);
+            },
+            LabelMetadata::Unknown { .. } => unreachable!(),
+        }
+    }
+
+    // added_size against the size of the encoded metadata before and after inserting
+    fn check_added_size(label_metadata: &mut LabelMetadata, policy_id: &[u8], asset_name: &[u8], details: MetadataDetails) {
+        let before = label_metadata.to_bytes().unwrap().len();
+        assert_eq!(label_metadata.encoded_size().unwrap(), before);
+        let added = label_metadata.added_size(policy_id, asset_name, &details).unwrap();
+        insert(label_metadata, policy_id, asset_name, details);
+        let after = label_metadata.to_bytes().unwrap().len();
+        assert_eq!(label_metadata.encoded_size().unwrap(), after);
+        assert_eq!(before as isize + added, after as isize, "{} {}", hex::encode(policy_id), hex::encode(asset_name));
+    }
+
+    fn versions() -> Vec<LabelMetadata> {
+        let mut versioned = LabelMetadataV1::new(BTreeMap::new());
+        versioned.version = Some(VersionFormat::Text);
+        vec![
+            LabelMetadata::new_label_metadata_v1(LabelMetadataV1::new(BTreeMap::new())),
+            LabelMetadata::new_label_metadata_v1(versioned),
+            LabelMetadata::new_label_metadata_v2(LabelMetadataV2::new(BTreeMap::new()).unwrap()),
+        ]
+    }
+
+    #[test]
+    fn encoded_sizes() {
+        let mut details = details("NFT");
+        details.description = Some(String64OrArrString64::new_arr_string64(vec![String64::new("a".repeat(64)).unwrap(); 3]));
+        details.other_properties.insert(String::from("x"), TransactionMetadatum::new_int(-1000));
+        assert_eq!(details.encoded_size().unwrap(), details.to_bytes().unwrap().len());
+        for mut label_metadata in versions() {
+            insert(&mut label_metadata, &POLICY_ID, b"NFT", details.clone());
+            let metadata = Metadata::new(label_metadata.clone());
+            assert_eq!(label_metadata.encoded_size().unwrap(), label_metadata.to_bytes().unwrap().len());
+            assert_eq!(metadata.encoded_size().unwrap(), metadata.to_bytes().unwrap().len());
+            assert!(metadata.fits_budget(metadata.encoded_size().unwrap()).unwrap());
+            assert!(!metadata.fits_budget(metadata.encoded_size().unwrap() - 1).unwrap());
+        }
+    }
+
+    #[test]
+    fn added_sizes() {
+        for mut label_metadata in versions() {
+            check_added_size(&mut label_metadata, &POLICY_ID, b"NFT", details("NFT"));
+            check_added_size(&mut label_metadata, &POLICY_ID, b"other", details("other"));
+            check_added_size(&mut label_metadata, &[0xcd; 28], b"NFT", details("NFT"));
+            // replacing an asset, bigger then smaller
+            check_added_size(&mut label_metadata, &POLICY_ID, b"NFT", details(&"a".repeat(64)));
+            check_added_size(&mut label_metadata, &POLICY_ID, b"NFT", details("a"));
+        }
+    }
+
+    #[test]
+    fn added_sizes_at_map_header_boundaries() {
+        // the asset map header grows from 1 to 2 bytes at 24 entries and from 2 to 3 at 256
+        for mut label_metadata in versions() {
+            for index in 0..257 {
+                check_added_size(&mut label_metadata, &POLICY_ID, format!("{:03}", index).as_bytes(), details("NFT"));
+            }
+        }
+        // and so does the policy map, which holds the version too in version 1
+        for mut label_metadata in versions() {
+            for index in 0..26u8 {
+                check_added_size(&mut label_metadata, &[index; 28], b"NFT", details("NFT"));
+            }
+        }
+    }
+
+    #[test]
+    fn version_1_policy_ids_keep_their_case() {
+        let mut data = BTreeMap::new();
+        data.insert(String64::new(hex::encode_upper(POLICY_ID)).unwrap(), BTreeMap::new());
+        let mut label_metadata = LabelMetadata::new_label_metadata_v1(LabelMetadataV1::new(data));
+        check_added_size(&mut label_metadata, &POLICY_ID, b"NFT", details("NFT"));
+        assert!(label_metadata.added_size(&POLICY_ID, &[0xff], &details("NFT")).is_err());
+        assert!(LabelMetadata::new_unknown(TransactionMetadatum::new_int(3), TransactionMetadatum::new_map(Vec::new())).added_size(&POLICY_ID, b"NFT", &details("NFT")).is_err());
+    }
+}
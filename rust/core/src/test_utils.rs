@@ -0,0 +1,37 @@
+// helpers shared by the tests of the modules
+
+use super::*;
+
+pub const POLICY_ID: &str = "7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373";
+
+// the encoding of texts and bytes without the length limit, as found on-chain before it
+pub fn unchecked_text(text: &str) -> Vec<u8> {
+    let mut serializer = Serializer::new_vec();
+    serializer.write_text(text).unwrap();
+    serializer.finalize()
+}
+
+pub fn unchecked_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut serializer = Serializer::new_vec();
+    serializer.write_bytes(bytes).unwrap();
+    serializer.finalize()
+}
+
+// the same as hex, to be spliced in hand written CBOR
+pub fn text_hex(text: &str) -> String {
+    hex::encode(unchecked_text(text))
+}
+
+pub fn bytes_hex(bytes: &[u8]) -> String {
+    hex::encode(unchecked_bytes(bytes))
+}
+
+// {721: {"data": {policy_id: {asset_name: {"name": "NFT 1", "image": "ipfs://patate"}}}, "version": 2}}
+pub fn metadata_v2(policy_id: &[u8], asset_name: &[u8]) -> Vec<u8> {
+    let details = format!("a2{}{}{}{}", text_hex("name"), text_hex("NFT 1"), text_hex("image"), text_hex("ipfs://patate"));
+    hex::decode(format!("a11902d1a2{}a1{}a1{}{}{}02", text_hex("data"), bytes_hex(policy_id), bytes_hex(asset_name), details, text_hex("version"))).unwrap()
+}
+
+pub fn assert_too_long(err: &DeserializeError, found: usize) {
+    assert!(matches!(err.failure(), DeserializeFailure::RangeCheck{ found: f, min: Some(0), max: Some(64) } if *f == found), "{}", err);
+}
@@ -0,0 +1,497 @@
+// Minimal decoding of Shelley-era-or-later transactions and blocks. Only the parts needed
+// for CIP-25 are decoded (auxiliary data metadata, the mint field and validity), everything
+// else is skipped over without being checked.
+//
+// transaction    = [body, witness_set, auxiliary_data / null]             (Shelley - Mary)
+//                / [body, witness_set, bool, auxiliary_data / null]       (Alonzo onwards)
+// auxiliary_data = metadata                                               (Shelley)
+//                / [metadata, [* native_script]]                          (Shelley-MA)
+//                / #6.259({ ? 0: metadata, * uint => any })               (Alonzo onwards)
+// block          = [header, [* body], [* witness_set], { * uint => auxiliary_data }, ? [* uint]]
+
+use super::*;
+use std::io::{Seek, SeekFrom};
+
+pub type Mint = BTreeMap<PolicyIdV2, BTreeMap<AssetNameV2, i64>>;
+
+const MINT_KEY: u64 = 9;
+
+const AUXILIARY_DATA_TAG: u64 = 259;
+
+#[derive(Clone, Debug)]
+pub struct ExtractedTransaction {
+    // None when there is no auxiliary data or it has no 721 label
+    pub metadata: Option<Metadata>,
+    // None when the body has no mint field
+    pub mint: Option<Mint>,
+    // false for transactions that failed phase-2 validation (their mint is not applied)
+    pub is_valid: bool,
+}
+
+impl ExtractedTransaction {
+    // policy ids / asset names minted (or burnt) by the transaction
+    pub fn minted_assets(&self) -> Vec<(PolicyIdV2, AssetNameV2)> {
+        self.mint
+            .iter()
+            .flat_map(|mint| mint.iter())
+            .flat_map(|(policy_id, assets)| assets.keys().map(move |asset_name| (policy_id.clone(), asset_name.clone())))
+            .collect()
+    }
+}
+
+pub fn extract_from_transaction(bytes: Vec<u8>) -> Result<ExtractedTransaction, DeserializeError> {
+    let mut raw = Deserializer::from(std::io::Cursor::new(bytes));
+    read_transaction(&mut raw).map_err(|e| e.with_offset(raw.as_mut_ref().position()))
+}
+
+pub fn extract_from_auxiliary_data(bytes: Vec<u8>) -> Result<Option<Metadata>, DeserializeError> {
+    let mut raw = Deserializer::from(std::io::Cursor::new(bytes));
+    read_auxiliary_data(&mut raw).map_err(|e| e.with_offset(raw.as_mut_ref().position()))
+}
+
+// One entry per transaction in the block, in order. An error in one transaction's
+// mint or auxiliary data doesn't prevent the others from being extracted.
+pub fn extract_from_block(bytes: Vec<u8>) -> Result<Vec<Result<ExtractedTransaction, DeserializeError>>, DeserializeError> {
+    let mut raw = Deserializer::from(std::io::Cursor::new(bytes));
+    read_block(&mut raw).map_err(|e| e.with_offset(raw.as_mut_ref().position()))
+}
+
+fn read_transaction<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<ExtractedTransaction, DeserializeError> {
+    (|| -> Result<_, DeserializeError> {
+        let len = raw.array()?;
+        let mut read_len = CBORReadLen::new(len);
+        read_len.read_elems(3)?;
+        let mint = read_transaction_body(raw).map_err(|e| e.annotate("body").annotate_path(PathSegment::Field(String::from("body"))))?;
+        skip(raw)?;
+        let mut is_valid = true;
+        let mut metadata = None;
+        let mut auxiliary_data_present = true;
+        if raw.cbor_type()? == CBORType::Special {
+            match raw.special()? {
+                CBORSpecial::Bool(valid) => {
+                    read_len.read_elems(1)?;
+                    is_valid = valid;
+                },
+                CBORSpecial::Null => auxiliary_data_present = false,
+                _ => return Err(DeserializeFailure::ExpectedNull.into()),
+            }
+        }
+        if auxiliary_data_present {
+            metadata = read_nullable_auxiliary_data(raw).map_err(|e| e.annotate("auxiliary_data").annotate_path(PathSegment::Field(String::from("auxiliary_data"))))?;
+        }
+        read_len.finish()?;
+        if len == cbor_event::Len::Indefinite && raw.special()? != CBORSpecial::Break {
+            return Err(DeserializeFailure::EndingBreakMissing.into());
+        }
+        Ok(ExtractedTransaction {
+            metadata,
+            mint,
+            is_valid,
+        })
+    })().map_err(|e| e.annotate("Transaction"))
+}
+
+fn read_transaction_body<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Option<Mint>, DeserializeError> {
+    (|| -> Result<_, DeserializeError> {
+        let mut mint = None;
+        let len = raw.map()?;
+        let mut read = 0;
+        while match len { cbor_event::Len::Len(n) => read < n, cbor_event::Len::Indefinite => true, } {
+            match raw.cbor_type()? {
+                CBORType::UnsignedInteger => match raw.unsigned_integer()? {
+                    MINT_KEY => {
+                        if mint.is_some() {
+                            return Err(DeserializeFailure::DuplicateKey(Key::Uint(MINT_KEY)).into());
+                        }
+                        mint = Some(read_mint(raw).map_err(|e| e.annotate("mint").annotate_path(PathSegment::Field(MINT_KEY.to_string())))?);
+                    },
+                    _ => skip(raw)?,
+                },
+                CBORType::Special => match len {
+                    cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
+                    cbor_event::Len::Indefinite => match raw.special()? {
+                        CBORSpecial::Break => break,
+                        _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                    },
+                },
+                other_type => return Err(DeserializeFailure::UnexpectedKeyType(other_type).into()),
+            }
+            read += 1;
+        }
+        Ok(mint)
+    })().map_err(|e| e.annotate("TransactionBody"))
+}
+
+fn read_mint<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Mint, DeserializeError> {
+    let mut mint = BTreeMap::new();
+    let mint_len = raw.map()?;
+    while match mint_len { cbor_event::Len::Len(n) => mint.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+        if raw.cbor_type()? == CBORType::Special {
+            match raw.special()? {
+                CBORSpecial::Break => break,
+                _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+            }
+        }
+        let policy_id = raw.bytes()?;
+        let mut assets = BTreeMap::new();
+        let assets_len = raw.map()?;
+        while match assets_len { cbor_event::Len::Len(n) => assets.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+            if raw.cbor_type()? == CBORType::Special {
+                match raw.special()? {
+                    CBORSpecial::Break => break,
+                    _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                }
+            }
+            let asset_name = raw.bytes()?;
+            let quantity = match raw.cbor_type()? {
+                CBORType::UnsignedInteger => i64::try_from(raw.unsigned_integer()?).map_err(|_| cbor_event::Error::ExpectedI64)?,
+                _ => raw.negative_integer()?,
+            };
+            if assets.insert(asset_name.clone(), quantity).is_some() {
+                return Err(DeserializeFailure::DuplicateKey(Key::Bytes(asset_name)).into());
+            }
+        }
+        if mint.insert(policy_id.clone(), assets).is_some() {
+            return Err(DeserializeFailure::DuplicateKey(Key::Bytes(policy_id)).into());
+        }
+    }
+    Ok(mint)
+}
+
+fn read_nullable_auxiliary_data<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Option<Metadata>, DeserializeError> {
+    if raw.cbor_type()? == CBORType::Special {
+        if raw.special()? != CBORSpecial::Null {
+            return Err(DeserializeFailure::ExpectedNull.into());
+        }
+        return Ok(None);
+    }
+    read_auxiliary_data(raw)
+}
+
+fn read_auxiliary_data<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Option<Metadata>, DeserializeError> {
+    (|| -> Result<_, DeserializeError> {
+        match raw.cbor_type()? {
+            CBORType::Map => read_metadata(raw),
+            CBORType::Array => {
+                let len = raw.array()?;
+                let mut read_len = CBORReadLen::new(len);
+                read_len.read_elems(2)?;
+                let metadata = read_metadata(raw)?;
+                skip(raw)?;
+                read_len.finish()?;
+                if len == cbor_event::Len::Indefinite && raw.special()? != CBORSpecial::Break {
+                    return Err(DeserializeFailure::EndingBreakMissing.into());
+                }
+                Ok(metadata)
+            },
+            CBORType::Tag => {
+                let tag = raw.tag()?;
+                if tag != AUXILIARY_DATA_TAG {
+                    return Err(DeserializeFailure::TagMismatch{ found: tag, expected: AUXILIARY_DATA_TAG }.into());
+                }
+                let mut metadata = None;
+                let len = raw.map()?;
+                let mut read = 0;
+                while match len { cbor_event::Len::Len(n) => read < n, cbor_event::Len::Indefinite => true, } {
+                    match raw.cbor_type()? {
+                        CBORType::UnsignedInteger => match raw.unsigned_integer()? {
+                            0 => {
+                                if metadata.is_some() {
+                                    return Err(DeserializeFailure::DuplicateKey(Key::Uint(0)).into());
+                                }
+                                metadata = Some(read_metadata(raw)?);
+                            },
+                            _ => skip(raw)?,
+                        },
+                        CBORType::Special => match len {
+                            cbor_event::Len::Len(_) => return Err(DeserializeFailure::BreakInDefiniteLen.into()),
+                            cbor_event::Len::Indefinite => match raw.special()? {
+                                CBORSpecial::Break => break,
+                                _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                            },
+                        },
+                        other_type => return Err(DeserializeFailure::UnexpectedKeyType(other_type).into()),
+                    }
+                    read += 1;
+                }
+                Ok(metadata.flatten())
+            },
+            _ => Err(DeserializeFailure::NoVariantMatched.into()),
+        }
+    })().map_err(|e| e.annotate("AuxiliaryData"))
+}
+
+// None if the metadata has no 721 label. Metadata itself is permissive towards other labels
+// but fails when 721 is missing so we check for it first.
+fn read_metadata<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Option<Metadata>, DeserializeError> {
+    let initial_position = raw.as_mut_ref().stream_position().unwrap();
+    let has_721 = match TransactionMetadatum::deserialize(raw)? {
+        TransactionMetadatum::Map(labels) => labels.iter().any(|(label, _)| *label == TransactionMetadatum::Int(721)),
+        _ => return Err(DeserializeError::new("Metadata", DeserializeFailure::NoVariantMatched)),
+    };
+    if !has_721 {
+        return Ok(None);
+    }
+    raw.as_mut_ref().seek(SeekFrom::Start(initial_position)).unwrap();
+    Metadata::deserialize(raw).map(Some)
+}
+
+fn read_block<R: BufRead + Seek>(raw: &mut Deserializer<R>) -> Result<Vec<Result<ExtractedTransaction, DeserializeError>>, DeserializeError> {
+    (|| -> Result<_, DeserializeError> {
+        let mut len = raw.array()?;
+        // node clients commonly wrap blocks as [era, block]
+        if len == cbor_event::Len::Len(2) && raw.cbor_type()? == CBORType::UnsignedInteger {
+            raw.unsigned_integer()?;
+            len = raw.array()?;
+        }
+        let mut read_len = CBORReadLen::new(len);
+        read_len.read_elems(4)?;
+        skip(raw)?;
+        let mut mints = Vec::new();
+        let bodies_len = raw.array()?;
+        while match bodies_len { cbor_event::Len::Len(n) => mints.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+            if raw.cbor_type()? == CBORType::Special {
+                match raw.special()? {
+                    CBORSpecial::Break => break,
+                    _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                }
+            }
+            let index = mints.len();
+            mints.push(read_isolated(raw, read_transaction_body).map_err(|e| {
+                e.annotate(format!("transaction_bodies[{}]", index))
+                    .annotate_path(PathSegment::Index(index))
+                    .annotate_path(PathSegment::Field(String::from("transaction_bodies")))
+            }));
+        }
+        skip(raw)?;
+        let mut metadatas = BTreeMap::new();
+        let auxiliary_data_set_len = raw.map()?;
+        while match auxiliary_data_set_len { cbor_event::Len::Len(n) => metadatas.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+            if raw.cbor_type()? == CBORType::Special {
+                match raw.special()? {
+                    CBORSpecial::Break => break,
+                    _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                }
+            }
+            let index = raw.unsigned_integer()?;
+            let metadata = read_isolated(raw, read_auxiliary_data).map_err(|e| {
+                e.annotate(format!("auxiliary_data_set[{}]", index))
+                    .annotate_path(PathSegment::Index(index as usize))
+                    .annotate_path(PathSegment::Field(String::from("auxiliary_data_set")))
+            });
+            if metadatas.insert(index, metadata).is_some() {
+                return Err(DeserializeFailure::DuplicateKey(Key::Uint(index)).into());
+            }
+        }
+        let mut invalid_transactions = Vec::new();
+        if len == cbor_event::Len::Len(5) || (len == cbor_event::Len::Indefinite && raw.cbor_type()? != CBORType::Special) {
+            read_len.read_elems(1)?;
+            let invalid_len = raw.array()?;
+            while match invalid_len { cbor_event::Len::Len(n) => invalid_transactions.len() < n as usize, cbor_event::Len::Indefinite => true, } {
+                if raw.cbor_type()? == CBORType::Special {
+                    match raw.special()? {
+                        CBORSpecial::Break => break,
+                        _ => return Err(DeserializeFailure::EndingBreakMissing.into()),
+                    }
+                }
+                invalid_transactions.push(raw.unsigned_integer()?);
+            }
+        }
+        read_len.finish()?;
+        if len == cbor_event::Len::Indefinite && raw.special()? != CBORSpecial::Break {
+            return Err(DeserializeFailure::EndingBreakMissing.into());
+        }
+        Ok(mints
+            .into_iter()
+            .enumerate()
+            .map(|(index, mint)| {
+                let metadata = match metadatas.remove(&(index as u64)) {
+                    Some(metadata) => metadata?,
+                    None => None,
+                };
+                Ok(ExtractedTransaction {
+                    metadata,
+                    mint: mint?,
+                    is_valid: !invalid_transactions.contains(&(index as u64)),
+                })
+            })
+            .collect())
+    })().map_err(|e| e.annotate("Block"))
+}
+
+// Reads one item with the given decoder, making sure the whole item is consumed even when
+// decoding fails so the caller can carry on with the next one.
+fn read_isolated<R: BufRead + Seek, T>(
+    raw: &mut Deserializer<R>,
+    read: fn(&mut Deserializer<R>) -> Result<T, DeserializeError>,
+) -> Result<T, DeserializeError> {
+    let initial_position = raw.as_mut_ref().stream_position().unwrap();
+    match read(raw) {
+        Ok(value) => Ok(value),
+        Err(e) => {
+            let e = e.with_offset(raw.as_mut_ref().stream_position().unwrap());
+            raw.as_mut_ref().seek(SeekFrom::Start(initial_position)).unwrap();
+            skip(raw)?;
+            Err(e)
+        },
+    }
+}
+
+// Items skipped over are decoded recursively too. This is well above MAX_METADATUM_DEPTH
+// so that metadatums which can be decoded can be skipped along with the maps around them.
+const MAX_SKIP_DEPTH: usize = 256;
+
+// Skips over any CBOR item, also used by the diagnostics
+pub(crate) fn skip<R: BufRead>(raw: &mut Deserializer<R>) -> Result<(), DeserializeError> {
+    skip_nested(raw, 0)
+}
+
+fn skip_nested<R: BufRead>(raw: &mut Deserializer<R>, depth: usize) -> Result<(), DeserializeError> {
+    match raw.cbor_type()? {
+        CBORType::Array | CBORType::Map | CBORType::Tag if depth == MAX_SKIP_DEPTH => {
+            return Err(DeserializeError::new("nesting", DeserializeFailure::RangeCheck{ found: depth + 1, min: None, max: Some(MAX_SKIP_DEPTH as isize) }));
+        },
+        CBORType::UnsignedInteger => {
+            raw.unsigned_integer()?;
+        },
+        CBORType::NegativeInteger => {
+            raw.negative_integer_sz()?;
+        },
+        CBORType::Bytes => {
+            raw.bytes()?;
+        },
+        CBORType::Text => {
+            raw.text()?;
+        },
+        CBORType::Array => {
+            let len = raw.array()?;
+            skip_elems(raw, len, 1, depth + 1)?;
+        },
+        CBORType::Map => {
+            let len = raw.map()?;
+            skip_elems(raw, len, 2, depth + 1)?;
+        },
+        CBORType::Tag => {
+            raw.tag()?;
+            skip_nested(raw, depth + 1)?;
+        },
+        CBORType::Special => {
+            raw.special()?;
+        },
+    }
+    Ok(())
+}
+
+fn skip_elems<R: BufRead>(raw: &mut Deserializer<R>, len: cbor_event::Len, items_per_elem: usize, depth: usize) -> Result<(), DeserializeError> {
+    let mut read = 0;
+    while match len { cbor_event::Len::Len(n) => read < n, cbor_event::Len::Indefinite => true, } {
+        if raw.cbor_type()? == CBORType::Special && raw.special_break()? {
+            break;
+        }
+        for _ in 0..items_per_elem {
+            skip_nested(raw, depth)?;
+        }
+        read += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::POLICY_ID;
+
+    fn fixture(hex: &str) -> Vec<u8> {
+        hex::decode(hex.trim()).unwrap()
+    }
+
+    // the 721 metadata of every fixture, as a Shelley auxiliary data
+    fn v1_metadata() -> Vec<u8> {
+        fixture(include_str!("../tests/fixtures/v1.hex"))
+    }
+
+    // a body minting one NFT1 of POLICY_ID: {9: {policy_id: {"NFT1": 1}}}
+    fn minting_body() -> Vec<u8> {
+        fixture(&format!("a109a1581c{}a1444e46543101", POLICY_ID))
+    }
+
+    fn assert_v1_metadata(metadata: Option<Metadata>) {
+        assert_eq!(metadata.expect("721 metadata").to_bytes().unwrap(), v1_metadata());
+    }
+
+    #[test]
+    fn shelley_transaction() {
+        let extracted = extract_from_transaction(fixture(include_str!("../tests/fixtures/tx_shelley.hex"))).unwrap();
+        assert!(extracted.is_valid);
+        assert_v1_metadata(extracted.metadata);
+    }
+
+    #[test]
+    fn shelley_ma_auxiliary_data() {
+        assert_v1_metadata(extract_from_auxiliary_data(fixture(include_str!("../tests/fixtures/aux_shelley_ma.hex"))).unwrap());
+        assert_v1_metadata(extract_from_auxiliary_data(v1_metadata()).unwrap());
+    }
+
+    #[test]
+    fn alonzo_transaction() {
+        let extracted = extract_from_transaction(fixture(include_str!("../tests/fixtures/tx_alonzo.hex"))).unwrap();
+        assert!(extracted.is_valid);
+        assert_v1_metadata(extracted.metadata);
+        // #6.259({}) has no metadata at all
+        assert!(extract_from_auxiliary_data(fixture("d90103a0")).unwrap().is_none());
+        let err = extract_from_auxiliary_data(fixture("d90104a0")).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::TagMismatch{ found: 260, expected: AUXILIARY_DATA_TAG }), "{}", err);
+    }
+
+    #[test]
+    fn transaction_without_auxiliary_data() {
+        let extracted = extract_from_transaction(fixture(include_str!("../tests/fixtures/tx_no_aux.hex"))).unwrap();
+        assert!(extracted.metadata.is_none());
+        assert!(extracted.mint.is_none());
+    }
+
+    #[test]
+    fn transaction_mint() {
+        let mut bytes = vec![0x84];
+        bytes.extend(minting_body());
+        bytes.extend([0xa0, 0xf4, 0xf6]);
+        let extracted = extract_from_transaction(bytes).unwrap();
+        assert!(!extracted.is_valid);
+        assert_eq!(extracted.minted_assets(), [(hex::decode(POLICY_ID).unwrap(), b"NFT1".to_vec())]);
+    }
+
+    #[test]
+    fn malformed_indefinite_lengths_are_errors() {
+        // an indefinite mint holding true instead of a policy id or a break
+        let err = extract_from_transaction(fixture("84a109bff5ffa0f5f6")).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::EndingBreakMissing), "{}", err);
+        // the same in the bodies of a block
+        let err = extract_from_block(fixture("8480 9ff5ff 80 a0".replace(' ', "").as_str())).unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::EndingBreakMissing), "{}", err);
+    }
+
+    #[test]
+    fn block_with_missing_and_invalid_auxiliary_data() {
+        // [header, [body 0, body 1, body 2], [], {0: metadata, 2: "x"}, [1]]
+        let mut bytes = vec![0x85, 0x80, 0x83];
+        bytes.extend(minting_body());
+        bytes.extend([0xa0, 0xa0, 0x80, 0xa2, 0x00]);
+        bytes.extend(v1_metadata());
+        bytes.extend([0x02, 0x61, 0x78, 0x81, 0x01]);
+        let extracted = extract_from_block(bytes.clone()).unwrap();
+        assert_eq!(extracted.len(), 3);
+        let first = extracted[0].as_ref().unwrap();
+        assert!(first.is_valid);
+        assert_eq!(first.minted_assets().len(), 1);
+        assert_v1_metadata(first.metadata.clone());
+        let second = extracted[1].as_ref().unwrap();
+        assert!(!second.is_valid);
+        assert!(second.metadata.is_none());
+        let err = extracted[2].as_ref().unwrap_err();
+        assert!(matches!(err.failure(), DeserializeFailure::NoVariantMatched), "{}", err);
+        assert_eq!(err.path(), [PathSegment::Field(String::from("auxiliary_data_set")), PathSegment::Index(2)]);
+        // the same block wrapped as [era, block]
+        let wrapped = [vec![0x82, 0x06], bytes].concat();
+        assert_eq!(extract_from_block(wrapped).unwrap().len(), 3);
+    }
+}
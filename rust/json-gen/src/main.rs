@@ -1,10 +1,17 @@
 use core::*;
+use core::royalty::{RoyaltyMetadata, TokenMetadata};
 
 fn main() {
+    // optional fields are omitted in CIP-25 JSON rather than being null
+    let settings = schemars::gen::SchemaSettings::draft07().with(|s| {
+        s.option_nullable = false;
+        s.option_add_null_type = false;
+    });
     macro_rules! gen_json_schema {
         ($name:ident) =>  {
             let dest_path = std::path::Path::new(&"schemas").join(&format!("{}.json", stringify!($name)));
-            std::fs::write(&dest_path, serde_json::to_string_pretty(&schemars::schema_for!($name)).unwrap()).unwrap();
+            let schema = settings.clone().into_generator().into_root_schema_for::<$name>();
+            std::fs::write(&dest_path, serde_json::to_string_pretty(&schema).unwrap()).unwrap();
         }
     }
     let schema_path = std::path::Path::new(&"schemas");
@@ -13,9 +20,12 @@ fn main() {
     }
     gen_json_schema!(FilesDetails);
     gen_json_schema!(LabelMetadata);
+    gen_json_schema!(LabelMetadataV1);
     gen_json_schema!(LabelMetadataV2);
     gen_json_schema!(Metadata);
     gen_json_schema!(MetadataDetails);
+    gen_json_schema!(RoyaltyMetadata);
     gen_json_schema!(String64);
     gen_json_schema!(String64OrArrString64);
+    gen_json_schema!(TokenMetadata);
 }
@@ -10,6 +10,89 @@ pub type PolicyIdV1 = String64;
 
 pub type PolicyIdV2 = Vec<u8>;
 
+// thrown by every from_bytes / from_json so callers can locate the error:
+// code is the kind of failure, path a list of { kind, value } from the outermost field,
+// offset the byte where CBOR decoding stopped and cbor the underlying CBOR error message
+#[wasm_bindgen(inline_js = "export class Cip25DecodeError extends Error {
+    constructor(message, code, path, offset, cbor) {
+        super(message);
+        this.name = 'Cip25DecodeError';
+        this.code = code;
+        this.path = path;
+        this.offset = offset;
+        this.cbor = cbor;
+    }
+}")]
+extern "C" {
+    type Cip25DecodeError;
+
+    #[wasm_bindgen(constructor)]
+    fn new(message: &str, code: &str, path: JsValue, offset: Option<f64>, cbor: Option<String>) -> Cip25DecodeError;
+}
+
+fn failure_code(failure: &core::prelude::DeserializeFailure) -> &'static str {
+    use core::prelude::DeserializeFailure;
+    match failure {
+        DeserializeFailure::BreakInDefiniteLen => "break_in_definite_len",
+        DeserializeFailure::CBOR(_) => "cbor",
+        DeserializeFailure::DefiniteLenMismatch(_, _) => "definite_len_mismatch",
+        DeserializeFailure::DuplicateKey(_) => "duplicate_key",
+        DeserializeFailure::EndingBreakMissing => "ending_break_missing",
+        DeserializeFailure::ExpectedNull => "expected_null",
+        DeserializeFailure::FixedValueMismatch{ .. } => "fixed_value_mismatch",
+        DeserializeFailure::InvalidAssetId(_) => "invalid_asset_id",
+        DeserializeFailure::InvalidRoyaltyRate(_) => "invalid_royalty_rate",
+        DeserializeFailure::MandatoryFieldMissing(_) => "mandatory_field_missing",
+        DeserializeFailure::NoVariantMatched => "no_variant_matched",
+        DeserializeFailure::RangeCheck{ .. } => "range_check",
+        DeserializeFailure::TagMismatch{ .. } => "tag_mismatch",
+        DeserializeFailure::UnknownKey(_) => "unknown_key",
+        DeserializeFailure::UnexpectedKeyType(_) => "unexpected_key_type",
+        DeserializeFailure::UnsupportedVersion(_) => "unsupported_version",
+    }
+}
+
+fn decode_error(context: &str, e: &core::prelude::DeserializeError) -> JsValue {
+    use core::prelude::{DeserializeFailure, PathSegment};
+    let path = e.path().iter().map(|segment| match segment {
+        PathSegment::Field(field) => serde_json::json!({ "kind": "field", "value": field }),
+        PathSegment::Index(index) => serde_json::json!({ "kind": "index", "value": index }),
+        PathSegment::PolicyId(policy_id) => serde_json::json!({ "kind": "policy_id", "value": policy_id }),
+        PathSegment::AssetName(asset_name) => serde_json::json!({ "kind": "asset_name", "value": asset_name }),
+    }).collect::<Vec<_>>();
+    let cbor = match e.failure() {
+        DeserializeFailure::CBOR(cbor) => Some(cbor.to_string()),
+        _ => None,
+    };
+    Cip25DecodeError::new(
+        &format!("{}: {}", context, e),
+        failure_code(e.failure()),
+        JsValue::from_serde(&path).unwrap_or(JsValue::UNDEFINED),
+        e.offset().map(|offset| offset as f64),
+        cbor,
+    ).into()
+}
+
+fn json_decode_error(context: &str, e: &serde_json::Error) -> JsValue {
+    Cip25DecodeError::new(&format!("{}: {}", context, e), "json", JsValue::from_serde(&serde_json::json!([])).unwrap_or(JsValue::UNDEFINED), None, None).into()
+}
+
+#[wasm_bindgen]
+
+pub enum JsonFormat {
+    Cip25,
+    Legacy,
+}
+
+impl From<JsonFormat> for core::json::JsonFormat {
+    fn from(wasm: JsonFormat) -> Self {
+        match wasm {
+            JsonFormat::Cip25 => core::json::JsonFormat::Cip25,
+            JsonFormat::Legacy => core::json::JsonFormat::Legacy,
+        }
+    }
+}
+
 #[wasm_bindgen]
 
 #[derive(Clone, Debug)]
@@ -26,8 +109,10 @@ impl MapAssetNameV2ToMetadataDetails {
         self.0.len()
     }
 
-    pub fn insert(&mut self, key: AssetNameV2, value: &MetadataDetails) -> Option<MetadataDetails> {
-        self.0.insert(key, value.clone().into()).map(|v| v.clone().into())
+    // asset names are limited to 64 bytes like any metadata bytes
+    pub fn insert(&mut self, key: AssetNameV2, value: &MetadataDetails) -> Result<Option<MetadataDetails>, JsValue> {
+        core::check_metadatum_len(key.len()).map_err(|e| JsValue::from_str(&format!("insert: {}", e)))?;
+        Ok(self.0.insert(key, value.clone().into()).map(|v| v.clone().into()))
     }
 
     pub fn get(&self, key: AssetNameV2) -> Option<MetadataDetails> {
@@ -178,8 +263,10 @@ impl Data {
         self.0.len()
     }
 
-    pub fn insert(&mut self, key: PolicyIdV2, value: &MapAssetNameV2ToMetadataDetails) -> Option<MapAssetNameV2ToMetadataDetails> {
-        self.0.insert(key, value.clone().into()).map(|v| v.clone().into())
+    // policy ids are limited to 64 bytes like any metadata bytes
+    pub fn insert(&mut self, key: PolicyIdV2, value: &MapAssetNameV2ToMetadataDetails) -> Result<Option<MapAssetNameV2ToMetadataDetails>, JsValue> {
+        core::check_metadatum_len(key.len()).map_err(|e| JsValue::from_str(&format!("insert: {}", e)))?;
+        Ok(self.0.insert(key, value.clone().into()).map(|v| v.clone().into()))
     }
 
     pub fn get(&self, key: PolicyIdV2) -> Option<MapAssetNameV2ToMetadataDetails> {
@@ -211,14 +298,19 @@ pub struct FilesDetails(core::FilesDetails);
 #[wasm_bindgen]
 
 impl FilesDetails {
-    pub fn to_bytes(&self) -> Vec<u8> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn to_bytes_with(&self, options: &EncodingOptions) -> Result<Vec<u8>, JsValue> {
         use core::serialization::ToBytes;
-        ToBytes::to_bytes(&self.0)
+        ToBytes::to_bytes_with(&self.0, &options.0).map_err(|e| JsValue::from_str(&format!("to_bytes_with: {}", e)))
     }
 
     pub fn from_bytes(data: Vec<u8>) -> Result<FilesDetails, JsValue> {
         use core::prelude::FromBytes;
-        FromBytes::from_bytes(data).map(Self).map_err(|e| JsValue::from_str(&format!("from_bytes: {}", e)))
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
     }
 
     pub fn to_json(&self) -> Result<String, JsValue> {
@@ -230,7 +322,17 @@ impl FilesDetails {
     }
 
     pub fn from_json(json: &str) -> Result<FilesDetails, JsValue> {
-        serde_json::from_str(json).map(Self).map_err(|e| JsValue::from_str(&format!("from_json: {}", e)))
+        serde_json::from_str(json).map(Self).map_err(|e| json_decode_error("from_json", &e))
+    }
+
+    pub fn to_json_with_format(&self, format: JsonFormat) -> Result<String, JsValue> {
+        use core::json::ToJson;
+        ToJson::to_json(&self.0, format.into()).map_err(|e| JsValue::from_str(&format!("to_json_with_format: {}", e)))
+    }
+
+    pub fn from_json_with_format(json: &str, format: JsonFormat) -> Result<FilesDetails, JsValue> {
+        use core::json::FromJson;
+        FromJson::from_json(json, format.into()).map(Self).map_err(|e| json_decode_error("from_json_with_format", &e))
     }
 
     pub fn name(&self) -> String64 {
@@ -460,6 +562,7 @@ impl std::convert::Into<Vec<core::PolicyIdV2>> for PolicyIdV2s {
 pub enum LabelMetadataKind {
     LabelMetadataV1,
     LabelMetadataV2,
+    Unknown,
 }
 
 #[wasm_bindgen]
@@ -470,14 +573,41 @@ pub struct LabelMetadata(core::LabelMetadata);
 #[wasm_bindgen]
 
 impl LabelMetadata {
-    pub fn to_bytes(&self) -> Vec<u8> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
         use core::serialization::ToBytes;
-        ToBytes::to_bytes(&self.0)
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn to_bytes_with(&self, options: &EncodingOptions) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes_with(&self.0, &options.0).map_err(|e| JsValue::from_str(&format!("to_bytes_with: {}", e)))
+    }
+
+    // without allocating the encoded bytes
+    pub fn encoded_size(&self) -> Result<usize, JsValue> {
+        use core::size::EncodedSize;
+        EncodedSize::encoded_size(&self.0).map_err(|e| JsValue::from_str(&format!("encoded_size: {}", e)))
+    }
+
+    pub fn fits_budget(&self, budget: usize) -> Result<bool, JsValue> {
+        use core::size::EncodedSize;
+        EncodedSize::fits_budget(&self.0, budget).map_err(|e| JsValue::from_str(&format!("fits_budget: {}", e)))
+    }
+
+    // policy id and asset name as raw bytes whatever the version
+    pub fn added_size(&self, policy_id: PolicyIdV2, asset_name: AssetNameV2, details: &MetadataDetails) -> Result<isize, JsValue> {
+        self.0.added_size(&policy_id, &asset_name, &details.0).map_err(|e| JsValue::from_str(&format!("added_size: {}", e)))
     }
 
     pub fn from_bytes(data: Vec<u8>) -> Result<LabelMetadata, JsValue> {
         use core::prelude::FromBytes;
-        FromBytes::from_bytes(data).map(Self).map_err(|e| JsValue::from_str(&format!("from_bytes: {}", e)))
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    pub fn from_bytes_with_options(data: Vec<u8>, duplicate_keys: DuplicateKeys) -> Result<LabelMetadata, JsValue> {
+        use core::prelude::DeserializeWithOptions;
+        let options = core::prelude::DeserializeOptions { duplicate_keys: duplicate_keys.into() };
+        DeserializeWithOptions::from_bytes_with_options(data, &options).map(Self).map_err(|e| decode_error("from_bytes", &e))
     }
 
     pub fn to_json(&self) -> Result<String, JsValue> {
@@ -489,10 +619,20 @@ impl LabelMetadata {
     }
 
     pub fn from_json(json: &str) -> Result<LabelMetadata, JsValue> {
-        serde_json::from_str(json).map(Self).map_err(|e| JsValue::from_str(&format!("from_json: {}", e)))
+        serde_json::from_str(json).map(Self).map_err(|e| json_decode_error("from_json", &e))
+    }
+
+    pub fn to_json_with_format(&self, format: JsonFormat) -> Result<String, JsValue> {
+        use core::json::ToJson;
+        ToJson::to_json(&self.0, format.into()).map_err(|e| JsValue::from_str(&format!("to_json_with_format: {}", e)))
+    }
+
+    pub fn from_json_with_format(json: &str, format: JsonFormat) -> Result<LabelMetadata, JsValue> {
+        use core::json::FromJson;
+        FromJson::from_json(json, format.into()).map(Self).map_err(|e| json_decode_error("from_json_with_format", &e))
     }
 
-    pub fn new_label_metadata_v1(label_metadata_v1: LabelMetadataV1) -> Self {
+    pub fn new_label_metadata_v1(label_metadata_v1: &LabelMetadataV1) -> Self {
         Self(core::LabelMetadata::new_label_metadata_v1(label_metadata_v1.clone().into()))
     }
 
@@ -504,6 +644,7 @@ impl LabelMetadata {
         match &self.0 {
             core::LabelMetadata::LabelMetadataV1(_) => LabelMetadataKind::LabelMetadataV1,
             core::LabelMetadata::LabelMetadataV2(_) => LabelMetadataKind::LabelMetadataV2,
+            core::LabelMetadata::Unknown { .. } => LabelMetadataKind::Unknown,
         }
     }
 
@@ -520,6 +661,54 @@ impl LabelMetadata {
             _ => None,
         }
     }
+
+    // CBOR bytes of the version field of an unknown version
+    pub fn unknown_version(&self) -> Result<Option<Vec<u8>>, JsValue> {
+        use core::serialization::ToBytes;
+        match &self.0 {
+            core::LabelMetadata::Unknown { version, .. } => ToBytes::to_bytes(version).map(Some).map_err(|e| JsValue::from_str(&format!("unknown_version: {}", e))),
+            _ => Ok(None),
+        }
+    }
+
+    // batches of at most budget bytes, see Batches
+    pub fn split_into_batches(&self, budget: usize) -> Result<Batches, JsValue> {
+        self.0.split_into_batches(budget).map(Batches).map_err(|e| JsValue::from_str(&format!("split_into_batches: {}", e)))
+    }
+
+    pub fn asset_details(&self, policy_id: PolicyIdV2, asset_name: AssetNameV2) -> Result<Option<MetadataDetails>, JsValue> {
+        use core::query::AssetMetadataQuery;
+        AssetMetadataQuery::asset_details(&self.0, &policy_id, &asset_name).map(|details| details.map(MetadataDetails)).map_err(|e| decode_error("asset_details", &e))
+    }
+
+    pub fn token_pairs(&self) -> Result<TokenPairs, JsValue> {
+        self.0.token_pairs().map(TokenPairs).map_err(|e| decode_error("token_pairs", &e))
+    }
+
+    // the described assets the mint doesn't match and the minted NFTs without metadata
+    pub fn check_mint(&self, mint: &Mint) -> Result<MintIssues, JsValue> {
+        self.0.check_mint(&mint.0).map(MintIssues).map_err(|e| decode_error("check_mint", &e))
+    }
+
+    // the bech32 fingerprints of the assets, in the order of the metadata
+    pub fn fingerprints(&self) -> Result<Vec<JsValue>, JsValue> {
+        self.0
+            .fingerprints()
+            .map(|fingerprints| fingerprints.into_iter().map(|(_, _, fingerprint)| JsValue::from_str(&fingerprint.to_bech32())).collect())
+            .map_err(|e| decode_error("fingerprints", &e))
+    }
+
+    pub fn interpret_as_v2(&self) -> Result<Option<LabelMetadataV2>, JsValue> {
+        self.0.interpret_as_v2().map(|label_metadata_v2| label_metadata_v2.map(Into::into)).map_err(|e| decode_error("interpret_as_v2", &e))
+    }
+
+    pub fn convert_to_v1(&self) -> Result<LabelMetadataV1, JsValue> {
+        self.0.convert_to_v1().map(Into::into).map_err(|e| decode_error("convert_to_v1", &e))
+    }
+
+    pub fn convert_to_v2(&self) -> Result<LabelMetadataV2, JsValue> {
+        self.0.convert_to_v2().map(Into::into).map_err(|e| decode_error("convert_to_v2", &e))
+    }
 }
 
 impl From<core::LabelMetadata> for LabelMetadata {
@@ -534,7 +723,93 @@ impl From<LabelMetadata> for core::LabelMetadata {
     }
 }
 
-type LabelMetadataV1 = MapPolicyIdV1ToMapAssetNameV1ToMetadataDetails;
+type DataV1 = MapPolicyIdV1ToMapAssetNameV1ToMetadataDetails;
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct LabelMetadataV1(core::LabelMetadataV1);
+
+#[wasm_bindgen]
+
+impl LabelMetadataV1 {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn to_bytes_with(&self, options: &EncodingOptions) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes_with(&self.0, &options.0).map_err(|e| JsValue::from_str(&format!("to_bytes_with: {}", e)))
+    }
+
+    // without allocating the encoded bytes
+    pub fn encoded_size(&self) -> Result<usize, JsValue> {
+        use core::size::EncodedSize;
+        EncodedSize::encoded_size(&self.0).map_err(|e| JsValue::from_str(&format!("encoded_size: {}", e)))
+    }
+
+    pub fn fits_budget(&self, budget: usize) -> Result<bool, JsValue> {
+        use core::size::EncodedSize;
+        EncodedSize::fits_budget(&self.0, budget).map_err(|e| JsValue::from_str(&format!("fits_budget: {}", e)))
+    }
+
+    // how much the encoded size grows when inserting this asset, negative if it replaces a bigger one
+    pub fn added_size(&self, policy_id: &PolicyIdV1, asset_name: &AssetNameV1, details: &MetadataDetails) -> Result<isize, JsValue> {
+        self.0.added_size(&policy_id.0, &asset_name.0, &details.0).map_err(|e| JsValue::from_str(&format!("added_size: {}", e)))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<LabelMetadataV1, JsValue> {
+        use core::prelude::FromBytes;
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    pub fn from_bytes_with_options(data: Vec<u8>, duplicate_keys: DuplicateKeys) -> Result<LabelMetadataV1, JsValue> {
+        use core::prelude::DeserializeWithOptions;
+        let options = core::prelude::DeserializeOptions { duplicate_keys: duplicate_keys.into() };
+        DeserializeWithOptions::from_bytes_with_options(data, &options).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    pub fn to_json(&self) -> Result<String, JsValue> {
+        serde_json::to_string_pretty(&self.0).map_err(|e| JsValue::from_str(&format!("to_json: {}", e)))
+    }
+
+    pub fn to_json_value(&self) -> Result<JsValue, JsValue> {
+        JsValue::from_serde(&self.0).map_err(|e| JsValue::from_str(&format!("to_js_value: {}", e)))
+    }
+
+    pub fn from_json(json: &str) -> Result<LabelMetadataV1, JsValue> {
+        serde_json::from_str(json).map(Self).map_err(|e| json_decode_error("from_json", &e))
+    }
+
+    pub fn data(&self) -> DataV1 {
+        self.0.data.clone().into()
+    }
+
+    pub fn version(&self) -> Option<VersionFormat> {
+        self.0.version.map(Into::into)
+    }
+
+    pub fn set_version(&mut self, version: VersionFormat) {
+        self.0.version = Some(version.into())
+    }
+
+    pub fn new(data: DataV1) -> Self {
+        Self(core::LabelMetadataV1::new(data.clone().into()))
+    }
+}
+
+impl From<core::LabelMetadataV1> for LabelMetadataV1 {
+    fn from(native: core::LabelMetadataV1) -> Self {
+        Self(native)
+    }
+}
+
+impl From<LabelMetadataV1> for core::LabelMetadataV1 {
+    fn from(wasm: LabelMetadataV1) -> Self {
+        wasm.0
+    }
+}
 
 #[wasm_bindgen]
 
@@ -544,14 +819,41 @@ pub struct LabelMetadataV2(core::LabelMetadataV2);
 #[wasm_bindgen]
 
 impl LabelMetadataV2 {
-    pub fn to_bytes(&self) -> Vec<u8> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
         use core::serialization::ToBytes;
-        ToBytes::to_bytes(&self.0)
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn to_bytes_with(&self, options: &EncodingOptions) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes_with(&self.0, &options.0).map_err(|e| JsValue::from_str(&format!("to_bytes_with: {}", e)))
+    }
+
+    // without allocating the encoded bytes
+    pub fn encoded_size(&self) -> Result<usize, JsValue> {
+        use core::size::EncodedSize;
+        EncodedSize::encoded_size(&self.0).map_err(|e| JsValue::from_str(&format!("encoded_size: {}", e)))
+    }
+
+    pub fn fits_budget(&self, budget: usize) -> Result<bool, JsValue> {
+        use core::size::EncodedSize;
+        EncodedSize::fits_budget(&self.0, budget).map_err(|e| JsValue::from_str(&format!("fits_budget: {}", e)))
+    }
+
+    // how much the encoded size grows when inserting this asset, negative if it replaces a bigger one
+    pub fn added_size(&self, policy_id: PolicyIdV2, asset_name: AssetNameV2, details: &MetadataDetails) -> Result<isize, JsValue> {
+        self.0.added_size(&policy_id, &asset_name, &details.0).map_err(|e| JsValue::from_str(&format!("added_size: {}", e)))
     }
 
     pub fn from_bytes(data: Vec<u8>) -> Result<LabelMetadataV2, JsValue> {
         use core::prelude::FromBytes;
-        FromBytes::from_bytes(data).map(Self).map_err(|e| JsValue::from_str(&format!("from_bytes: {}", e)))
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    pub fn from_bytes_with_options(data: Vec<u8>, duplicate_keys: DuplicateKeys) -> Result<LabelMetadataV2, JsValue> {
+        use core::prelude::DeserializeWithOptions;
+        let options = core::prelude::DeserializeOptions { duplicate_keys: duplicate_keys.into() };
+        DeserializeWithOptions::from_bytes_with_options(data, &options).map(Self).map_err(|e| decode_error("from_bytes", &e))
     }
 
     pub fn to_json(&self) -> Result<String, JsValue> {
@@ -563,15 +865,33 @@ impl LabelMetadataV2 {
     }
 
     pub fn from_json(json: &str) -> Result<LabelMetadataV2, JsValue> {
-        serde_json::from_str(json).map(Self).map_err(|e| JsValue::from_str(&format!("from_json: {}", e)))
+        serde_json::from_str(json).map(Self).map_err(|e| json_decode_error("from_json", &e))
+    }
+
+    pub fn to_json_with_format(&self, format: JsonFormat) -> Result<String, JsValue> {
+        use core::json::ToJson;
+        ToJson::to_json(&self.0, format.into()).map_err(|e| JsValue::from_str(&format!("to_json_with_format: {}", e)))
+    }
+
+    pub fn from_json_with_format(json: &str, format: JsonFormat) -> Result<LabelMetadataV2, JsValue> {
+        use core::json::FromJson;
+        FromJson::from_json(json, format.into()).map(Self).map_err(|e| json_decode_error("from_json_with_format", &e))
     }
 
     pub fn data(&self) -> Data {
         self.0.data.clone().into()
     }
 
-    pub fn new(data: Data) -> Self {
-        Self(core::LabelMetadataV2::new(data.clone().into()))
+    pub fn version(&self) -> VersionFormat {
+        self.0.version.into()
+    }
+
+    pub fn set_version(&mut self, version: VersionFormat) {
+        self.0.version = version.into()
+    }
+
+    pub fn new(data: Data) -> Result<LabelMetadataV2, JsValue> {
+        core::LabelMetadataV2::new(data.clone().into()).map(Self).map_err(|e| JsValue::from_str(&format!("new: {}", e)))
     }
 }
 
@@ -595,14 +915,41 @@ pub struct Metadata(core::Metadata);
 #[wasm_bindgen]
 
 impl Metadata {
-    pub fn to_bytes(&self) -> Vec<u8> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn to_bytes_with(&self, options: &EncodingOptions) -> Result<Vec<u8>, JsValue> {
         use core::serialization::ToBytes;
-        ToBytes::to_bytes(&self.0)
+        ToBytes::to_bytes_with(&self.0, &options.0).map_err(|e| JsValue::from_str(&format!("to_bytes_with: {}", e)))
+    }
+
+    // without allocating the encoded bytes
+    pub fn encoded_size(&self) -> Result<usize, JsValue> {
+        use core::size::EncodedSize;
+        EncodedSize::encoded_size(&self.0).map_err(|e| JsValue::from_str(&format!("encoded_size: {}", e)))
+    }
+
+    pub fn fits_budget(&self, budget: usize) -> Result<bool, JsValue> {
+        use core::size::EncodedSize;
+        EncodedSize::fits_budget(&self.0, budget).map_err(|e| JsValue::from_str(&format!("fits_budget: {}", e)))
     }
 
     pub fn from_bytes(data: Vec<u8>) -> Result<Metadata, JsValue> {
         use core::prelude::FromBytes;
-        FromBytes::from_bytes(data).map(Self).map_err(|e| JsValue::from_str(&format!("from_bytes: {}", e)))
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    pub fn from_bytes_with_options(data: Vec<u8>, duplicate_keys: DuplicateKeys) -> Result<Metadata, JsValue> {
+        use core::prelude::DeserializeWithOptions;
+        let options = core::prelude::DeserializeOptions { duplicate_keys: duplicate_keys.into() };
+        DeserializeWithOptions::from_bytes_with_options(data, &options).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    // keeps decoding after recoverable errors, see MetadataPartialDecode
+    pub fn from_bytes_diagnostic(data: Vec<u8>) -> MetadataPartialDecode {
+        MetadataPartialDecode(core::Metadata::from_bytes_diagnostic(data))
     }
 
     pub fn to_json(&self) -> Result<String, JsValue> {
@@ -614,7 +961,27 @@ impl Metadata {
     }
 
     pub fn from_json(json: &str) -> Result<Metadata, JsValue> {
-        serde_json::from_str(json).map(Self).map_err(|e| JsValue::from_str(&format!("from_json: {}", e)))
+        serde_json::from_str(json).map(Self).map_err(|e| json_decode_error("from_json", &e))
+    }
+
+    pub fn to_json_with_format(&self, format: JsonFormat) -> Result<String, JsValue> {
+        use core::json::ToJson;
+        ToJson::to_json(&self.0, format.into()).map_err(|e| JsValue::from_str(&format!("to_json_with_format: {}", e)))
+    }
+
+    pub fn from_json_with_format(json: &str, format: JsonFormat) -> Result<Metadata, JsValue> {
+        use core::json::FromJson;
+        FromJson::from_json(json, format.into()).map(Self).map_err(|e| json_decode_error("from_json_with_format", &e))
+    }
+
+    pub fn asset_details(&self, policy_id: PolicyIdV2, asset_name: AssetNameV2) -> Result<Option<MetadataDetails>, JsValue> {
+        use core::query::AssetMetadataQuery;
+        AssetMetadataQuery::asset_details(&self.0, &policy_id, &asset_name).map(|details| details.map(MetadataDetails)).map_err(|e| decode_error("asset_details", &e))
+    }
+
+    // the policies with the script among native_scripts they are derived from, see PolicyCheck
+    pub fn check_policies(&self, native_scripts: &NativeScripts, slot: u64) -> Result<PolicyChecks, JsValue> {
+        self.0.check_policies(&native_scripts.0, slot).map(PolicyChecks).map_err(|e| decode_error("check_policies", &e))
     }
 
     pub fn key_721(&self) -> LabelMetadata {
@@ -646,14 +1013,19 @@ pub struct MetadataDetails(core::MetadataDetails);
 #[wasm_bindgen]
 
 impl MetadataDetails {
-    pub fn to_bytes(&self) -> Vec<u8> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn to_bytes_with(&self, options: &EncodingOptions) -> Result<Vec<u8>, JsValue> {
         use core::serialization::ToBytes;
-        ToBytes::to_bytes(&self.0)
+        ToBytes::to_bytes_with(&self.0, &options.0).map_err(|e| JsValue::from_str(&format!("to_bytes_with: {}", e)))
     }
 
     pub fn from_bytes(data: Vec<u8>) -> Result<MetadataDetails, JsValue> {
         use core::prelude::FromBytes;
-        FromBytes::from_bytes(data).map(Self).map_err(|e| JsValue::from_str(&format!("from_bytes: {}", e)))
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
     }
 
     pub fn to_json(&self) -> Result<String, JsValue> {
@@ -665,7 +1037,17 @@ impl MetadataDetails {
     }
 
     pub fn from_json(json: &str) -> Result<MetadataDetails, JsValue> {
-        serde_json::from_str(json).map(Self).map_err(|e| JsValue::from_str(&format!("from_json: {}", e)))
+        serde_json::from_str(json).map(Self).map_err(|e| json_decode_error("from_json", &e))
+    }
+
+    pub fn to_json_with_format(&self, format: JsonFormat) -> Result<String, JsValue> {
+        use core::json::ToJson;
+        ToJson::to_json(&self.0, format.into()).map_err(|e| JsValue::from_str(&format!("to_json_with_format: {}", e)))
+    }
+
+    pub fn from_json_with_format(json: &str, format: JsonFormat) -> Result<MetadataDetails, JsValue> {
+        use core::json::FromJson;
+        FromJson::from_json(json, format.into()).map(Self).map_err(|e| json_decode_error("from_json_with_format", &e))
     }
 
     pub fn name(&self) -> String64 {
@@ -725,14 +1107,19 @@ pub struct String64(core::String64);
 #[wasm_bindgen]
 
 impl String64 {
-    pub fn to_bytes(&self) -> Vec<u8> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn to_bytes_with(&self, options: &EncodingOptions) -> Result<Vec<u8>, JsValue> {
         use core::serialization::ToBytes;
-        ToBytes::to_bytes(&self.0)
+        ToBytes::to_bytes_with(&self.0, &options.0).map_err(|e| JsValue::from_str(&format!("to_bytes_with: {}", e)))
     }
 
     pub fn from_bytes(data: Vec<u8>) -> Result<String64, JsValue> {
         use core::prelude::FromBytes;
-        FromBytes::from_bytes(data).map(Self).map_err(|e| JsValue::from_str(&format!("from_bytes: {}", e)))
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
     }
 
     pub fn to_json(&self) -> Result<String, JsValue> {
@@ -744,7 +1131,7 @@ impl String64 {
     }
 
     pub fn from_json(json: &str) -> Result<String64, JsValue> {
-        serde_json::from_str(json).map(Self).map_err(|e| JsValue::from_str(&format!("from_json: {}", e)))
+        serde_json::from_str(json).map(Self).map_err(|e| json_decode_error("from_json", &e))
     }
 
     pub fn get(&self) -> String {
@@ -779,14 +1166,19 @@ pub struct String64OrArrString64(core::String64OrArrString64);
 #[wasm_bindgen]
 
 impl String64OrArrString64 {
-    pub fn to_bytes(&self) -> Vec<u8> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
         use core::serialization::ToBytes;
-        ToBytes::to_bytes(&self.0)
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn to_bytes_with(&self, options: &EncodingOptions) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes_with(&self.0, &options.0).map_err(|e| JsValue::from_str(&format!("to_bytes_with: {}", e)))
     }
 
     pub fn from_bytes(data: Vec<u8>) -> Result<String64OrArrString64, JsValue> {
         use core::prelude::FromBytes;
-        FromBytes::from_bytes(data).map(Self).map_err(|e| JsValue::from_str(&format!("from_bytes: {}", e)))
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
     }
 
     pub fn to_json(&self) -> Result<String, JsValue> {
@@ -798,7 +1190,17 @@ impl String64OrArrString64 {
     }
 
     pub fn from_json(json: &str) -> Result<String64OrArrString64, JsValue> {
-        serde_json::from_str(json).map(Self).map_err(|e| JsValue::from_str(&format!("from_json: {}", e)))
+        serde_json::from_str(json).map(Self).map_err(|e| json_decode_error("from_json", &e))
+    }
+
+    pub fn to_json_with_format(&self, format: JsonFormat) -> Result<String, JsValue> {
+        use core::json::ToJson;
+        ToJson::to_json(&self.0, format.into()).map_err(|e| JsValue::from_str(&format!("to_json_with_format: {}", e)))
+    }
+
+    pub fn from_json_with_format(json: &str, format: JsonFormat) -> Result<String64OrArrString64, JsValue> {
+        use core::json::FromJson;
+        FromJson::from_json(json, format.into()).map(Self).map_err(|e| json_decode_error("from_json_with_format", &e))
     }
 
     pub fn new_string64(string64: &String64) -> Self {
@@ -841,4 +1243,1005 @@ impl From<String64OrArrString64> for core::String64OrArrString64 {
     fn from(wasm: String64OrArrString64) -> Self {
         wasm.0
     }
-}
\ No newline at end of file
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Copy, Debug)]
+pub enum VersionFormat {
+    Uint,
+    Text,
+}
+
+impl From<core::VersionFormat> for VersionFormat {
+    fn from(native: core::VersionFormat) -> Self {
+        match native {
+            core::VersionFormat::Uint => VersionFormat::Uint,
+            core::VersionFormat::Text => VersionFormat::Text,
+        }
+    }
+}
+
+impl From<VersionFormat> for core::VersionFormat {
+    fn from(wasm: VersionFormat) -> Self {
+        match wasm {
+            VersionFormat::Uint => core::VersionFormat::Uint,
+            VersionFormat::Text => core::VersionFormat::Text,
+        }
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Copy, Debug)]
+pub enum DuplicateKeys {
+    Error,
+    KeepFirst,
+    KeepLast,
+}
+
+impl From<DuplicateKeys> for core::prelude::DuplicateKeys {
+    fn from(wasm: DuplicateKeys) -> Self {
+        match wasm {
+            DuplicateKeys::Error => core::prelude::DuplicateKeys::Error,
+            DuplicateKeys::KeepFirst => core::prelude::DuplicateKeys::KeepFirst,
+            DuplicateKeys::KeepLast => core::prelude::DuplicateKeys::KeepLast,
+        }
+    }
+}
+
+#[wasm_bindgen]
+
+pub struct MetadataPartialDecode(core::diagnostic::PartialDecode<core::Metadata>);
+
+#[wasm_bindgen]
+
+impl MetadataPartialDecode {
+    pub fn is_valid(&self) -> bool {
+        self.0.is_valid()
+    }
+
+    // what could be decoded, without the invalid assets and optional fields
+    pub fn value(&self) -> Option<Metadata> {
+        self.0.value.clone().map(Metadata)
+    }
+
+    // a Cip25DecodeError per error
+    pub fn errors(&self) -> Vec<JsValue> {
+        self.0.errors.iter().map(|e| decode_error("from_bytes_diagnostic", e)).collect()
+    }
+}
+
+#[wasm_bindgen]
+
+pub struct EncodingOptions(core::encoding::EncodingOptions);
+
+#[wasm_bindgen]
+
+impl EncodingOptions {
+    pub fn new_core_deterministic() -> Self {
+        Self(core::encoding::EncodingOptions::CoreDeterministic)
+    }
+
+    pub fn new_legacy_canonical() -> Self {
+        Self(core::encoding::EncodingOptions::LegacyCanonical)
+    }
+
+    pub fn new_minimal() -> Self {
+        Self(core::encoding::EncodingOptions::Minimal)
+    }
+
+    pub fn new_preserve_original(original: Vec<u8>) -> Self {
+        Self(core::encoding::EncodingOptions::PreserveOriginal(original))
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct Metadatas(Vec<core::Metadata>);
+
+#[wasm_bindgen]
+
+impl Metadatas {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> Metadata {
+        self.0[index].clone().into()
+    }
+
+    pub fn add(&mut self, elem: &Metadata) {
+        self.0.push(elem.clone().into());
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct OversizedAsset(core::batch::OversizedAsset);
+
+#[wasm_bindgen]
+
+impl OversizedAsset {
+    // text for version 1, hex for version 2
+    pub fn policy_id(&self) -> String {
+        self.0.policy_id.clone()
+    }
+
+    // text for version 1, hex for version 2
+    pub fn asset_name(&self) -> String {
+        self.0.asset_name.clone()
+    }
+
+    pub fn size(&self) -> usize {
+        self.0.size
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct OversizedAssets(Vec<core::batch::OversizedAsset>);
+
+#[wasm_bindgen]
+
+impl OversizedAssets {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> OversizedAsset {
+        OversizedAsset(self.0[index].clone())
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct Batches(core::batch::Batches);
+
+#[wasm_bindgen]
+
+impl Batches {
+    pub fn batches(&self) -> Metadatas {
+        Metadatas(self.0.batches.clone())
+    }
+
+    // assets that don't fit the budget even alone, they are in none of the batches
+    pub fn oversized(&self) -> OversizedAssets {
+        OversizedAssets(self.0.oversized.clone())
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct Cip68Datum(core::cip68::Cip68Datum);
+
+#[wasm_bindgen]
+
+impl Cip68Datum {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Cip68Datum, JsValue> {
+        use core::prelude::FromBytes;
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    // extra is the CBOR of any plutus data, an empty constructor 0 when undefined
+    pub fn from_details(details: &MetadataDetails, version: u64, extra: Option<Vec<u8>>) -> Result<Cip68Datum, JsValue> {
+        use core::prelude::FromBytes;
+        let extra = match extra {
+            Some(extra) => core::cip68::PlutusData::from_bytes(extra).map_err(|e| decode_error("from_details", &e))?,
+            None => core::cip68::PlutusData::new_constr(0, Vec::new()),
+        };
+        Ok(Self(core::cip68::Cip68Datum::from_details(&details.0, version, extra)))
+    }
+
+    pub fn details(&self) -> Result<MetadataDetails, JsValue> {
+        self.0.details().map(MetadataDetails).map_err(|e| decode_error("details", &e))
+    }
+
+    pub fn version(&self) -> u64 {
+        self.0.version
+    }
+
+    pub fn extra(&self) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes(&self.0.extra).map_err(|e| JsValue::from_str(&format!("extra: {}", e)))
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct Cip68Metadata(core::cip68::Cip68Metadata);
+
+#[wasm_bindgen]
+
+impl Cip68Metadata {
+    pub fn new() -> Self {
+        Self(core::cip68::Cip68Metadata::default())
+    }
+
+    // asset_name is the one of the reference token holding the datum
+    pub fn insert(&mut self, policy_id: PolicyIdV2, asset_name: AssetNameV2, datum: &Cip68Datum) -> Option<Cip68Datum> {
+        self.0.data.entry(policy_id).or_default().insert(asset_name, datum.0.clone()).map(Cip68Datum)
+    }
+
+    pub fn asset_details(&self, policy_id: PolicyIdV2, asset_name: AssetNameV2) -> Result<Option<MetadataDetails>, JsValue> {
+        use core::query::AssetMetadataQuery;
+        AssetMetadataQuery::asset_details(&self.0, &policy_id, &asset_name).map(|details| details.map(MetadataDetails)).map_err(|e| decode_error("asset_details", &e))
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenClass {
+    Reference,
+    Nft,
+    Ft,
+    Rft,
+    Other,
+    Unlabelled,
+}
+
+impl From<core::cip67::TokenClass> for TokenClass {
+    fn from(native: core::cip67::TokenClass) -> Self {
+        match native {
+            core::cip67::TokenClass::Reference => Self::Reference,
+            core::cip67::TokenClass::Nft => Self::Nft,
+            core::cip67::TokenClass::Ft => Self::Ft,
+            core::cip67::TokenClass::Rft => Self::Rft,
+            core::cip67::TokenClass::Other(_) => Self::Other,
+            core::cip67::TokenClass::Unlabelled => Self::Unlabelled,
+        }
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct LabelledAssetName(core::cip67::LabelledAssetName);
+
+#[wasm_bindgen]
+
+impl LabelledAssetName {
+    pub fn new(label: u16, name: Vec<u8>) -> Result<LabelledAssetName, JsValue> {
+        core::cip67::LabelledAssetName::new(label, name).map(Self).map_err(|e| JsValue::from_str(&format!("new: {}", e)))
+    }
+
+    // undefined when the asset name doesn't start with a valid CIP-67 prefix
+    pub fn parse(asset_name: AssetNameV2) -> Option<LabelledAssetName> {
+        core::cip67::LabelledAssetName::parse(&asset_name).map(Self)
+    }
+
+    pub fn label(&self) -> u16 {
+        self.0.label
+    }
+
+    pub fn name(&self) -> Vec<u8> {
+        self.0.name.clone()
+    }
+
+    pub fn to_asset_name(&self) -> AssetNameV2 {
+        self.0.to_asset_name()
+    }
+
+    pub fn token_class(&self) -> TokenClass {
+        self.0.token_class().into()
+    }
+
+    pub fn token_class_of(asset_name: AssetNameV2) -> TokenClass {
+        core::cip67::TokenClass::of(&asset_name).into()
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct TokenPair(core::cip67::TokenPair);
+
+#[wasm_bindgen]
+
+impl TokenPair {
+    pub fn policy_id(&self) -> PolicyIdV2 {
+        self.0.policy_id.clone()
+    }
+
+    // the asset name without the label prefix
+    pub fn name(&self) -> Vec<u8> {
+        self.0.name.clone()
+    }
+
+    pub fn reference(&self) -> Option<AssetNameV2> {
+        self.0.reference.clone()
+    }
+
+    pub fn user(&self) -> Option<AssetNameV2> {
+        self.0.user.clone()
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct TokenPairs(Vec<core::cip67::TokenPair>);
+
+#[wasm_bindgen]
+
+impl TokenPairs {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> TokenPair {
+        TokenPair(self.0[index].clone())
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct AssetFingerprint(core::cip14::AssetFingerprint);
+
+#[wasm_bindgen]
+
+impl AssetFingerprint {
+    pub fn new(policy_id: PolicyIdV2, asset_name: AssetNameV2) -> Self {
+        Self(core::cip14::AssetFingerprint::new(&policy_id, &asset_name))
+    }
+
+    // undefined when the policy id isn't hex text
+    pub fn from_v1(policy_id: &String64, asset_name: &String64) -> Option<AssetFingerprint> {
+        core::cip14::AssetFingerprint::from_v1(&policy_id.0, &asset_name.0).map(Self)
+    }
+
+    pub fn digest(&self) -> Vec<u8> {
+        self.0.digest().to_vec()
+    }
+
+    pub fn to_bech32(&self) -> String {
+        self.0.to_bech32()
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct RoyaltyMetadata(core::royalty::RoyaltyMetadata);
+
+#[wasm_bindgen]
+
+impl RoyaltyMetadata {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<RoyaltyMetadata, JsValue> {
+        use core::prelude::FromBytes;
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    pub fn to_json(&self) -> Result<String, JsValue> {
+        serde_json::to_string_pretty(&self.0).map_err(|e| JsValue::from_str(&format!("to_json: {}", e)))
+    }
+
+    pub fn to_json_value(&self) -> Result<JsValue, JsValue> {
+        JsValue::from_serde(&self.0).map_err(|e| JsValue::from_str(&format!("to_js_value: {}", e)))
+    }
+
+    pub fn from_json(json: &str) -> Result<RoyaltyMetadata, JsValue> {
+        serde_json::from_str(json).map(Self).map_err(|e| json_decode_error("from_json", &e))
+    }
+
+    pub fn rate(&self) -> String64 {
+        self.0.rate.clone().into()
+    }
+
+    pub fn rate_value(&self) -> f64 {
+        self.0.rate_value()
+    }
+
+    pub fn addr(&self) -> String64OrArrString64 {
+        self.0.addr.clone().into()
+    }
+
+    // the address with its chunks joined
+    pub fn address(&self) -> String {
+        self.0.address()
+    }
+
+    pub fn new(rate: &String64, addr: &String64OrArrString64) -> Result<RoyaltyMetadata, JsValue> {
+        core::royalty::RoyaltyMetadata::new(rate.clone().into(), addr.clone().into()).map(Self).map_err(|e| JsValue::from_str(&format!("new: {}", e)))
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct TokenMetadata(core::royalty::TokenMetadata);
+
+#[wasm_bindgen]
+
+impl TokenMetadata {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<TokenMetadata, JsValue> {
+        use core::prelude::FromBytes;
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    pub fn from_bytes_with_options(data: Vec<u8>, duplicate_keys: DuplicateKeys) -> Result<TokenMetadata, JsValue> {
+        use core::prelude::DeserializeWithOptions;
+        let options = core::prelude::DeserializeOptions { duplicate_keys: duplicate_keys.into() };
+        DeserializeWithOptions::from_bytes_with_options(data, &options).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    pub fn to_json(&self) -> Result<String, JsValue> {
+        serde_json::to_string_pretty(&self.0).map_err(|e| JsValue::from_str(&format!("to_json: {}", e)))
+    }
+
+    pub fn to_json_value(&self) -> Result<JsValue, JsValue> {
+        JsValue::from_serde(&self.0).map_err(|e| JsValue::from_str(&format!("to_js_value: {}", e)))
+    }
+
+    pub fn from_json(json: &str) -> Result<TokenMetadata, JsValue> {
+        serde_json::from_str(json).map(Self).map_err(|e| json_decode_error("from_json", &e))
+    }
+
+    pub fn key_721(&self) -> Option<LabelMetadata> {
+        self.0.key_721.clone().map(LabelMetadata)
+    }
+
+    pub fn key_777(&self) -> Option<RoyaltyMetadata> {
+        self.0.key_777.clone().map(RoyaltyMetadata)
+    }
+
+    pub fn set_key_721(&mut self, key_721: &LabelMetadata) {
+        self.0.key_721 = Some(key_721.clone().into())
+    }
+
+    pub fn set_key_777(&mut self, key_777: &RoyaltyMetadata) {
+        self.0.key_777 = Some(key_777.0.clone())
+    }
+
+    pub fn new() -> Self {
+        Self(core::royalty::TokenMetadata::default())
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MusicMetadataVersion {
+    V1,
+    V2,
+    V3,
+}
+
+impl From<core::music::MusicMetadataVersion> for MusicMetadataVersion {
+    fn from(native: core::music::MusicMetadataVersion) -> Self {
+        match native {
+            core::music::MusicMetadataVersion::V1 => MusicMetadataVersion::V1,
+            core::music::MusicMetadataVersion::V2 => MusicMetadataVersion::V2,
+            core::music::MusicMetadataVersion::V3 => MusicMetadataVersion::V3,
+        }
+    }
+}
+
+impl From<MusicMetadataVersion> for core::music::MusicMetadataVersion {
+    fn from(wasm: MusicMetadataVersion) -> Self {
+        match wasm {
+            MusicMetadataVersion::V1 => core::music::MusicMetadataVersion::V1,
+            MusicMetadataVersion::V2 => core::music::MusicMetadataVersion::V2,
+            MusicMetadataVersion::V3 => core::music::MusicMetadataVersion::V3,
+        }
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct Artist(core::music::Artist);
+
+#[wasm_bindgen]
+
+impl Artist {
+    pub fn name(&self) -> String64 {
+        self.0.name.clone().into()
+    }
+
+    pub fn new(name: &String64) -> Self {
+        Self(core::music::Artist::new(name.clone().into()))
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct Artists(Vec<core::music::Artist>);
+
+#[wasm_bindgen]
+
+impl Artists {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> Artist {
+        Artist(self.0[index].clone())
+    }
+
+    pub fn add(&mut self, elem: &Artist) {
+        self.0.push(elem.0.clone());
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct Release(core::music::Release);
+
+#[wasm_bindgen]
+
+impl Release {
+    pub fn release_type(&self) -> String64 {
+        self.0.release_type.clone().into()
+    }
+
+    pub fn set_release_title(&mut self, release_title: &String64OrArrString64) {
+        self.0.release_title = Some(release_title.clone().into())
+    }
+
+    pub fn release_title(&self) -> Option<String64OrArrString64> {
+        self.0.release_title.clone().map(std::convert::Into::into)
+    }
+
+    pub fn new(release_type: &String64) -> Self {
+        Self(core::music::Release::new(release_type.clone().into()))
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct Song(core::music::Song);
+
+#[wasm_bindgen]
+
+impl Song {
+    pub fn song_title(&self) -> String64OrArrString64 {
+        self.0.song_title.clone().into()
+    }
+
+    pub fn song_duration(&self) -> String64 {
+        self.0.song_duration.clone().into()
+    }
+
+    pub fn track_number(&self) -> u64 {
+        self.0.track_number
+    }
+
+    pub fn artists(&self) -> Artists {
+        Artists(self.0.artists.clone())
+    }
+
+    pub fn genres(&self) -> String64s {
+        self.0.genres.clone().into()
+    }
+
+    pub fn copyright(&self) -> String64OrArrString64 {
+        self.0.copyright.clone().into()
+    }
+
+    pub fn new(song_title: &String64OrArrString64, song_duration: &String64, track_number: u64, artists: &Artists, genres: &String64s, copyright: &String64OrArrString64) -> Self {
+        Self(core::music::Song::new(song_title.clone().into(), song_duration.clone().into(), track_number, artists.0.clone(), genres.clone().into(), copyright.clone().into()))
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct MusicFile(core::music::MusicFile);
+
+#[wasm_bindgen]
+
+impl MusicFile {
+    // without the song fields
+    pub fn file(&self) -> FilesDetails {
+        self.0.file.clone().into()
+    }
+
+    pub fn song(&self) -> Option<Song> {
+        self.0.song.clone().map(Song)
+    }
+
+    pub fn new(file: &FilesDetails, song: Option<Song>) -> Self {
+        Self(core::music::MusicFile { file: file.clone().into(), song: song.map(|song| song.0) })
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct MusicFiles(Vec<core::music::MusicFile>);
+
+#[wasm_bindgen]
+
+impl MusicFiles {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> MusicFile {
+        MusicFile(self.0[index].clone())
+    }
+
+    pub fn add(&mut self, elem: &MusicFile) {
+        self.0.push(elem.0.clone());
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct MusicMetadata(core::music::MusicMetadata);
+
+#[wasm_bindgen]
+
+impl MusicMetadata {
+    // undefined when the details aren't those of a CIP-60 music token
+    pub fn from_details(details: &MetadataDetails) -> Result<Option<MusicMetadata>, JsValue> {
+        core::music::MusicMetadata::from_details(&details.0).map(|music_metadata| music_metadata.map(Self)).map_err(|e| decode_error("from_details", &e))
+    }
+
+    // the details with the music fields written in the layout of the version
+    pub fn apply(&self, details: &MetadataDetails) -> MetadataDetails {
+        let mut details = details.0.clone();
+        self.0.apply(&mut details);
+        MetadataDetails(details)
+    }
+
+    pub fn validate(&self) -> Result<(), JsValue> {
+        self.0.validate().map_err(|e| decode_error("validate", &e))
+    }
+
+    pub fn version(&self) -> MusicMetadataVersion {
+        self.0.version.into()
+    }
+
+    pub fn release(&self) -> Release {
+        Release(self.0.release.clone())
+    }
+
+    pub fn files(&self) -> MusicFiles {
+        MusicFiles(self.0.files.clone())
+    }
+
+    pub fn new(version: MusicMetadataVersion, release: &Release, files: &MusicFiles) -> Self {
+        Self(core::music::MusicMetadata::new(version.into(), release.0.clone(), files.0.clone()))
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NativeScriptKind {
+    Sig,
+    All,
+    Any,
+    NOfK,
+    After,
+    Before,
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct NativeScript(core::native_script::NativeScript);
+
+#[wasm_bindgen]
+
+impl NativeScript {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        use core::serialization::ToBytes;
+        ToBytes::to_bytes(&self.0).map_err(|e| JsValue::from_str(&format!("to_bytes: {}", e)))
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<NativeScript, JsValue> {
+        use core::prelude::FromBytes;
+        FromBytes::from_bytes(data).map(Self).map_err(|e| decode_error("from_bytes", &e))
+    }
+
+    pub fn kind(&self) -> NativeScriptKind {
+        match &self.0 {
+            core::native_script::NativeScript::Sig(_) => NativeScriptKind::Sig,
+            core::native_script::NativeScript::All(_) => NativeScriptKind::All,
+            core::native_script::NativeScript::Any(_) => NativeScriptKind::Any,
+            core::native_script::NativeScript::NOfK { .. } => NativeScriptKind::NOfK,
+            core::native_script::NativeScript::After(_) => NativeScriptKind::After,
+            core::native_script::NativeScript::Before(_) => NativeScriptKind::Before,
+        }
+    }
+
+    pub fn policy_id(&self) -> PolicyIdV2 {
+        self.0.policy_id()
+    }
+
+    pub fn is_valid_at(&self, slot: u64) -> bool {
+        self.0.is_valid_at(slot)
+    }
+
+    // undefined when the script can still be satisfied at some point whatever the slot
+    pub fn locked_from(&self) -> Option<u64> {
+        self.0.locked_from()
+    }
+
+    pub fn is_locked_after(&self, slot: u64) -> bool {
+        self.0.is_locked_after(slot)
+    }
+
+    pub fn new_sig(key_hash: Vec<u8>) -> Result<NativeScript, JsValue> {
+        core::native_script::NativeScript::new_sig(key_hash).map(Self).map_err(|e| JsValue::from_str(&format!("new_sig: {}", e)))
+    }
+
+    pub fn new_all(native_scripts: &NativeScripts) -> Self {
+        Self(core::native_script::NativeScript::new_all(native_scripts.0.clone()))
+    }
+
+    pub fn new_any(native_scripts: &NativeScripts) -> Self {
+        Self(core::native_script::NativeScript::new_any(native_scripts.0.clone()))
+    }
+
+    pub fn new_n_of_k(n: u64, native_scripts: &NativeScripts) -> Self {
+        Self(core::native_script::NativeScript::new_n_of_k(n, native_scripts.0.clone()))
+    }
+
+    pub fn new_after(slot: u64) -> Self {
+        Self(core::native_script::NativeScript::new_after(slot))
+    }
+
+    pub fn new_before(slot: u64) -> Self {
+        Self(core::native_script::NativeScript::new_before(slot))
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct NativeScripts(Vec<core::native_script::NativeScript>);
+
+#[wasm_bindgen]
+
+impl NativeScripts {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> NativeScript {
+        NativeScript(self.0[index].clone())
+    }
+
+    pub fn add(&mut self, elem: &NativeScript) {
+        self.0.push(elem.0.clone());
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct PolicyCheck(core::native_script::PolicyCheck);
+
+#[wasm_bindgen]
+
+impl PolicyCheck {
+    // text for version 1, hex for version 2
+    pub fn policy_id(&self) -> String {
+        self.0.policy_id.clone()
+    }
+
+    // undefined when the policy isn't derived from any of the given scripts
+    pub fn native_script(&self) -> Option<NativeScript> {
+        self.0.native_script.clone().map(NativeScript)
+    }
+
+    pub fn locked(&self) -> bool {
+        self.0.locked
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct PolicyChecks(Vec<core::native_script::PolicyCheck>);
+
+#[wasm_bindgen]
+
+impl PolicyChecks {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> PolicyCheck {
+        PolicyCheck(self.0[index].clone())
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct Mint(core::mint::Mint);
+
+#[wasm_bindgen]
+
+impl Mint {
+    pub fn new() -> Self {
+        Self(core::mint::Mint::new())
+    }
+
+    // negative quantities are burns
+    pub fn insert(&mut self, policy_id: PolicyIdV2, asset_name: AssetNameV2, quantity: i64) -> Option<i64> {
+        self.0.entry(policy_id).or_default().insert(asset_name, quantity)
+    }
+
+    pub fn get(&self, policy_id: PolicyIdV2, asset_name: AssetNameV2) -> Option<i64> {
+        self.0.get(&policy_id).and_then(|assets| assets.get(&asset_name)).copied()
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MintIssueKind {
+    NotMinted,
+    MissingMetadata,
+    BurnWithMetadata,
+    QuantityAboveOne,
+}
+
+impl From<core::mint::MintIssueKind> for MintIssueKind {
+    fn from(native: core::mint::MintIssueKind) -> Self {
+        match native {
+            core::mint::MintIssueKind::NotMinted => MintIssueKind::NotMinted,
+            core::mint::MintIssueKind::MissingMetadata => MintIssueKind::MissingMetadata,
+            core::mint::MintIssueKind::BurnWithMetadata => MintIssueKind::BurnWithMetadata,
+            core::mint::MintIssueKind::QuantityAboveOne => MintIssueKind::QuantityAboveOne,
+        }
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct MintIssue(core::mint::MintIssue);
+
+#[wasm_bindgen]
+
+impl MintIssue {
+    pub fn policy_id(&self) -> PolicyIdV2 {
+        self.0.policy_id.clone()
+    }
+
+    pub fn asset_name(&self) -> AssetNameV2 {
+        self.0.asset_name.clone()
+    }
+
+    pub fn quantity(&self) -> i64 {
+        self.0.quantity
+    }
+
+    pub fn kind(&self) -> MintIssueKind {
+        self.0.kind.into()
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct MintIssues(Vec<core::mint::MintIssue>);
+
+#[wasm_bindgen]
+
+impl MintIssues {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> MintIssue {
+        MintIssue(self.0[index].clone())
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct AssetName(core::AssetNameV2);
+
+#[wasm_bindgen]
+
+impl AssetName {
+    pub fn new(bytes: AssetNameV2) -> Self {
+        Self(bytes)
+    }
+
+    pub fn from_v1(asset_name: &String64) -> Self {
+        Self(asset_name.0.get().as_bytes().to_vec())
+    }
+
+    pub fn bytes(&self) -> AssetNameV2 {
+        self.0.clone()
+    }
+
+    // the name after any CIP-67 label, as text when printable UTF-8 and as hex otherwise
+    pub fn display_name(&self) -> String {
+        use core::asset_name::AssetNameDisplay;
+        self.0.display_name()
+    }
+
+    pub fn to_hex(&self) -> String {
+        use core::asset_name::AssetNameDisplay;
+        AssetNameDisplay::to_hex(self.0.as_slice())
+    }
+
+    pub fn escaped(&self) -> String {
+        use core::asset_name::AssetNameDisplay;
+        self.0.escaped()
+    }
+}
+
+#[wasm_bindgen]
+
+#[derive(Clone, Debug)]
+pub struct AssetId(core::asset_name::AssetId);
+
+#[wasm_bindgen]
+
+impl AssetId {
+    pub fn new(policy_id: PolicyIdV2, asset_name: AssetNameV2) -> Self {
+        Self(core::asset_name::AssetId::new(policy_id, asset_name))
+    }
+
+    // the hex policy id followed by the hex asset name, with or without a "." in between
+    pub fn from_hex(asset_id: &str) -> Result<AssetId, JsValue> {
+        core::asset_name::AssetId::from_hex(asset_id).map(Self).map_err(|e| decode_error("from_hex", &e))
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.to_hex()
+    }
+
+    pub fn policy_id(&self) -> PolicyIdV2 {
+        self.0.policy_id.clone()
+    }
+
+    pub fn asset_name(&self) -> AssetName {
+        AssetName(self.0.asset_name.clone())
+    }
+}